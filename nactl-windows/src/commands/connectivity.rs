@@ -0,0 +1,525 @@
+//! Internet connectivity assessment modeled on Windows NCSI
+//!
+//! Walks the same ladder NCSI does: local address, default gateway, DNS
+//! resolution of the probe host, and finally an HTTP fetch of a well-known
+//! file whose content must match exactly. A probe that answers with the
+//! wrong content or a redirect indicates a captive portal.
+
+use crate::commands::ping;
+use crate::errors::NactlError;
+use crate::utils::http::{self, HttpResponse, Url};
+use crate::utils::validation;
+use regex::Regex;
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default probe URL (same endpoint Windows NCSI uses)
+pub const DEFAULT_PROBE_URL: &str = "http://www.msftconnecttest.com/connecttest.txt";
+
+/// Body the default probe URL returns on an unfiltered connection
+pub const DEFAULT_PROBE_BODY: &str = "Microsoft Connect Test";
+
+/// Probe endpoint and expectations
+#[derive(Debug, Clone)]
+pub struct ProbeConfig {
+    pub url: Url,
+    pub expected_body: String,
+    pub timeout_ms: u32,
+}
+
+impl ProbeConfig {
+    pub fn new(url: &str, expected_body: &str, timeout_ms: u32) -> Result<Self, NactlError> {
+        let url = Url::parse(url)?;
+        validation::validate_hostname(&url.host)?;
+
+        Ok(Self {
+            url,
+            expected_body: expected_body.to_string(),
+            timeout_ms,
+        })
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.max(1) as u64)
+    }
+
+    fn display_url(&self) -> String {
+        format!(
+            "http://{}:{}{}",
+            self.url.host, self.url.port, self.url.path
+        )
+    }
+}
+
+/// Highest level of connectivity reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectivityLevel {
+    /// No usable IP address
+    None,
+    /// IP address assigned but gateway unreachable
+    Local,
+    /// Gateway reachable but name resolution fails
+    Gateway,
+    /// Names resolve but the HTTP probe fails
+    Dns,
+    /// HTTP probe returned the expected content
+    Internet,
+    /// HTTP probe was redirected or returned different content
+    CaptivePortal,
+}
+
+/// Outcome of a single assessment stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageResult {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectivityStage {
+    pub stage: String,
+    pub result: StageResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ConnectivityStage {
+    fn new(
+        stage: &str,
+        result: StageResult,
+        latency_ms: Option<f64>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            stage: stage.to_string(),
+            result,
+            latency_ms,
+            detail: Some(detail.into()),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.result == StageResult::Pass
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Connectivity {
+    pub level: ConnectivityLevel,
+    pub probe_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub portal_url: Option<String>,
+    pub stages: Vec<ConnectivityStage>,
+}
+
+/// How the HTTP probe response compares to what was expected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeVerdict {
+    /// Expected status and body
+    Expected,
+    /// Redirected elsewhere (location, if any)
+    Redirected(Option<String>),
+    /// Answered with different content
+    Unexpected(String),
+    /// No HTTP response at all
+    Unreachable(String),
+}
+
+/// Run every stage and determine the connectivity level
+pub fn assess(
+    ip_address: Option<&str>,
+    gateway: Option<&str>,
+    config: &ProbeConfig,
+) -> Connectivity {
+    let local = check_local(ip_address);
+
+    let gateway_stage = match gateway {
+        Some(gw) => match ping::probe(gw, config.timeout_ms) {
+            Some(rtt) => ConnectivityStage::new(
+                "gateway",
+                StageResult::Pass,
+                Some(rtt),
+                format!("Gateway {} replied", gw),
+            ),
+            None => ConnectivityStage::new(
+                "gateway",
+                StageResult::Fail,
+                None,
+                format!("Gateway {} did not reply", gw),
+            ),
+        },
+        None => ConnectivityStage::new("gateway", StageResult::Fail, None, "No default gateway"),
+    };
+
+    let internet = probe_internet(config);
+
+    let level = classify(
+        local.passed(),
+        gateway_stage.passed(),
+        internet.dns.passed(),
+        &internet.verdict,
+    );
+
+    Connectivity {
+        level,
+        probe_url: config.display_url(),
+        portal_url: internet.portal_url,
+        stages: vec![local, gateway_stage, internet.dns, internet.http],
+    }
+}
+
+/// Result of resolving the probe host and fetching the probe URL
+pub struct InternetProbe {
+    pub dns: ConnectivityStage,
+    pub http: ConnectivityStage,
+    pub verdict: ProbeVerdict,
+    pub portal_url: Option<String>,
+}
+
+/// Run only the DNS and HTTP stages of the assessment
+pub fn probe_internet(config: &ProbeConfig) -> InternetProbe {
+    let (dns, addr) = resolve_probe_host(config);
+
+    let (http, verdict, portal_url) = match addr {
+        Some(addr) => {
+            let response = http::get_from(addr, &config.url, config.timeout());
+            let verdict = check_response(&response, 200, Some(&config.expected_body));
+            let portal_url = match (&verdict, &response) {
+                (ProbeVerdict::Expected | ProbeVerdict::Unreachable(_), _) => None,
+                (_, Ok(resp)) => find_login_url(resp),
+                (_, Err(_)) => None,
+            };
+            (http_stage(&response, &verdict), verdict, portal_url)
+        }
+        None => (
+            ConnectivityStage::new(
+                "http",
+                StageResult::Skipped,
+                None,
+                "Probe host did not resolve",
+            ),
+            ProbeVerdict::Unreachable("Probe host did not resolve".to_string()),
+            None,
+        ),
+    };
+
+    InternetProbe {
+        dns,
+        http,
+        verdict,
+        portal_url,
+    }
+}
+
+/// Compare a probe response against the expected status and body
+pub fn check_response(
+    response: &Result<HttpResponse, NactlError>,
+    expected_status: u16,
+    expected_body: Option<&str>,
+) -> ProbeVerdict {
+    let resp = match response {
+        Ok(resp) => resp,
+        Err(e) => return ProbeVerdict::Unreachable(e.response.message.clone()),
+    };
+
+    if resp.status != expected_status && (300..400).contains(&resp.status) {
+        return ProbeVerdict::Redirected(resp.redirect_location().map(String::from));
+    }
+
+    if resp.status != expected_status {
+        return ProbeVerdict::Unexpected(format!(
+            "Probe returned HTTP {} (expected {})",
+            resp.status, expected_status
+        ));
+    }
+
+    match expected_body {
+        Some(body) if resp.body.trim() != body.trim() => {
+            ProbeVerdict::Unexpected("Probe returned unexpected content".to_string())
+        }
+        // An expected-empty response (e.g. HTTP 204) that carries a body was
+        // injected somewhere along the way
+        None if expected_status == 204 && !resp.body.trim().is_empty() => {
+            ProbeVerdict::Unexpected("Probe returned content for a 204 endpoint".to_string())
+        }
+        _ => ProbeVerdict::Expected,
+    }
+}
+
+/// Extract a portal login URL from a redirect or injected page
+///
+/// Checks the Location header, WISPr `<LoginURL>`, meta refresh tags,
+/// script-driven redirects and finally the first form action.
+pub fn find_login_url(response: &HttpResponse) -> Option<String> {
+    if let Some(location) = response.redirect_location() {
+        return Some(location.to_string());
+    }
+
+    let patterns = [
+        r"(?is)<LoginURL>\s*(?:<!\[CDATA\[)?\s*([^<\]\s]+)",
+        r#"(?i)<meta[^>]+http-equiv=["']?refresh["']?[^>]*content=["']?\d+\s*;\s*url=['"]?([^"'>\s]+)"#,
+        r#"(?i)(?:window\.|document\.)?location(?:\.href)?\s*=\s*["']([^"']+)["']"#,
+        r#"(?i)<form[^>]+action=["'](https?://[^"']+)["']"#,
+    ];
+
+    patterns.iter().find_map(|pattern| {
+        Regex::new(pattern)
+            .unwrap()
+            .captures(&response.body)
+            .map(|caps| caps[1].replace("&amp;", "&"))
+    })
+}
+
+/// Highest level reached given each stage's outcome
+pub fn classify(
+    local: bool,
+    gateway: bool,
+    dns: bool,
+    verdict: &ProbeVerdict,
+) -> ConnectivityLevel {
+    match verdict {
+        ProbeVerdict::Expected => ConnectivityLevel::Internet,
+        ProbeVerdict::Redirected(_) | ProbeVerdict::Unexpected(_) => {
+            ConnectivityLevel::CaptivePortal
+        }
+        ProbeVerdict::Unreachable(_) if dns => ConnectivityLevel::Dns,
+        ProbeVerdict::Unreachable(_) if gateway => ConnectivityLevel::Gateway,
+        ProbeVerdict::Unreachable(_) if local => ConnectivityLevel::Local,
+        ProbeVerdict::Unreachable(_) => ConnectivityLevel::None,
+    }
+}
+
+/// Whether an IPv4 address is an APIPA (169.254.0.0/16) self-assigned address
+pub fn is_apipa(ip: &str) -> bool {
+    ip.starts_with("169.254.")
+}
+
+fn check_local(ip_address: Option<&str>) -> ConnectivityStage {
+    match ip_address {
+        Some(ip) if is_apipa(ip) => ConnectivityStage::new(
+            "local",
+            StageResult::Fail,
+            None,
+            format!("Self-assigned address {} (DHCP failed)", ip),
+        ),
+        Some(ip) => ConnectivityStage::new(
+            "local",
+            StageResult::Pass,
+            None,
+            format!("Address {} assigned", ip),
+        ),
+        None => ConnectivityStage::new("local", StageResult::Fail, None, "No IPv4 address"),
+    }
+}
+
+fn resolve_probe_host(config: &ProbeConfig) -> (ConnectivityStage, Option<SocketAddr>) {
+    if let Ok(ip) = config.url.host.parse::<IpAddr>() {
+        return (
+            ConnectivityStage::new(
+                "dns",
+                StageResult::Skipped,
+                None,
+                "Probe host is an IP address",
+            ),
+            Some(SocketAddr::new(ip, config.url.port)),
+        );
+    }
+
+    match resolve_host(&config.url.host, config.url.port, config.timeout()) {
+        Ok((addrs, latency)) => (
+            ConnectivityStage::new(
+                "dns",
+                StageResult::Pass,
+                Some(latency),
+                format!("{} resolved to {}", config.url.host, addrs[0].ip()),
+            ),
+            Some(addrs[0]),
+        ),
+        Err(detail) => (
+            ConnectivityStage::new("dns", StageResult::Fail, None, detail),
+            None,
+        ),
+    }
+}
+
+/// Resolve a host on a worker thread so a hung resolver cannot stall the
+/// caller past `timeout`. Returns the addresses and the lookup time.
+pub fn resolve_host(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<(Vec<SocketAddr>, f64), String> {
+    let owned_host = host.to_string();
+    let start = Instant::now();
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let result = (owned_host.as_str(), port)
+            .to_socket_addrs()
+            .map(|addrs| addrs.collect::<Vec<_>>());
+        let _ = tx.send(result);
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok(Ok(addrs)) if !addrs.is_empty() => Ok((addrs, elapsed_ms(start))),
+        Ok(Ok(_)) => Err(format!("{} returned no addresses", host)),
+        Ok(Err(e)) => Err(format!("Failed to resolve {}: {}", host, e)),
+        Err(_) => Err(format!("Timed out resolving {}", host)),
+    }
+}
+
+fn http_stage(
+    response: &Result<HttpResponse, NactlError>,
+    verdict: &ProbeVerdict,
+) -> ConnectivityStage {
+    let latency = response.as_ref().ok().map(|r| r.elapsed_ms);
+
+    match verdict {
+        ProbeVerdict::Expected => {
+            ConnectivityStage::new("http", StageResult::Pass, latency, "Probe content matched")
+        }
+        ProbeVerdict::Redirected(Some(location)) => ConnectivityStage::new(
+            "http",
+            StageResult::Fail,
+            latency,
+            format!("Probe redirected to {}", location),
+        ),
+        ProbeVerdict::Redirected(None) => {
+            ConnectivityStage::new("http", StageResult::Fail, latency, "Probe redirected")
+        }
+        ProbeVerdict::Unexpected(detail) | ProbeVerdict::Unreachable(detail) => {
+            ConnectivityStage::new("http", StageResult::Fail, latency, detail.clone())
+        }
+    }
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::http::stand_in_server;
+    use std::net::TcpListener;
+
+    fn config_for(port: u16) -> ProbeConfig {
+        let url = format!("http://127.0.0.1:{}/connecttest.txt", port);
+        ProbeConfig::new(&url, DEFAULT_PROBE_BODY, 2000).unwrap()
+    }
+
+    #[test]
+    fn test_assess_internet() {
+        let port = stand_in_server(
+            "HTTP/1.1 200 OK\r\nContent-Length: 22\r\nConnection: close\r\n\r\nMicrosoft Connect Test",
+        );
+        let result = assess(Some("192.168.1.20"), None, &config_for(port));
+
+        assert_eq!(result.level, ConnectivityLevel::Internet);
+        assert_eq!(result.stages.len(), 4);
+        assert_eq!(result.stages[3].result, StageResult::Pass);
+        assert!(result.stages[3].latency_ms.is_some());
+    }
+
+    #[test]
+    fn test_assess_captive_portal_redirect() {
+        let port = stand_in_server(
+            "HTTP/1.1 302 Found\r\nLocation: http://login.hotel.example/portal\r\nContent-Length: 0\r\n\r\n",
+        );
+        let result = assess(Some("10.0.0.5"), None, &config_for(port));
+
+        assert_eq!(result.level, ConnectivityLevel::CaptivePortal);
+        assert_eq!(
+            result.portal_url.as_deref(),
+            Some("http://login.hotel.example/portal")
+        );
+    }
+
+    #[test]
+    fn test_assess_captive_portal_content() {
+        let port = stand_in_server(
+            "HTTP/1.1 200 OK\r\nContent-Length: 26\r\n\r\n<html>Please log in</html>",
+        );
+        let result = assess(Some("10.0.0.5"), None, &config_for(port));
+
+        assert_eq!(result.level, ConnectivityLevel::CaptivePortal);
+    }
+
+    #[test]
+    fn test_assess_http_unreachable() {
+        // Bind and drop to get a port with nothing listening
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let result = assess(Some("169.254.10.2"), None, &config_for(port));
+
+        assert_eq!(result.stages[0].result, StageResult::Fail);
+        assert_eq!(result.stages[3].result, StageResult::Fail);
+        assert_eq!(result.level, ConnectivityLevel::None);
+    }
+
+    #[test]
+    fn test_find_login_url() {
+        let page = |body: &str| HttpResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: body.to_string(),
+            elapsed_ms: 0.0,
+        };
+
+        let meta = page(
+            r#"<meta http-equiv="refresh" content="0; url=http://10.1.1.1/login?a=1&amp;b=2">"#,
+        );
+        assert_eq!(
+            find_login_url(&meta).as_deref(),
+            Some("http://10.1.1.1/login?a=1&b=2")
+        );
+
+        let wispr =
+            page("<WISPAccessGatewayParam><LoginURL>https://wifi.airport.example/auth</LoginURL>");
+        assert_eq!(
+            find_login_url(&wispr).as_deref(),
+            Some("https://wifi.airport.example/auth")
+        );
+
+        let script = page(r#"<script>window.location.href = "http://portal.local/";</script>"#);
+        assert_eq!(
+            find_login_url(&script).as_deref(),
+            Some("http://portal.local/")
+        );
+
+        assert_eq!(find_login_url(&page("Microsoft Connect Test")), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        let down = ProbeVerdict::Unreachable("timeout".to_string());
+        assert_eq!(classify(true, true, true, &down), ConnectivityLevel::Dns);
+        assert_eq!(
+            classify(true, true, false, &down),
+            ConnectivityLevel::Gateway
+        );
+        assert_eq!(
+            classify(true, false, false, &down),
+            ConnectivityLevel::Local
+        );
+        assert_eq!(
+            classify(false, false, false, &down),
+            ConnectivityLevel::None
+        );
+        assert_eq!(
+            classify(true, false, true, &ProbeVerdict::Expected),
+            ConnectivityLevel::Internet
+        );
+    }
+}
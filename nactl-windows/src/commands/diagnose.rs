@@ -0,0 +1,538 @@
+//! Layered network diagnosis command implementation
+//!
+//! Checks each layer from the adapter up to proxy configuration and stops at
+//! the first one that fails, recommending the nactl command most likely to
+//! fix it.
+
+use crate::commands::connectivity::{self, ProbeConfig, ProbeVerdict, StageResult};
+use crate::commands::{ping, proxy, status};
+use crate::errors::{ExitCodes, NactlError};
+use crate::utils::output::{print_output, OutputFormat};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// Diagnosis layers, bottom to top
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layer {
+    Adapter,
+    IpAddress,
+    Gateway,
+    DnsServer,
+    NameResolution,
+    Http,
+    Proxy,
+}
+
+impl Layer {
+    const ALL: [Layer; 7] = [
+        Layer::Adapter,
+        Layer::IpAddress,
+        Layer::Gateway,
+        Layer::DnsServer,
+        Layer::NameResolution,
+        Layer::Http,
+        Layer::Proxy,
+    ];
+}
+
+#[derive(Debug, Serialize)]
+pub struct LayerResult {
+    pub layer: Layer,
+    pub result: StageResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    pub evidence: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Recommendation {
+    pub command: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DiagnoseData {
+    healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failing_layer: Option<Layer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recommendation: Option<Recommendation>,
+    layers: Vec<LayerResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct DiagnoseResponse {
+    success: bool,
+    data: DiagnoseData,
+}
+
+/// Run the layered diagnosis
+pub fn execute(
+    probe: &ProbeConfig,
+    format: OutputFormat,
+    interface: Option<&str>,
+) -> Result<u8, NactlError> {
+    let status = status::collect(interface);
+    let timeout = probe.timeout_ms;
+
+    // The HTTP probe covers both name resolution and HTTP, so run it lazily
+    // only once the lower layers have passed
+    let mut internet: Option<connectivity::InternetProbe> = None;
+
+    let data = run_layers(|layer| match layer {
+        Layer::Adapter => check_adapter(&status),
+        Layer::IpAddress => check_ip(&status),
+        Layer::Gateway => check_gateway(&status, timeout),
+        Layer::DnsServer => check_dns_servers(&status, timeout),
+        Layer::NameResolution | Layer::Http => {
+            let probe_result = internet.get_or_insert_with(|| connectivity::probe_internet(probe));
+            if layer == Layer::NameResolution {
+                check_name_resolution(probe_result)
+            } else {
+                check_http(probe_result)
+            }
+        }
+        Layer::Proxy => check_proxy(timeout),
+    });
+
+    let response = DiagnoseResponse {
+        success: true,
+        data,
+    };
+
+    print_output(&response, format)?;
+    Ok(ExitCodes::Success as u8)
+}
+
+/// Check each layer bottom up with `check`, stopping at the first failure and
+/// marking the layers above it skipped
+fn run_layers<F>(mut check: F) -> DiagnoseData
+where
+    F: FnMut(Layer) -> LayerResult,
+{
+    let mut layers: Vec<LayerResult> = Vec::new();
+    let mut failing_layer: Option<Layer> = None;
+    let mut recommendation: Option<Recommendation> = None;
+
+    for layer in Layer::ALL {
+        if failing_layer.is_some() {
+            layers.push(LayerResult {
+                layer,
+                result: StageResult::Skipped,
+                latency_ms: None,
+                evidence: json!({ "reason": "A lower layer failed" }),
+            });
+            continue;
+        }
+
+        let result = check(layer);
+        if result.result == StageResult::Fail {
+            failing_layer = Some(layer);
+            recommendation = Some(recommend(layer, &result.evidence));
+        }
+        layers.push(result);
+    }
+
+    DiagnoseData {
+        healthy: failing_layer.is_none(),
+        failing_layer,
+        recommendation,
+        layers,
+    }
+}
+
+/// Remediation for a failing layer
+pub fn recommend(layer: Layer, evidence: &Value) -> Recommendation {
+    let (command, reason) = match layer {
+        Layer::Adapter => (
+            "nactl stack reset --level soft",
+            "Adapter is not connected; restart it and renew its lease",
+        ),
+        Layer::IpAddress => (
+            "nactl stack reset --level soft",
+            "No valid IP address from DHCP; release and renew the lease",
+        ),
+        Layer::Gateway => (
+            "nactl stack reset --level soft",
+            "Default gateway is unreachable; flush ARP and renew the lease",
+        ),
+        Layer::DnsServer => (
+            "nactl dns reset",
+            "Configured DNS servers are unreachable; return to DHCP-assigned servers",
+        ),
+        Layer::NameResolution => (
+            "nactl dns set 1.1.1.1 1.0.0.1",
+            "DNS servers answer but names do not resolve; switch to a public resolver",
+        ),
+        Layer::Http if evidence.get("captive_portal") == Some(&Value::Bool(true)) => (
+            "nactl portal check",
+            "Network is behind a captive portal; sign in through the login page",
+        ),
+        Layer::Http => (
+            "nactl stack reset --level hard",
+            "Names resolve but HTTP fails; reset Winsock and TCP/IP (requires reboot)",
+        ),
+        Layer::Proxy => (
+            "nactl proxy clear",
+            "Configured proxy is unreachable; clear the proxy settings",
+        ),
+    };
+
+    Recommendation {
+        command: command.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn pass_or_fail(passed: bool) -> StageResult {
+    if passed {
+        StageResult::Pass
+    } else {
+        StageResult::Fail
+    }
+}
+
+fn check_adapter(status: &status::NetworkStatus) -> LayerResult {
+    LayerResult {
+        layer: Layer::Adapter,
+        result: pass_or_fail(status.connected),
+        latency_ms: None,
+        evidence: json!({
+            "interface": status.interface,
+            "type": status.connection_type,
+            "connected": status.connected,
+            "ssid": status.ssid,
+        }),
+    }
+}
+
+fn check_ip(status: &status::NetworkStatus) -> LayerResult {
+    let apipa = status
+        .ip_address
+        .as_deref()
+        .map(connectivity::is_apipa)
+        .unwrap_or(false);
+
+    LayerResult {
+        layer: Layer::IpAddress,
+        result: pass_or_fail(status.ip_address.is_some() && !apipa),
+        latency_ms: None,
+        evidence: json!({
+            "ip_address": status.ip_address,
+            "subnet_mask": status.subnet_mask,
+            "apipa": apipa,
+        }),
+    }
+}
+
+fn check_gateway(status: &status::NetworkStatus, timeout: u32) -> LayerResult {
+    let rtt = status
+        .gateway
+        .as_deref()
+        .and_then(|gw| ping::probe(gw, timeout));
+
+    LayerResult {
+        layer: Layer::Gateway,
+        result: pass_or_fail(rtt.is_some()),
+        latency_ms: rtt,
+        evidence: json!({
+            "gateway": status.gateway,
+            "replied": rtt.is_some(),
+        }),
+    }
+}
+
+fn check_dns_servers(status: &status::NetworkStatus, timeout: u32) -> LayerResult {
+    let servers = status.dns_servers.clone().unwrap_or_default();
+
+    let results: Vec<Value> = servers
+        .iter()
+        .map(|server| {
+            let (reachable, latency_ms, method) = dns_server_reachable(server, timeout);
+            json!({
+                "server": server,
+                "reachable": reachable,
+                "latency_ms": latency_ms,
+                "method": method,
+            })
+        })
+        .collect();
+
+    let any_reachable = results
+        .iter()
+        .any(|r| r.get("reachable") == Some(&Value::Bool(true)));
+
+    let best_latency = results
+        .iter()
+        .filter_map(|r| r.get("latency_ms").and_then(Value::as_f64))
+        .reduce(f64::min);
+
+    LayerResult {
+        layer: Layer::DnsServer,
+        result: pass_or_fail(any_reachable),
+        latency_ms: best_latency,
+        evidence: json!({ "servers": results }),
+    }
+}
+
+/// DNS servers frequently drop ICMP, so fall back to a TCP connect on port 53
+fn dns_server_reachable(server: &str, timeout: u32) -> (bool, Option<f64>, &'static str) {
+    if let Some(rtt) = ping::probe(server, timeout) {
+        return (true, Some(rtt), "icmp");
+    }
+
+    let addr = match (server, 53)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut a| a.next())
+    {
+        Some(addr) => addr,
+        None => return (false, None, "tcp"),
+    };
+
+    match tcp_connect(addr, timeout) {
+        Some(rtt) => (true, Some(rtt), "tcp"),
+        None => (false, None, "tcp"),
+    }
+}
+
+fn check_name_resolution(probe: &connectivity::InternetProbe) -> LayerResult {
+    LayerResult {
+        layer: Layer::NameResolution,
+        result: probe.dns.result,
+        latency_ms: probe.dns.latency_ms,
+        evidence: json!({ "detail": probe.dns.detail }),
+    }
+}
+
+fn check_http(probe: &connectivity::InternetProbe) -> LayerResult {
+    let captive = matches!(
+        probe.verdict,
+        ProbeVerdict::Redirected(_) | ProbeVerdict::Unexpected(_)
+    );
+
+    LayerResult {
+        layer: Layer::Http,
+        result: probe.http.result,
+        latency_ms: probe.http.latency_ms,
+        evidence: json!({
+            "detail": probe.http.detail,
+            "captive_portal": captive,
+            "portal_url": probe.portal_url,
+        }),
+    }
+}
+
+fn check_proxy(timeout: u32) -> LayerResult {
+    let config = match proxy::read_proxy_config() {
+        Ok(config) => config,
+        Err(e) => {
+            return LayerResult {
+                layer: Layer::Proxy,
+                result: StageResult::Skipped,
+                latency_ms: None,
+                evidence: json!({ "detail": e.response.message }),
+            }
+        }
+    };
+
+    let mut targets: Vec<(String, String, u16)> = Vec::new();
+    for (name, endpoint) in [
+        ("http_proxy", &config.http_proxy),
+        ("https_proxy", &config.https_proxy),
+        ("socks_proxy", &config.socks_proxy),
+    ] {
+        if let (true, Some(server)) = (endpoint.enabled, &endpoint.server) {
+            targets.push((
+                name.to_string(),
+                server.clone(),
+                endpoint.port.unwrap_or(8080),
+            ));
+        }
+    }
+    if let Some((host, port)) = config.auto_config_url.as_deref().and_then(url_host_port) {
+        targets.push(("auto_config_url".to_string(), host, port));
+    }
+
+    if targets.is_empty() {
+        return LayerResult {
+            layer: Layer::Proxy,
+            result: StageResult::Pass,
+            latency_ms: None,
+            evidence: json!({ "proxy_configured": false }),
+        };
+    }
+
+    let checks: Vec<Value> = targets
+        .iter()
+        .map(|(name, host, port)| {
+            let rtt = (host.as_str(), *port)
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut a| a.next())
+                .and_then(|addr| tcp_connect(addr, timeout));
+            json!({
+                "setting": name,
+                "server": host,
+                "port": port,
+                "reachable": rtt.is_some(),
+                "latency_ms": rtt,
+            })
+        })
+        .collect();
+
+    let all_reachable = checks
+        .iter()
+        .all(|c| c.get("reachable") == Some(&Value::Bool(true)));
+
+    LayerResult {
+        layer: Layer::Proxy,
+        result: pass_or_fail(all_reachable),
+        latency_ms: None,
+        evidence: json!({ "proxy_configured": true, "endpoints": checks }),
+    }
+}
+
+/// Host and port of a PAC URL such as `http://wpad.corp.local/proxy.pac`
+fn url_host_port(url: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let default_port = if scheme.eq_ignore_ascii_case("https") {
+        443
+    } else {
+        80
+    };
+    let authority = rest.split('/').next()?;
+
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Some((host.to_string(), port.parse().ok()?)),
+        None if !authority.is_empty() => Some((authority.to_string(), default_port)),
+        None => None,
+    }
+}
+
+fn tcp_connect(addr: SocketAddr, timeout: u32) -> Option<f64> {
+    let start = Instant::now();
+    TcpStream::connect_timeout(&addr, Duration::from_millis(timeout.max(1) as u64))
+        .ok()
+        .map(|_| start.elapsed().as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected_status(ip: &str) -> status::NetworkStatus {
+        status::NetworkStatus {
+            connected: true,
+            interface: Some("Wi-Fi".to_string()),
+            ip_address: Some(ip.to_string()),
+            subnet_mask: Some("255.255.255.0".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn passing(layer: Layer) -> LayerResult {
+        LayerResult {
+            layer,
+            result: StageResult::Pass,
+            latency_ms: None,
+            evidence: json!({}),
+        }
+    }
+
+    fn results(data: &DiagnoseData) -> Vec<StageResult> {
+        data.layers.iter().map(|l| l.result).collect()
+    }
+
+    #[test]
+    fn test_adapter_down_skips_later_layers() {
+        let status = status::NetworkStatus {
+            connected: false,
+            ..Default::default()
+        };
+        let mut checked = Vec::new();
+        let data = run_layers(|layer| {
+            checked.push(layer);
+            match layer {
+                Layer::Adapter => check_adapter(&status),
+                _ => passing(layer),
+            }
+        });
+
+        assert_eq!(checked, vec![Layer::Adapter]);
+        assert!(!data.healthy);
+        assert_eq!(data.failing_layer, Some(Layer::Adapter));
+        assert_eq!(data.layers.len(), Layer::ALL.len());
+        assert_eq!(data.layers[0].result, StageResult::Fail);
+        assert!(data.layers[1..]
+            .iter()
+            .all(|l| l.result == StageResult::Skipped));
+        assert_eq!(
+            data.recommendation.unwrap().command,
+            "nactl stack reset --level soft"
+        );
+    }
+
+    #[test]
+    fn test_apipa_fails_ip_address() {
+        let status = connected_status("169.254.12.34");
+        let data = run_layers(|layer| match layer {
+            Layer::Adapter => check_adapter(&status),
+            Layer::IpAddress => check_ip(&status),
+            _ => passing(layer),
+        });
+
+        assert_eq!(data.failing_layer, Some(Layer::IpAddress));
+        assert_eq!(data.layers[1].evidence["apipa"], json!(true));
+        assert_eq!(
+            results(&data)[..3],
+            [StageResult::Pass, StageResult::Fail, StageResult::Skipped]
+        );
+
+        let no_address = status::NetworkStatus {
+            ip_address: None,
+            ..connected_status("0.0.0.0")
+        };
+        assert_eq!(check_ip(&no_address).result, StageResult::Fail);
+    }
+
+    #[test]
+    fn test_all_layers_pass() {
+        let status = connected_status("192.168.1.20");
+        let data = run_layers(|layer| match layer {
+            Layer::Adapter => check_adapter(&status),
+            Layer::IpAddress => check_ip(&status),
+            _ => passing(layer),
+        });
+
+        assert!(data.healthy);
+        assert_eq!(data.failing_layer, None);
+        assert_eq!(data.recommendation, None);
+        assert!(results(&data).iter().all(|r| *r == StageResult::Pass));
+    }
+
+    #[test]
+    fn test_recommend_http_captive_portal() {
+        let portal = recommend(Layer::Http, &json!({ "captive_portal": true }));
+        assert_eq!(portal.command, "nactl portal check");
+
+        let blocked = recommend(Layer::Http, &json!({ "captive_portal": false }));
+        assert_eq!(blocked.command, "nactl stack reset --level hard");
+    }
+
+    #[test]
+    fn test_url_host_port() {
+        assert_eq!(
+            url_host_port("http://wpad.corp.local/proxy.pac"),
+            Some(("wpad.corp.local".to_string(), 80))
+        );
+        assert_eq!(
+            url_host_port("https://pac.example.com:8443/p.pac"),
+            Some(("pac.example.com".to_string(), 8443))
+        );
+        assert_eq!(url_host_port("not a url"), None);
+    }
+}
@@ -1,12 +1,50 @@
 //! DNS management command implementations
 
+use crate::commands::ping::IpFamily;
+use crate::commands::status;
 use crate::errors::{ExitCodes, NactlError};
 use crate::utils::admin;
+use crate::utils::dns::{self as wire, Flags, Record, RecordType, Transport};
 use crate::utils::netsh;
 use crate::utils::output::{print_output, OutputFormat};
-use crate::utils::validation;
+use crate::utils::{stats, validation};
+use regex::Regex;
 use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, SocketAddr};
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+/// Default time to wait for a DNS answer in milliseconds
+pub const DEFAULT_LOOKUP_TIMEOUT_MS: u32 = 3000;
+
+/// Public resolvers benchmarked alongside the configured ones: Cloudflare,
+/// Google, Quad9 and OpenDNS
+pub const DEFAULT_BENCHMARK_SERVERS: &str =
+    "1.1.1.1,1.0.0.1,8.8.8.8,8.8.4.4,9.9.9.9,149.112.112.112,208.67.222.222,208.67.220.220";
+
+/// Popular names most resolvers already have cached
+const CACHED_NAMES: [&str; 5] = [
+    "google.com",
+    "microsoft.com",
+    "cloudflare.com",
+    "amazon.com",
+    "wikipedia.org",
+];
+
+/// Zones without wildcards: a random label under them must be NXDOMAIN,
+/// and is never cached, so the resolver has to recurse
+const UNCACHED_ZONES: [&str; 3] = ["example.com", "example.net", "example.org"];
+
+/// Failure rate (percent) above which a server is not recommended
+const MAX_RECOMMENDED_FAILURE_RATE: f64 = 10.0;
+
+/// Unanswered queries in a row after which a server is given up on, so a
+/// firewalled server does not cost the full timeout for every query
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
 
 #[derive(Debug, Serialize)]
 struct FlushResponse {
@@ -17,9 +55,56 @@ struct FlushResponse {
 #[derive(Debug, Serialize)]
 struct SetDnsData {
     interface: String,
-    primary: String,
+    /// Servers asked for, per family, in order
+    requested: FamilyServers,
+    /// Configuration before the change
+    previous: FamilyServers,
+    /// Configuration read back afterwards (the previous one after a rollback),
+    /// or null when it could not be read
+    applied: Option<FamilyServers>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_back_error: Option<String>,
+    /// Every netsh command run, in order
+    steps: Vec<SetStep>,
+    /// The read-back configuration matches the request
+    verified: bool,
+    rolled_back: bool,
+}
+
+/// Server lists for the two address families
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+struct FamilyServers {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ipv4: Option<ServerList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ipv6: Option<ServerList>,
+}
+
+impl FamilyServers {
+    fn of(interface: &InterfaceDns) -> Self {
+        Self {
+            ipv4: interface.ipv4.clone(),
+            ipv6: interface.ipv6.clone(),
+        }
+    }
+
+    fn get(&self, family: IpFamily) -> Option<&ServerList> {
+        match family {
+            IpFamily::Ipv4 => self.ipv4.as_ref(),
+            IpFamily::Ipv6 => self.ipv6.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct SetStep {
+    command: String,
+    success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    secondary: Option<String>,
+    error: Option<String>,
+    /// Run to restore the previous configuration
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    rollback: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -33,6 +118,157 @@ struct SetDnsResponse {
 struct ResetResponse {
     success: bool,
     message: String,
+    data: ResetDnsData,
+}
+
+#[derive(Debug, Serialize)]
+struct ResetDnsData {
+    interface: String,
+    /// Every netsh command run, in order
+    steps: Vec<SetStep>,
+    /// Configuration read back afterwards, or null when it could not be read
+    applied: Option<FamilyServers>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_back_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct LookupData {
+    name: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    /// Server that answered, as `address:port`
+    server: String,
+    transport: Transport,
+    response_code: String,
+    flags: Flags,
+    latency_ms: f64,
+    answers: Vec<Record>,
+    /// SOA or NS records explaining an empty answer
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authority: Vec<Record>,
+}
+
+#[derive(Debug, Serialize)]
+struct LookupResponse {
+    success: bool,
+    data: LookupData,
+}
+
+#[derive(Debug, Serialize)]
+struct ServerBenchmark {
+    server: String,
+    #[serde(skip)]
+    address: SocketAddr,
+    /// One of the interface's current DNS servers
+    configured: bool,
+    rank: u32,
+    queries: u32,
+    failures: u32,
+    failure_rate: f64,
+    median_ms: Option<f64>,
+    p95_ms: Option<f64>,
+    cached_median_ms: Option<f64>,
+    uncached_median_ms: Option<f64>,
+    /// Answered a name that does not exist, typically with an ad or
+    /// search page: the server rewrites NXDOMAIN
+    nxdomain_hijack: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchmarkData {
+    rounds: u32,
+    cached_names: Vec<String>,
+    uncached_zones: Vec<String>,
+    /// Best first
+    servers: Vec<ServerBenchmark>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recommended: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recommended_command: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchmarkResponse {
+    success: bool,
+    data: BenchmarkData,
+}
+
+/// Where an interface's DNS servers come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsSource {
+    Static,
+    Dhcp,
+}
+
+/// An interface's servers for one address family, in order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServerList {
+    pub source: DnsSource,
+    pub servers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InterfaceDns {
+    pub interface: String,
+    /// Absent when the protocol is not bound to the interface
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv4: Option<ServerList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6: Option<ServerList>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_suffix: Option<String>,
+    /// Register this connection's addresses in DNS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub register_in_dns: Option<bool>,
+    /// Register under the connection-specific suffix too, not just the
+    /// primary one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub register_connection_suffix: Option<bool>,
+}
+
+/// Machine-wide and per-interface DNS client settings
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DnsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_suffix: Option<String>,
+    /// Suffixes appended to single-label names, in order
+    pub search_list: Vec<String>,
+    pub interfaces: Vec<InterfaceDns>,
+}
+
+#[derive(Debug, Serialize)]
+struct DnsGetResponse {
+    success: bool,
+    data: DnsConfig,
+}
+
+/// One interface's entry in `netsh interface ipv4|ipv6 show dnsservers`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct NetshDns {
+    interface: String,
+    list: ServerList,
+    /// (register in DNS, register the connection-specific suffix)
+    register: Option<(bool, bool)>,
+}
+
+/// Global and per-adapter suffixes from `ipconfig /all`
+#[derive(Debug, Default, PartialEq, Eq)]
+struct IpconfigSuffixes {
+    primary_suffix: Option<String>,
+    search_list: Vec<String>,
+    connection_suffixes: HashMap<String, String>,
+}
+
+/// One timed query in a benchmark
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    cached: bool,
+    /// `None` when the query failed (timeout, SERVFAIL, REFUSED, ...)
+    latency_ms: Option<f64>,
+    /// A random uncached name came back with records
+    hijacked: bool,
 }
 
 /// Flush DNS resolver cache
@@ -68,18 +304,15 @@ pub fn flush(format: OutputFormat) -> Result<u8, NactlError> {
     }
 }
 
-/// Set custom DNS servers
+/// Set an ordered list of DNS servers (IPv4 and/or IPv6). Only the families
+/// present in `servers` are changed. The result is read back, and on any
+/// failure the previous servers are restored.
 pub fn set(
-    primary: &str,
-    secondary: Option<&str>,
+    servers: &[String],
     format: OutputFormat,
     interface: Option<&str>,
 ) -> Result<u8, NactlError> {
-    // Validate IP addresses
-    validation::validate_ip_address(primary)?;
-    if let Some(sec) = secondary {
-        validation::validate_ip_address(sec)?;
-    }
+    let requested = parse_server_list(servers)?;
 
     // Check for admin privileges
     if !admin::is_elevated() {
@@ -91,37 +324,296 @@ pub fn set(
     // Get the interface name (default to Wi-Fi)
     let iface = interface.unwrap_or("Wi-Fi");
 
-    // Set primary DNS
-    // netsh interface ip set dns "Wi-Fi" static 1.1.1.1
-    let result = netsh::run_command(&["interface", "ip", "set", "dns", iface, "static", primary]);
+    let before = find_interface(&read_config()?, iface)?;
+    check_families(&requested, &before)?;
+    let previous = FamilyServers::of(&before);
+    let register = register_mode(&before);
 
-    if let Err(e) = result {
-        // Check if interface not found
-        let err_str = e.to_string();
-        if err_str.contains("not found") || err_str.contains("does not exist") {
-            return Err(NactlError::interface_not_found(iface));
+    let mut steps = Vec::new();
+    let mut touched = Vec::new();
+    let mut applied_ok = true;
+    for family in [IpFamily::Ipv4, IpFamily::Ipv6] {
+        let Some(list) = requested.get(family) else {
+            continue;
+        };
+        touched.push(family);
+        let plan = set_commands(&before.interface, family, &list.servers, register);
+        if !run_steps(&plan, false, &mut steps, netsh::run_command) {
+            applied_ok = false;
+            break;
         }
-        return Err(e);
     }
 
-    // Set secondary DNS if provided
-    if let Some(sec) = secondary {
-        // netsh interface ip add dns "Wi-Fi" 1.0.0.1 index=2
-        let _ = netsh::run_command(&["interface", "ip", "add", "dns", iface, sec, "index=2"]);
+    // A read-back failure counts as unverified, so it still rolls back
+    let mut after = read_back(iface);
+    let verified = applied_ok
+        && after
+            .as_ref()
+            .is_ok_and(|after| matches_request(&requested, after));
+
+    let rolled_back = !verified;
+    if rolled_back {
+        for &family in &touched {
+            let plan = restore_commands(&before.interface, family, previous.get(family), register);
+            run_steps(&plan, true, &mut steps, netsh::run_command);
+        }
+        after = read_back(iface);
     }
 
+    let message = if verified {
+        "DNS servers updated".to_string()
+    } else if !applied_ok {
+        "Setting DNS servers failed; previous servers restored".to_string()
+    } else if after.is_err() {
+        "DNS configuration could not be read back to verify; previous servers restored".to_string()
+    } else {
+        "DNS servers did not match the request after applying; previous servers restored"
+            .to_string()
+    };
+
     let response = SetDnsResponse {
-        success: true,
-        message: "DNS servers updated".to_string(),
+        success: verified,
+        message,
         data: SetDnsData {
-            interface: iface.to_string(),
-            primary: primary.to_string(),
-            secondary: secondary.map(String::from),
+            interface: before.interface.clone(),
+            requested,
+            previous,
+            applied: after.as_ref().ok().cloned(),
+            read_back_error: after.err().map(|e| e.response.message),
+            steps,
+            verified,
+            rolled_back,
         },
     };
 
     print_output(&response, format)?;
-    Ok(ExitCodes::Success as u8)
+    if verified {
+        Ok(ExitCodes::Success as u8)
+    } else {
+        Ok(ExitCodes::GeneralError as u8)
+    }
+}
+
+/// Validate servers and split them by family, keeping their order
+fn parse_server_list(servers: &[String]) -> Result<FamilyServers, NactlError> {
+    if servers.is_empty() {
+        return Err(NactlError::invalid_arguments(
+            "At least one DNS server is required",
+        ));
+    }
+
+    let mut requested = FamilyServers::default();
+    let mut seen: Vec<IpAddr> = Vec::new();
+    for server in servers {
+        validation::validate_ip_address(server)?;
+        let ip: IpAddr = server
+            .parse()
+            .map_err(|_| NactlError::invalid_input(format!("Invalid IP address: {}", server)))?;
+        if seen.contains(&ip) {
+            return Err(NactlError::invalid_arguments(format!(
+                "DNS server {} is listed twice",
+                server
+            )));
+        }
+        seen.push(ip);
+
+        let list = match IpFamily::of_ip(ip) {
+            IpFamily::Ipv4 => &mut requested.ipv4,
+            IpFamily::Ipv6 => &mut requested.ipv6,
+        };
+        list.get_or_insert_with(|| ServerList {
+            source: DnsSource::Static,
+            servers: Vec::new(),
+        })
+        .servers
+        .push(ip.to_string());
+    }
+    Ok(requested)
+}
+
+/// Reject servers for a family the interface is not bound to: netsh would
+/// fail to set them, and then fail again rolling back
+fn check_families(requested: &FamilyServers, interface: &InterfaceDns) -> Result<(), NactlError> {
+    let current = FamilyServers::of(interface);
+    for (family, name) in [(IpFamily::Ipv4, "IPv4"), (IpFamily::Ipv6, "IPv6")] {
+        if requested.get(family).is_some() && current.get(family).is_none() {
+            return Err(NactlError::invalid_arguments(format!(
+                "{} is not enabled on {}",
+                name, interface.interface
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Families `dns reset` returns to DHCP: those the interface is bound to,
+/// or IPv4 alone when netsh listed neither
+fn reset_families(interface: &InterfaceDns) -> Vec<IpFamily> {
+    let current = FamilyServers::of(interface);
+    let bound: Vec<IpFamily> = [IpFamily::Ipv4, IpFamily::Ipv6]
+        .into_iter()
+        .filter(|&family| current.get(family).is_some())
+        .collect();
+    if bound.is_empty() {
+        vec![IpFamily::Ipv4]
+    } else {
+        bound
+    }
+}
+
+/// Current servers of `iface`, for checking a change
+fn read_back(iface: &str) -> Result<FamilyServers, NactlError> {
+    read_config()
+        .and_then(|config| find_interface(&config, iface))
+        .map(|interface| FamilyServers::of(&interface))
+}
+
+fn find_interface(config: &DnsConfig, iface: &str) -> Result<InterfaceDns, NactlError> {
+    config
+        .interfaces
+        .iter()
+        .find(|i| i.interface.eq_ignore_ascii_case(iface))
+        .cloned()
+        .ok_or_else(|| NactlError::interface_not_found(iface))
+}
+
+/// netsh `register=` value that keeps the interface's current setting
+fn register_mode(interface: &InterfaceDns) -> &'static str {
+    match (
+        interface.register_in_dns,
+        interface.register_connection_suffix,
+    ) {
+        (Some(false), _) => "none",
+        (Some(true), Some(true)) => "both",
+        _ => "primary",
+    }
+}
+
+fn netsh_context(family: IpFamily) -> &'static str {
+    match family {
+        IpFamily::Ipv4 => "ipv4",
+        IpFamily::Ipv6 => "ipv6",
+    }
+}
+
+/// netsh commands that make `servers` the interface's static list.
+/// `validate=no` skips netsh's own reachability check, which is slow, fails
+/// offline, and is replaced here by reading the configuration back.
+fn set_commands(
+    iface: &str,
+    family: IpFamily,
+    servers: &[String],
+    register: &str,
+) -> Vec<Vec<String>> {
+    let context = netsh_context(family);
+    servers
+        .iter()
+        .enumerate()
+        .map(|(index, server)| {
+            let mut args = vec!["interface".to_string(), context.to_string()];
+            if index == 0 {
+                args.extend([
+                    "set".to_string(),
+                    "dnsservers".to_string(),
+                    iface.to_string(),
+                    "source=static".to_string(),
+                    format!("address={}", server),
+                    format!("register={}", register),
+                ]);
+            } else {
+                args.extend([
+                    "add".to_string(),
+                    "dnsservers".to_string(),
+                    iface.to_string(),
+                    format!("address={}", server),
+                    format!("index={}", index + 1),
+                ]);
+            }
+            args.push("validate=no".to_string());
+            args
+        })
+        .collect()
+}
+
+/// netsh commands that put back a previous configuration
+fn restore_commands(
+    iface: &str,
+    family: IpFamily,
+    previous: Option<&ServerList>,
+    register: &str,
+) -> Vec<Vec<String>> {
+    match previous {
+        Some(list) if list.source == DnsSource::Static && !list.servers.is_empty() => {
+            set_commands(iface, family, &list.servers, register)
+        }
+        Some(list) if list.source == DnsSource::Static => vec![vec![
+            "interface".to_string(),
+            netsh_context(family).to_string(),
+            "set".to_string(),
+            "dnsservers".to_string(),
+            iface.to_string(),
+            "source=static".to_string(),
+            "address=none".to_string(),
+            format!("register={}", register),
+        ]],
+        _ => vec![vec![
+            "interface".to_string(),
+            netsh_context(family).to_string(),
+            "set".to_string(),
+            "dnsservers".to_string(),
+            iface.to_string(),
+            "source=dhcp".to_string(),
+            format!("register={}", register),
+        ]],
+    }
+}
+
+/// Run `plan` in order, recording each step; stops at the first failure
+/// unless rolling back, where every step is worth trying
+fn run_steps<F>(plan: &[Vec<String>], rollback: bool, steps: &mut Vec<SetStep>, mut run: F) -> bool
+where
+    F: FnMut(&[&str]) -> Result<String, NactlError>,
+{
+    let mut ok = true;
+    for args in plan {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let result = run(&args);
+        steps.push(SetStep {
+            command: format!("netsh {}", args.join(" ")),
+            success: result.is_ok(),
+            error: result.err().map(|e| e.response.message),
+            rollback,
+        });
+        if !steps.last().is_some_and(|step| step.success) {
+            ok = false;
+            if !rollback {
+                break;
+            }
+        }
+    }
+    ok
+}
+
+/// Whether every requested family now has exactly the requested static
+/// servers, in order
+fn matches_request(requested: &FamilyServers, actual: &FamilyServers) -> bool {
+    let normalize = |servers: &[String]| -> Vec<Option<IpAddr>> {
+        servers
+            .iter()
+            .map(|s| s.split('%').next().unwrap_or(s).parse().ok())
+            .collect()
+    };
+
+    [IpFamily::Ipv4, IpFamily::Ipv6].into_iter().all(|family| {
+        match (requested.get(family), actual.get(family)) {
+            (None, _) => true,
+            (Some(want), Some(have)) => {
+                have.source == DnsSource::Static
+                    && normalize(&want.servers) == normalize(&have.servers)
+            }
+            (Some(_), None) => false,
+        }
+    })
 }
 
 /// Reset DNS to automatic (DHCP)
@@ -136,23 +628,1114 @@ pub fn reset(format: OutputFormat, interface: Option<&str>) -> Result<u8, NactlE
     // Get the interface name (default to Wi-Fi)
     let iface = interface.unwrap_or("Wi-Fi");
 
-    // Reset to DHCP
-    // netsh interface ip set dns "Wi-Fi" dhcp
-    let result = netsh::run_command(&["interface", "ip", "set", "dns", iface, "dhcp"]);
+    let before = find_interface(&read_config()?, iface)?;
+    let register = register_mode(&before);
 
-    if let Err(e) = result {
-        let err_str = e.to_string();
-        if err_str.contains("not found") || err_str.contains("does not exist") {
-            return Err(NactlError::interface_not_found(iface));
-        }
-        return Err(e);
+    // Every bound family, so IPv6 servers set by `dns set` are cleared too
+    let mut steps = Vec::new();
+    let mut reset_ok = true;
+    for family in reset_families(&before) {
+        let plan = restore_commands(&before.interface, family, None, register);
+        reset_ok &= run_steps(&plan, false, &mut steps, netsh::run_command);
     }
 
+    let after = read_back(iface);
+    let message = if reset_ok {
+        "DNS reset to automatic (DHCP)".to_string()
+    } else {
+        "Resetting DNS to automatic (DHCP) failed".to_string()
+    };
+
     let response = ResetResponse {
+        success: reset_ok,
+        message,
+        data: ResetDnsData {
+            interface: before.interface.clone(),
+            steps,
+            applied: after.as_ref().ok().cloned(),
+            read_back_error: after.err().map(|e| e.response.message),
+        },
+    };
+
+    print_output(&response, format)?;
+    if reset_ok {
+        Ok(ExitCodes::Success as u8)
+    } else {
+        Ok(ExitCodes::GeneralError as u8)
+    }
+}
+
+/// Query a DNS server directly, bypassing the system resolver
+pub fn lookup(
+    name: &str,
+    record_type: &str,
+    server: Option<&str>,
+    tcp: bool,
+    timeout: u32,
+    format: OutputFormat,
+    interface: Option<&str>,
+) -> Result<u8, NactlError> {
+    let record_type = RecordType::parse(record_type).ok_or_else(|| {
+        NactlError::invalid_arguments(format!(
+            "Unsupported record type '{}': use A, AAAA, CNAME, MX, TXT, NS, SOA, SRV or PTR",
+            record_type
+        ))
+    })?;
+    if timeout == 0 {
+        return Err(NactlError::invalid_arguments(
+            "Timeout must be at least 1 ms",
+        ));
+    }
+
+    let server = match server {
+        Some(server) => parse_server(server)?,
+        None => configured_servers(interface)
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                NactlError::not_available("No DNS server is configured; pass --server")
+            })?,
+    };
+
+    // PTR lookups accept a plain address
+    let name = match (record_type, name.parse::<IpAddr>()) {
+        (RecordType::Ptr, Ok(ip)) => wire::reverse_name(ip),
+        _ => name.to_string(),
+    };
+
+    let exchange = wire::query(
+        server,
+        &name,
+        record_type,
+        tcp,
+        Duration::from_millis(timeout as u64),
+    )?;
+    let message = exchange.message;
+    let answered = message.rcode == 0;
+
+    let response = LookupResponse {
+        success: answered,
+        data: LookupData {
+            name,
+            record_type: record_type.name(),
+            server: server.to_string(),
+            transport: exchange.transport,
+            response_code: message.response_code(),
+            flags: message.flags,
+            latency_ms: stats::round_to(exchange.latency_ms, 3),
+            answers: message.answers,
+            authority: message.authority,
+        },
+    };
+
+    print_output(&response, format)?;
+    if answered {
+        Ok(ExitCodes::Success as u8)
+    } else {
+        Ok(ExitCodes::GeneralError as u8)
+    }
+}
+
+/// Report DNS client configuration for the selected interface, the
+/// interfaces that have DNS servers, or with `all` every interface
+pub fn get(all: bool, format: OutputFormat, interface: Option<&str>) -> Result<u8, NactlError> {
+    let mut config = read_config()?;
+
+    match interface {
+        Some(iface) => {
+            config
+                .interfaces
+                .retain(|i| i.interface.eq_ignore_ascii_case(iface));
+            if config.interfaces.is_empty() {
+                return Err(NactlError::interface_not_found(iface));
+            }
+        }
+        None if !all => config.interfaces.retain(has_servers),
+        None => {}
+    }
+
+    let response = DnsGetResponse {
         success: true,
-        message: "DNS reset to automatic (DHCP)".to_string(),
+        data: config,
     };
 
     print_output(&response, format)?;
     Ok(ExitCodes::Success as u8)
 }
+
+/// Read DNS client configuration for every interface
+pub fn read_config() -> Result<DnsConfig, NactlError> {
+    let ipv4 = parse_dnsservers(&netsh::run_command(&[
+        "interface",
+        "ipv4",
+        "show",
+        "dnsservers",
+    ])?);
+    // IPv6 may be unbound everywhere; that is not an error
+    let ipv6 = netsh::run_command(&["interface", "ipv6", "show", "dnsservers"])
+        .map(|output| parse_dnsservers(&output))
+        .unwrap_or_default();
+
+    let output = Command::new("ipconfig")
+        .arg("/all")
+        .output()
+        .map_err(|e| NactlError::command_failed(format!("Failed to run ipconfig: {}", e)))?;
+    let suffixes = parse_ipconfig_suffixes(&String::from_utf8_lossy(&output.stdout));
+
+    Ok(merge_config(ipv4, ipv6, suffixes))
+}
+
+fn merge_config(ipv4: Vec<NetshDns>, ipv6: Vec<NetshDns>, suffixes: IpconfigSuffixes) -> DnsConfig {
+    let mut names: Vec<String> = Vec::new();
+    for entry in ipv4.iter().chain(&ipv6) {
+        if !names.contains(&entry.interface) {
+            names.push(entry.interface.clone());
+        }
+    }
+
+    let interfaces = names
+        .into_iter()
+        .map(|name| {
+            let v4 = ipv4.iter().find(|e| e.interface == name);
+            let v6 = ipv6.iter().find(|e| e.interface == name);
+            let register = v4.and_then(|e| e.register).or(v6.and_then(|e| e.register));
+
+            InterfaceDns {
+                ipv4: v4.map(|e| e.list.clone()),
+                ipv6: v6.map(|e| e.list.clone()),
+                connection_suffix: suffixes
+                    .connection_suffixes
+                    .iter()
+                    .find(|(adapter, _)| adapter.eq_ignore_ascii_case(&name))
+                    .map(|(_, suffix)| suffix.clone()),
+                register_in_dns: register.map(|(register, _)| register),
+                register_connection_suffix: register.map(|(_, suffix)| suffix),
+                interface: name,
+            }
+        })
+        .collect();
+
+    DnsConfig {
+        primary_suffix: suffixes.primary_suffix,
+        search_list: suffixes.search_list,
+        interfaces,
+    }
+}
+
+/// Whether an interface has real DNS servers. Windows lists the
+/// deprecated fec0:0:0:ffff::1-3 site-local defaults on interfaces without
+/// DHCPv6; those do not count.
+fn has_servers(interface: &InterfaceDns) -> bool {
+    interface
+        .ipv4
+        .iter()
+        .chain(&interface.ipv6)
+        .flat_map(|list| &list.servers)
+        .any(|server| !server.to_lowercase().starts_with("fec0:0:0:ffff::"))
+}
+
+/// Parse `netsh interface ipv4|ipv6 show dnsservers`
+fn parse_dnsservers(output: &str) -> Vec<NetshDns> {
+    let header = Regex::new(r#"^\s*Configuration for interface "(.+)""#).unwrap();
+    let servers_label = Regex::new(
+        r"(?i)^\s*(DNS servers configured through DHCP|Statically Configured DNS Servers):\s*(.*)$",
+    )
+    .unwrap();
+    let register_label = Regex::new(r"(?i)^\s*Register with which suffix:\s*(.*)$").unwrap();
+
+    let mut entries: Vec<NetshDns> = Vec::new();
+    let mut collecting = false;
+
+    for line in output.lines() {
+        if let Some(caps) = header.captures(line) {
+            entries.push(NetshDns {
+                interface: caps[1].to_string(),
+                list: ServerList {
+                    source: DnsSource::Static,
+                    servers: Vec::new(),
+                },
+                register: None,
+            });
+            collecting = false;
+            continue;
+        }
+        let Some(entry) = entries.last_mut() else {
+            continue;
+        };
+
+        if let Some(caps) = servers_label.captures(line) {
+            entry.list.source = if caps[1].to_lowercase().contains("dhcp") {
+                DnsSource::Dhcp
+            } else {
+                DnsSource::Static
+            };
+            let first = caps[2].trim();
+            if !first.is_empty() && !first.eq_ignore_ascii_case("none") {
+                entry.list.servers.push(first.to_string());
+            }
+            collecting = true;
+        } else if let Some(caps) = register_label.captures(line) {
+            let value = caps[1].trim().to_lowercase();
+            entry.register = Some(if value.contains("connection-specific") {
+                (true, true)
+            } else if value.contains("primary") {
+                (true, false)
+            } else {
+                (false, false)
+            });
+            collecting = false;
+        } else if collecting {
+            let value = line.trim();
+            if !value.is_empty() && !value.contains(' ') {
+                entry.list.servers.push(value.to_string());
+            } else {
+                collecting = false;
+            }
+        }
+    }
+
+    entries
+}
+
+/// Pull the primary suffix, search list and connection-specific suffixes
+/// out of `ipconfig /all`
+fn parse_ipconfig_suffixes(output: &str) -> IpconfigSuffixes {
+    let adapter_header = Regex::new(r"^\S.* adapter (.+):\s*$").unwrap();
+    let label = Regex::new(r"^\s+([A-Za-z][A-Za-z0-9 -]*?)[ .]*:\s*(.*)$").unwrap();
+
+    let mut suffixes = IpconfigSuffixes::default();
+    let mut adapter: Option<String> = None;
+    let mut in_search_list = false;
+
+    for line in output.lines() {
+        if let Some(caps) = adapter_header.captures(line) {
+            adapter = Some(caps[1].to_string());
+            in_search_list = false;
+            continue;
+        }
+
+        if let Some(caps) = label.captures(line) {
+            let value = caps[2].trim();
+            in_search_list = false;
+            match (caps[1].trim(), &adapter) {
+                ("Primary Dns Suffix", None) if !value.is_empty() => {
+                    suffixes.primary_suffix = Some(value.to_string());
+                }
+                ("DNS Suffix Search List", None) => {
+                    if !value.is_empty() {
+                        suffixes.search_list.push(value.to_string());
+                    }
+                    in_search_list = true;
+                }
+                ("Connection-specific DNS Suffix", Some(name)) if !value.is_empty() => {
+                    suffixes
+                        .connection_suffixes
+                        .insert(name.clone(), value.to_string());
+                }
+                _ => {}
+            }
+        } else if in_search_list && line.starts_with(' ') && !line.trim().is_empty() {
+            suffixes.search_list.push(line.trim().to_string());
+        } else {
+            in_search_list = false;
+        }
+    }
+
+    suffixes
+}
+
+/// Benchmark the configured DNS servers and a candidate list, and
+/// recommend the best pair
+pub fn benchmark(
+    candidates: &str,
+    rounds: u32,
+    timeout: u32,
+    format: OutputFormat,
+    interface: Option<&str>,
+) -> Result<u8, NactlError> {
+    if rounds == 0 {
+        return Err(NactlError::invalid_arguments("Rounds must be at least 1"));
+    }
+    if timeout == 0 {
+        return Err(NactlError::invalid_arguments(
+            "Timeout must be at least 1 ms",
+        ));
+    }
+
+    let configured = configured_servers(interface);
+    let mut servers: Vec<(SocketAddr, bool)> = configured.iter().map(|s| (*s, true)).collect();
+    for candidate in candidates
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+    {
+        let server = parse_server(candidate)?;
+        if !servers.iter().any(|(s, _)| *s == server) {
+            servers.push((server, false));
+        }
+    }
+    if servers.is_empty() {
+        return Err(NactlError::invalid_arguments("No DNS servers to benchmark"));
+    }
+
+    // Servers run side by side; queries to one server run one at a time so
+    // they do not queue behind each other
+    let timeout = Duration::from_millis(timeout as u64);
+    let samples: Vec<Vec<Sample>> = thread::scope(|scope| {
+        let handles: Vec<_> = servers
+            .iter()
+            .map(|(server, _)| scope.spawn(move || benchmark_server(*server, rounds, timeout)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    });
+
+    let mut results: Vec<ServerBenchmark> = servers
+        .iter()
+        .zip(&samples)
+        .map(|((server, configured), samples)| summarize(*server, *configured, samples))
+        .collect();
+    rank(&mut results);
+
+    let recommended = recommend(&results);
+    let recommended_command = recommended.as_ref().map(|pair| {
+        let mut command = format!("nactl dns set {}", pair.join(" "));
+        if let Some(iface) = interface {
+            command.push_str(&format!(" --interface \"{}\"", iface));
+        }
+        command
+    });
+
+    let response = BenchmarkResponse {
+        success: results.iter().any(|r| r.median_ms.is_some()),
+        data: BenchmarkData {
+            rounds,
+            cached_names: CACHED_NAMES.iter().map(|n| n.to_string()).collect(),
+            uncached_zones: UNCACHED_ZONES.iter().map(|z| z.to_string()).collect(),
+            servers: results,
+            recommended,
+            recommended_command,
+        },
+    };
+
+    let answered = response.success;
+    print_output(&response, format)?;
+    if answered {
+        Ok(ExitCodes::Success as u8)
+    } else {
+        Ok(ExitCodes::GeneralError as u8)
+    }
+}
+
+/// Query one server `rounds` times for every cached name and a fresh
+/// random name in every uncached zone
+fn benchmark_server(server: SocketAddr, rounds: u32, timeout: Duration) -> Vec<Sample> {
+    benchmark_with(rounds, |name| {
+        wire::query(server, name, RecordType::A, false, timeout)
+    })
+}
+
+/// `benchmark_server` with the query supplied. Once `MAX_CONSECUTIVE_FAILURES`
+/// queries in a row get no answer, the remaining ones count as failures
+/// without being sent.
+fn benchmark_with<F>(rounds: u32, mut query: F) -> Vec<Sample>
+where
+    F: FnMut(&str) -> Result<wire::Exchange, NactlError>,
+{
+    let mut unanswered = 0;
+    let mut ask = |name: &str| {
+        if unanswered >= MAX_CONSECUTIVE_FAILURES {
+            return None;
+        }
+        let exchange = query(name).ok();
+        if exchange.is_some() {
+            unanswered = 0;
+        } else {
+            unanswered += 1;
+        }
+        exchange
+    };
+
+    // Warm-up so the "cached" names really are cached
+    for name in CACHED_NAMES {
+        let _ = ask(name);
+    }
+
+    let mut samples = Vec::new();
+    for _ in 0..rounds {
+        for name in CACHED_NAMES {
+            let latency_ms = ask(name)
+                .filter(|e| e.message.rcode == 0)
+                .map(|e| e.latency_ms);
+            samples.push(Sample {
+                cached: true,
+                latency_ms,
+                hijacked: false,
+            });
+        }
+
+        for zone in UNCACHED_ZONES {
+            let name = format!("{}.{}", random_label(), zone);
+            let sample = match ask(&name) {
+                // NXDOMAIN is the right answer; records mean a rewrite
+                Some(e) if e.message.rcode == 3 || e.message.rcode == 0 => Sample {
+                    cached: false,
+                    latency_ms: Some(e.latency_ms),
+                    hijacked: !e.message.answers.is_empty(),
+                },
+                _ => Sample {
+                    cached: false,
+                    latency_ms: None,
+                    hijacked: false,
+                },
+            };
+            samples.push(sample);
+        }
+    }
+    samples
+}
+
+fn summarize(server: SocketAddr, configured: bool, samples: &[Sample]) -> ServerBenchmark {
+    let latencies = |cached: Option<bool>| -> Vec<f64> {
+        samples
+            .iter()
+            .filter(|s| cached.is_none_or(|c| s.cached == c))
+            .filter_map(|s| s.latency_ms)
+            .collect()
+    };
+    let median = |values: &[f64]| stats::percentile(values, 50.0).map(|v| stats::round_to(v, 3));
+
+    let all = latencies(None);
+    let queries = samples.len() as u32;
+    let failures = queries - all.len() as u32;
+
+    ServerBenchmark {
+        // Port only when it is not the standard one
+        server: if server.port() == wire::DNS_PORT {
+            server.ip().to_string()
+        } else {
+            server.to_string()
+        },
+        address: server,
+        configured,
+        rank: 0,
+        queries,
+        failures,
+        failure_rate: if queries == 0 {
+            0.0
+        } else {
+            stats::round_to(failures as f64 / queries as f64 * 100.0, 1)
+        },
+        median_ms: median(&all),
+        p95_ms: stats::percentile(&all, 95.0).map(|v| stats::round_to(v, 3)),
+        cached_median_ms: median(&latencies(Some(true))),
+        uncached_median_ms: median(&latencies(Some(false))),
+        nxdomain_hijack: samples.iter().any(|s| s.hijacked),
+    }
+}
+
+/// Order best first: honest answers, then reliability, then median latency
+fn rank(results: &mut [ServerBenchmark]) {
+    results.sort_by(|a, b| {
+        a.nxdomain_hijack
+            .cmp(&b.nxdomain_hijack)
+            .then(a.median_ms.is_none().cmp(&b.median_ms.is_none()))
+            .then(a.failure_rate.total_cmp(&b.failure_rate))
+            .then(
+                a.median_ms
+                    .unwrap_or(f64::MAX)
+                    .total_cmp(&b.median_ms.unwrap_or(f64::MAX)),
+            )
+    });
+    for (index, result) in results.iter_mut().enumerate() {
+        result.rank = index as u32 + 1;
+    }
+}
+
+/// The two best usable servers of the best server's address family
+fn recommend(ranked: &[ServerBenchmark]) -> Option<Vec<String>> {
+    let usable: Vec<&ServerBenchmark> = ranked
+        .iter()
+        .filter(|r| {
+            // `dns set` has no port, so only standard-port servers qualify
+            r.address.port() == wire::DNS_PORT
+                && !r.nxdomain_hijack
+                && r.median_ms.is_some()
+                && r.failure_rate <= MAX_RECOMMENDED_FAILURE_RATE
+        })
+        .collect();
+    let best = usable.first()?;
+    let family = |r: &ServerBenchmark| r.address.ip().is_ipv6();
+
+    Some(
+        usable
+            .iter()
+            .filter(|r| family(r) == family(best))
+            .take(2)
+            .map(|r| r.address.ip().to_string())
+            .collect(),
+    )
+}
+
+/// Random label for names no resolver can have cached
+fn random_label() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("nactl-{:012x}", hasher.finish() & 0xffff_ffff_ffff)
+}
+
+/// DNS servers of the selected (or default) interface, in order
+pub fn configured_servers(interface: Option<&str>) -> Vec<SocketAddr> {
+    status::collect(interface)
+        .dns_servers
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|server| server.parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, wire::DNS_PORT))
+        .collect()
+}
+
+/// `address`, `address:port` or `[v6]:port`
+pub fn parse_server(value: &str) -> Result<SocketAddr, NactlError> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    value
+        .parse::<IpAddr>()
+        .map(|ip| SocketAddr::new(ip, wire::DNS_PORT))
+        .map_err(|_| {
+            NactlError::invalid_arguments(format!(
+                "Invalid DNS server '{}': expected an IP address with an optional port",
+                value
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server() {
+        assert_eq!(
+            parse_server("1.1.1.1").unwrap(),
+            "1.1.1.1:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_server("127.0.0.1:5353").unwrap(),
+            "127.0.0.1:5353".parse().unwrap()
+        );
+        assert_eq!(
+            parse_server("2606:4700:4700::1111").unwrap(),
+            "[2606:4700:4700::1111]:53".parse().unwrap()
+        );
+        assert_eq!(
+            parse_server("[::1]:5353").unwrap(),
+            "[::1]:5353".parse().unwrap()
+        );
+        assert!(parse_server("dns.example.com").is_err());
+    }
+
+    fn result(
+        server: &str,
+        median_ms: Option<f64>,
+        failure_rate: f64,
+        hijack: bool,
+    ) -> ServerBenchmark {
+        ServerBenchmark {
+            server: server.to_string(),
+            address: parse_server(server).unwrap(),
+            configured: false,
+            rank: 0,
+            queries: 40,
+            failures: 0,
+            failure_rate,
+            median_ms,
+            p95_ms: median_ms,
+            cached_median_ms: median_ms,
+            uncached_median_ms: median_ms,
+            nxdomain_hijack: hijack,
+        }
+    }
+
+    #[test]
+    fn test_rank_and_recommend() {
+        let mut results = vec![
+            result("192.168.1.1", Some(2.0), 0.0, true),
+            result("9.9.9.9", Some(30.0), 0.0, false),
+            result("8.8.8.8", Some(12.0), 0.0, false),
+            result("2606:4700:4700::1111", Some(15.0), 0.0, false),
+            result("1.1.1.1", Some(10.0), 20.0, false),
+            result("10.0.0.53", None, 100.0, false),
+            result("1.0.0.1", Some(11.0), 0.0, false),
+        ];
+        rank(&mut results);
+
+        let order: Vec<&str> = results.iter().map(|r| r.server.as_str()).collect();
+        assert_eq!(
+            order,
+            vec![
+                "1.0.0.1",
+                "8.8.8.8",
+                "2606:4700:4700::1111",
+                "9.9.9.9",
+                "1.1.1.1",
+                "10.0.0.53",
+                "192.168.1.1",
+            ]
+        );
+        assert_eq!(results[0].rank, 1);
+        assert_eq!(
+            recommend(&results),
+            Some(vec!["1.0.0.1".to_string(), "8.8.8.8".to_string()])
+        );
+
+        // A local resolver on another port cannot be passed to `dns set`,
+        // and its colon does not make it IPv6
+        let mut with_port = vec![
+            result("127.0.0.1:5353", Some(1.0), 0.0, false),
+            result("1.1.1.1", Some(10.0), 0.0, false),
+            result("2606:4700:4700::1111", Some(11.0), 0.0, false),
+            result("8.8.8.8", Some(12.0), 0.0, false),
+        ];
+        rank(&mut with_port);
+        assert_eq!(
+            recommend(&with_port),
+            Some(vec!["1.1.1.1".to_string(), "8.8.8.8".to_string()])
+        );
+
+        let hopeless = vec![result("192.168.1.1", Some(2.0), 0.0, true)];
+        assert_eq!(recommend(&hopeless), None);
+    }
+
+    #[test]
+    fn test_benchmark_gives_up_on_silent_server() {
+        let mut sent = 0;
+        let samples = benchmark_with(5, |_| {
+            sent += 1;
+            Err(NactlError::timeout("No answer"))
+        });
+
+        assert_eq!(sent, MAX_CONSECUTIVE_FAILURES);
+        assert_eq!(
+            samples.len(),
+            5 * (CACHED_NAMES.len() + UNCACHED_ZONES.len())
+        );
+        assert!(samples.iter().all(|s| s.latency_ms.is_none()));
+
+        // Occasional losses do not stop a server that keeps answering
+        let mut sent = 0;
+        let samples = benchmark_with(2, |_| {
+            sent += 1;
+            if sent % 3 == 0 {
+                Err(NactlError::timeout("No answer"))
+            } else {
+                Ok(wire::Exchange {
+                    message: wire::Message {
+                        id: 1,
+                        rcode: 3,
+                        flags: Flags::default(),
+                        answers: Vec::new(),
+                        authority: Vec::new(),
+                    },
+                    latency_ms: 5.0,
+                    transport: Transport::Udp,
+                })
+            }
+        });
+        assert_eq!(sent, CACHED_NAMES.len() + samples.len());
+        assert!(samples.iter().any(|s| s.latency_ms == Some(5.0)));
+    }
+
+    #[test]
+    fn test_summarize() {
+        let sample = |cached, latency_ms, hijacked| Sample {
+            cached,
+            latency_ms,
+            hijacked,
+        };
+        let samples = vec![
+            sample(true, Some(1.0), false),
+            sample(true, Some(3.0), false),
+            sample(true, None, false),
+            sample(false, Some(40.0), false),
+            sample(false, Some(50.0), true),
+        ];
+        let summary = summarize("192.168.1.1:53".parse().unwrap(), true, &samples);
+
+        assert_eq!(summary.server, "192.168.1.1");
+        assert_eq!((summary.queries, summary.failures), (5, 1));
+        assert_eq!(summary.failure_rate, 20.0);
+        assert_eq!(summary.median_ms, Some(3.0));
+        assert_eq!(summary.p95_ms, Some(50.0));
+        assert_eq!(summary.cached_median_ms, Some(1.0));
+        assert_eq!(summary.uncached_median_ms, Some(40.0));
+        assert!(summary.nxdomain_hijack);
+    }
+
+    /// Stand-in resolver: NXDOMAIN for the uncached zones (or, when
+    /// `hijack` is set, an A record), an A record for everything else
+    fn serve(socket: std::net::UdpSocket, hijack: bool) {
+        socket
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let mut buf = [0u8; 512];
+        while let Ok((len, from)) = socket.recv_from(&mut buf) {
+            // Question runs from the header to the trailing 11-byte OPT record
+            let question = &buf[12..len - 11];
+            let text = String::from_utf8_lossy(question);
+            let nx = text.contains("example") && !hijack;
+
+            let mut reply = buf[..2].to_vec();
+            reply.extend_from_slice(&[0x81, if nx { 0x83 } else { 0x80 }, 0, 1, 0]);
+            reply.push(u8::from(!nx));
+            reply.extend_from_slice(&[0, 0, 0, 0]);
+            reply.extend_from_slice(question);
+            if !nx {
+                reply.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 0, 2, 1]);
+            }
+            socket.send_to(&reply, from).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_benchmark_stand_in_servers() {
+        let honest = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let hijacking = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (honest_addr, hijacking_addr) = (
+            honest.local_addr().unwrap(),
+            hijacking.local_addr().unwrap(),
+        );
+        let servers = [
+            thread::spawn(move || serve(honest, false)),
+            thread::spawn(move || serve(hijacking, true)),
+        ];
+
+        let timeout = Duration::from_secs(2);
+        let honest = summarize(
+            honest_addr,
+            false,
+            &benchmark_server(honest_addr, 2, timeout),
+        );
+        let hijacking = summarize(
+            hijacking_addr,
+            false,
+            &benchmark_server(hijacking_addr, 2, timeout),
+        );
+        for server in servers {
+            server.join().unwrap();
+        }
+
+        assert_eq!(honest.queries, 16);
+        assert_eq!(honest.failures, 0);
+        assert!(!honest.nxdomain_hijack);
+        assert!(honest.cached_median_ms.is_some() && honest.uncached_median_ms.is_some());
+        assert!(hijacking.nxdomain_hijack);
+
+        assert_ne!(random_label(), random_label());
+    }
+
+    const NETSH_IPV4: &str = "
+
+Configuration for interface \"Wi-Fi\"
+    DNS servers configured through DHCP:  192.168.1.1
+                                          8.8.8.8
+    Register with which suffix:           Primary only
+
+Configuration for interface \"Ethernet 2\"
+    Statically Configured DNS Servers:    1.1.1.1
+                                          1.0.0.1
+    Register with which suffix:           Primary and connection-specific
+
+Configuration for interface \"Loopback Pseudo-Interface 1\"
+    Statically Configured DNS Servers:    None
+    Register with which suffix:           None
+
+";
+
+    const NETSH_IPV6: &str = "
+
+Configuration for interface \"Wi-Fi\"
+    DNS servers configured through DHCP:  fd00::1
+    Register with which suffix:           Primary only
+
+Configuration for interface \"Ethernet 2\"
+    DNS servers configured through DHCP:  fec0:0:0:ffff::1%1
+                                          fec0:0:0:ffff::2%1
+                                          fec0:0:0:ffff::3%1
+    Register with which suffix:           Primary only
+
+";
+
+    const IPCONFIG_ALL: &str = "
+Windows IP Configuration
+
+   Host Name . . . . . . . . . . . . : DESKTOP-01
+   Primary Dns Suffix  . . . . . . . : corp.example.com
+   Node Type . . . . . . . . . . . . : Hybrid
+   IP Routing Enabled. . . . . . . . : No
+   DNS Suffix Search List. . . . . . : corp.example.com
+                                       example.com
+
+Ethernet adapter Ethernet 2:
+
+   Connection-specific DNS Suffix  . :
+   Description . . . . . . . . . . . : Intel(R) Ethernet Connection
+
+Wireless LAN adapter Wi-Fi:
+
+   Connection-specific DNS Suffix  . : home.lan
+   DNS Servers . . . . . . . . . . . : 192.168.1.1
+                                       8.8.8.8
+";
+
+    #[test]
+    fn test_parse_dnsservers() {
+        let entries = parse_dnsservers(NETSH_IPV4);
+        assert_eq!(entries.len(), 3);
+
+        assert_eq!(entries[0].interface, "Wi-Fi");
+        assert_eq!(entries[0].list.source, DnsSource::Dhcp);
+        assert_eq!(entries[0].list.servers, vec!["192.168.1.1", "8.8.8.8"]);
+        assert_eq!(entries[0].register, Some((true, false)));
+
+        assert_eq!(entries[1].list.source, DnsSource::Static);
+        assert_eq!(entries[1].list.servers, vec!["1.1.1.1", "1.0.0.1"]);
+        assert_eq!(entries[1].register, Some((true, true)));
+
+        assert!(entries[2].list.servers.is_empty());
+        assert_eq!(entries[2].register, Some((false, false)));
+
+        let v6 = parse_dnsservers(NETSH_IPV6);
+        assert_eq!(v6[0].list.servers, vec!["fd00::1"]);
+        assert_eq!(v6[1].list.servers.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_ipconfig_suffixes() {
+        let suffixes = parse_ipconfig_suffixes(IPCONFIG_ALL);
+
+        assert_eq!(suffixes.primary_suffix.as_deref(), Some("corp.example.com"));
+        assert_eq!(
+            suffixes.search_list,
+            vec!["corp.example.com", "example.com"]
+        );
+        assert_eq!(
+            suffixes
+                .connection_suffixes
+                .get("Wi-Fi")
+                .map(String::as_str),
+            Some("home.lan")
+        );
+        assert!(!suffixes.connection_suffixes.contains_key("Ethernet 2"));
+    }
+
+    #[test]
+    fn test_merge_config() {
+        let config = merge_config(
+            parse_dnsservers(NETSH_IPV4),
+            parse_dnsservers(NETSH_IPV6),
+            parse_ipconfig_suffixes(IPCONFIG_ALL),
+        );
+        assert_eq!(config.interfaces.len(), 3);
+
+        let wifi = &config.interfaces[0];
+        assert_eq!(wifi.ipv6.as_ref().unwrap().servers, vec!["fd00::1"]);
+        assert_eq!(wifi.connection_suffix.as_deref(), Some("home.lan"));
+        assert_eq!(wifi.register_in_dns, Some(true));
+        assert_eq!(wifi.register_connection_suffix, Some(false));
+
+        let loopback = &config.interfaces[2];
+        assert!(loopback.ipv6.is_none());
+
+        let with_servers: Vec<&str> = config
+            .interfaces
+            .iter()
+            .filter(|i| has_servers(i))
+            .map(|i| i.interface.as_str())
+            .collect();
+        assert_eq!(with_servers, vec!["Wi-Fi", "Ethernet 2"]);
+
+        let ipv6_defaults_only = InterfaceDns {
+            ipv4: None,
+            ..config.interfaces[1].clone()
+        };
+        assert!(!has_servers(&ipv6_defaults_only));
+    }
+
+    fn static_list(servers: &[&str]) -> ServerList {
+        ServerList {
+            source: DnsSource::Static,
+            servers: servers.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_server_list() {
+        let servers: Vec<String> = ["1.1.1.1", "2606:4700:4700::1111", "8.8.8.8"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let requested = parse_server_list(&servers).unwrap();
+        assert_eq!(requested.ipv4, Some(static_list(&["1.1.1.1", "8.8.8.8"])));
+        assert_eq!(requested.ipv6, Some(static_list(&["2606:4700:4700::1111"])));
+
+        assert!(parse_server_list(&[]).is_err());
+        assert!(parse_server_list(&["1.1.1.1".to_string(), "1.1.1.1".to_string()]).is_err());
+        assert!(parse_server_list(&["dns.google".to_string()]).is_err());
+    }
+
+    fn ipv4_only() -> InterfaceDns {
+        InterfaceDns {
+            interface: "Ethernet".to_string(),
+            ipv4: Some(ServerList {
+                source: DnsSource::Dhcp,
+                servers: vec!["192.168.1.1".to_string()],
+            }),
+            ipv6: None,
+            connection_suffix: None,
+            register_in_dns: Some(true),
+            register_connection_suffix: Some(false),
+        }
+    }
+
+    #[test]
+    fn test_ipv4_only_interface() {
+        let interface = ipv4_only();
+        assert_eq!(reset_families(&interface), vec![IpFamily::Ipv4]);
+
+        let v4 = parse_server_list(&["1.1.1.1".to_string()]).unwrap();
+        assert!(check_families(&v4, &interface).is_ok());
+
+        let v6 = parse_server_list(&["1.1.1.1".to_string(), "2606:4700:4700::1111".to_string()])
+            .unwrap();
+        let err = check_families(&v6, &interface).unwrap_err();
+        assert_eq!(err.response.message, "IPv6 is not enabled on Ethernet");
+
+        let dual = InterfaceDns {
+            ipv6: Some(static_list(&["fd00::1"])),
+            ..interface
+        };
+        assert_eq!(reset_families(&dual), vec![IpFamily::Ipv4, IpFamily::Ipv6]);
+        assert!(check_families(&v6, &dual).is_ok());
+    }
+
+    #[test]
+    fn test_set_commands() {
+        let servers = vec!["fd00::1".to_string(), "fd00::2".to_string()];
+        let plan: Vec<String> = set_commands("Wi-Fi", IpFamily::Ipv6, &servers, "primary")
+            .iter()
+            .map(|args| args.join(" "))
+            .collect();
+        assert_eq!(
+            plan,
+            vec![
+                "interface ipv6 set dnsservers Wi-Fi source=static address=fd00::1 register=primary validate=no",
+                "interface ipv6 add dnsservers Wi-Fi address=fd00::2 index=2 validate=no",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_restore_commands() {
+        let dhcp = ServerList {
+            source: DnsSource::Dhcp,
+            servers: vec!["192.168.1.1".to_string()],
+        };
+        let restore = restore_commands("Wi-Fi", IpFamily::Ipv4, Some(&dhcp), "both");
+        assert_eq!(
+            restore[0].join(" "),
+            "interface ipv4 set dnsservers Wi-Fi source=dhcp register=both"
+        );
+
+        // What `dns reset` runs for each family
+        let restore = restore_commands("Wi-Fi", IpFamily::Ipv6, None, "primary");
+        assert_eq!(
+            restore[0].join(" "),
+            "interface ipv6 set dnsservers Wi-Fi source=dhcp register=primary"
+        );
+
+        let restore = restore_commands("Wi-Fi", IpFamily::Ipv4, Some(&static_list(&[])), "none");
+        assert_eq!(
+            restore[0].join(" "),
+            "interface ipv4 set dnsservers Wi-Fi source=static address=none register=none"
+        );
+
+        let previous = static_list(&["9.9.9.9", "149.112.112.112"]);
+        let restore = restore_commands("Wi-Fi", IpFamily::Ipv4, Some(&previous), "primary");
+        assert_eq!(restore.len(), 2);
+        assert!(restore[1].contains(&"address=149.112.112.112".to_string()));
+    }
+
+    #[test]
+    fn test_run_steps_stops_at_failure() {
+        let plan = set_commands(
+            "Wi-Fi",
+            IpFamily::Ipv4,
+            &[
+                "1.1.1.1".to_string(),
+                "1.0.0.1".to_string(),
+                "8.8.8.8".to_string(),
+            ],
+            "primary",
+        );
+        let mut calls = 0;
+        let mut steps = Vec::new();
+        let ok = run_steps(&plan, false, &mut steps, |_| {
+            calls += 1;
+            if calls == 2 {
+                Err(NactlError::command_failed("The parameter is incorrect."))
+            } else {
+                Ok(String::new())
+            }
+        });
+
+        assert!(!ok);
+        assert_eq!(steps.len(), 2);
+        assert!(steps[0].success);
+        assert!(!steps[1].success);
+        assert_eq!(
+            steps[1].error.as_deref(),
+            Some("The parameter is incorrect.")
+        );
+        assert!(steps[1]
+            .command
+            .starts_with("netsh interface ipv4 add dnsservers"));
+    }
+
+    #[test]
+    fn test_matches_request() {
+        let requested = FamilyServers {
+            ipv4: Some(static_list(&["1.1.1.1", "1.0.0.1"])),
+            ipv6: None,
+        };
+        let mut actual = FamilyServers {
+            ipv4: Some(static_list(&["1.1.1.1", "1.0.0.1"])),
+            ipv6: Some(static_list(&["fe80::1%12"])),
+        };
+        assert!(matches_request(&requested, &actual));
+
+        actual.ipv4 = Some(static_list(&["1.0.0.1", "1.1.1.1"]));
+        assert!(!matches_request(&requested, &actual));
+
+        actual.ipv4 = Some(ServerList {
+            source: DnsSource::Dhcp,
+            servers: vec!["1.1.1.1".to_string(), "1.0.0.1".to_string()],
+        });
+        assert!(!matches_request(&requested, &actual));
+
+        let zoned = FamilyServers {
+            ipv4: None,
+            ipv6: Some(static_list(&["fe80::1"])),
+        };
+        assert!(matches_request(
+            &zoned,
+            &FamilyServers {
+                ipv4: None,
+                ipv6: Some(static_list(&["fe80::1%12"])),
+            }
+        ));
+    }
+}
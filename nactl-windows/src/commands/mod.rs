@@ -1,9 +1,16 @@
 //! Command modules for nactl
 
+pub mod connectivity;
+pub mod diagnose;
 pub mod dns;
+pub mod mtu;
 pub mod ping;
+pub mod portal;
 pub mod proxy;
+pub mod scan;
+pub mod snapshot;
 pub mod stack;
 pub mod status;
 pub mod trace;
+pub mod watch;
 pub mod wifi;
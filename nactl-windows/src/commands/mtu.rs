@@ -0,0 +1,490 @@
+//! Path MTU discovery command implementation
+//!
+//! Binary-searches the largest echo request that reaches the host with the
+//! don't-fragment bit set. Routers that drop oversized packets without
+//! sending "fragmentation needed" create an MTU black hole: small requests
+//! work while full-size TCP segments stall, so some sites load and others
+//! hang.
+
+use crate::commands::ping::{self, IpFamily, PingEngine};
+use crate::errors::{ExitCodes, NactlError};
+use crate::utils::icmp::{EchoOptions, EchoOutcome, Pinger};
+use crate::utils::netsh;
+use crate::utils::output::{print_output, OutputFormat};
+use regex::Regex;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::process::Command;
+use std::time::Duration;
+
+/// Upper bound when the interface MTU cannot be read (Ethernet)
+const DEFAULT_CEILING: u32 = 1500;
+
+/// Largest IP packet
+const MAX_PACKET: u32 = 65535;
+
+/// Sends per size before it counts as dropped, to ride out random loss
+const ATTEMPTS_PER_SIZE: usize = 2;
+
+/// Outcome of sending one packet size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ProbeResult {
+    Passed,
+    /// "Fragmentation needed" / "packet too big" came back
+    TooBig,
+    /// Nothing came back
+    Lost,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Attempt {
+    result: ProbeResult,
+    /// Next-hop MTU from the router's error, when it sent one
+    reported_mtu: Option<u32>,
+    from: Option<IpAddr>,
+}
+
+impl Attempt {
+    fn passed() -> Self {
+        Self::new(ProbeResult::Passed)
+    }
+
+    fn lost() -> Self {
+        Self::new(ProbeResult::Lost)
+    }
+
+    fn new(result: ProbeResult) -> Self {
+        Self {
+            result,
+            reported_mtu: None,
+            from: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MtuProbe {
+    /// Total IP packet size in bytes
+    size: u32,
+    result: ProbeResult,
+}
+
+#[derive(Debug)]
+struct Search {
+    path_mtu: u32,
+    /// Whether any oversized probe drew a fragmentation-needed error
+    fragmentation_reported: bool,
+    reported_by: Option<IpAddr>,
+    probes: Vec<MtuProbe>,
+}
+
+#[derive(Debug, Serialize)]
+struct MtuData {
+    host: String,
+    resolved_ip: String,
+    engine: PingEngine,
+    interface: Option<String>,
+    interface_mtu: Option<u32>,
+    path_mtu: u32,
+    /// Largest ping payload that fits (`ping -l`)
+    max_payload: u32,
+    /// TCP maximum segment size the path supports
+    tcp_mss: u32,
+    black_hole: bool,
+    /// Router that sent "fragmentation needed"
+    reported_by: Option<String>,
+    recommendation: Option<String>,
+    probes: Vec<MtuProbe>,
+}
+
+#[derive(Debug, Serialize)]
+struct MtuResponse {
+    success: bool,
+    message: String,
+    data: MtuData,
+}
+
+/// Discover the path MTU to a host
+pub fn execute(
+    host: &str,
+    family: Option<IpFamily>,
+    timeout: u32,
+    format: OutputFormat,
+    interface: Option<&str>,
+) -> Result<u8, NactlError> {
+    ping::validate_target(host, family)?;
+
+    let ip = ping::resolve_target(host, family)
+        .ok_or_else(|| NactlError::network_error(format!("Could not resolve {}", host)))?;
+    let header = header_size(ip);
+    let floor = if ip.is_ipv4() { 68 } else { 1280 };
+
+    let (local_interface, interface_mtu) = match interface_mtu(ip, interface) {
+        Some((name, mtu)) => (Some(name), Some(mtu)),
+        None => (None, None),
+    };
+    let ceiling = interface_mtu
+        .unwrap_or(DEFAULT_CEILING)
+        .clamp(floor, MAX_PACKET);
+
+    let timeout = Duration::from_millis(timeout.max(1) as u64);
+    let (engine, search) = match Pinger::new(
+        ip,
+        &EchoOptions {
+            dont_fragment: true,
+            ..EchoOptions::default()
+        },
+    ) {
+        Ok(mut pinger) => {
+            let engine = pinger.mode().into();
+            let mut seq = 0u16;
+            let search = find_path_mtu(floor, ceiling, |size| {
+                seq = seq.wrapping_add(1);
+                pinger.set_size((size - header) as usize);
+                echo_attempt(&pinger, seq, timeout)
+            });
+            (engine, search)
+        }
+        // ping.exe can only set DF on IPv4
+        Err(_) if ip.is_ipv4() => {
+            let target = ip.to_string();
+            let search = find_path_mtu(floor, ceiling, |size| {
+                ping_exe_attempt(&target, size - header, timeout)
+            });
+            (PingEngine::PingExe, search)
+        }
+        Err(_) => {
+            return Err(NactlError::not_available(
+                "IPv6 path MTU discovery needs an ICMP socket; run elevated",
+            ))
+        }
+    };
+
+    let search = search.ok_or_else(|| {
+        NactlError::timeout(format!(
+            "No reply from {} even at {} bytes; cannot measure the path MTU",
+            host, floor
+        ))
+    })?;
+
+    let below_interface = search.path_mtu < ceiling;
+    let black_hole = below_interface && !search.fragmentation_reported;
+
+    let message = if black_hole {
+        format!(
+            "MTU black hole: packets over {} bytes are silently dropped",
+            search.path_mtu
+        )
+    } else if below_interface {
+        format!(
+            "Path MTU {} is below the interface MTU {} (PPPoE or VPN overhead)",
+            search.path_mtu, ceiling
+        )
+    } else {
+        format!("Path MTU is {}", search.path_mtu)
+    };
+
+    // Only a black hole breaks PMTUD; a reported lower MTU fixes itself
+    let recommendation = match (&local_interface, black_hole) {
+        (Some(name), true) => Some(format!(
+            "netsh interface {} set subinterface \"{}\" mtu={} store=persistent",
+            if ip.is_ipv4() { "ipv4" } else { "ipv6" },
+            name,
+            search.path_mtu
+        )),
+        _ => None,
+    };
+
+    let response = MtuResponse {
+        success: true,
+        message,
+        data: MtuData {
+            host: host.to_string(),
+            resolved_ip: ip.to_string(),
+            engine,
+            interface: local_interface,
+            interface_mtu,
+            path_mtu: search.path_mtu,
+            max_payload: search.path_mtu - header,
+            tcp_mss: search.path_mtu - if ip.is_ipv4() { 40 } else { 60 },
+            black_hole,
+            reported_by: search.reported_by.map(|ip| ip.to_string()),
+            recommendation,
+            probes: search.probes,
+        },
+    };
+
+    print_output(&response, format)?;
+    Ok(ExitCodes::Success as u8)
+}
+
+/// IP plus ICMP echo header bytes
+fn header_size(ip: IpAddr) -> u32 {
+    if ip.is_ipv4() {
+        20 + 8
+    } else {
+        40 + 8
+    }
+}
+
+/// Largest size in `floor..=ceiling` that passes, or `None` when even the
+/// floor gets no reply
+fn find_path_mtu(floor: u32, ceiling: u32, mut send: impl FnMut(u32) -> Attempt) -> Option<Search> {
+    let mut found = Search {
+        path_mtu: floor,
+        fragmentation_reported: false,
+        reported_by: None,
+        probes: Vec::new(),
+    };
+
+    let mut try_size = |size: u32, found: &mut Search| {
+        for _ in 0..ATTEMPTS_PER_SIZE {
+            let attempt = send(size);
+            found.probes.push(MtuProbe {
+                size,
+                result: attempt.result,
+            });
+
+            if attempt.result == ProbeResult::TooBig {
+                found.fragmentation_reported = true;
+                found.reported_by = found.reported_by.or(attempt.from);
+            }
+            if attempt.result != ProbeResult::Lost {
+                return attempt;
+            }
+        }
+        Attempt::lost()
+    };
+
+    if try_size(floor, &mut found).result != ProbeResult::Passed {
+        return None;
+    }
+    if try_size(ceiling, &mut found).result == ProbeResult::Passed {
+        found.path_mtu = ceiling;
+        return Some(found);
+    }
+
+    let (mut low, mut high) = (floor, ceiling);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        let attempt = try_size(mid, &mut found);
+
+        if attempt.result == ProbeResult::Passed {
+            low = mid;
+        } else {
+            // A reported next-hop MTU narrows the search in one step
+            high = match attempt.reported_mtu {
+                Some(mtu) if mtu > low && mtu < mid => mtu + 1,
+                _ => mid,
+            };
+        }
+    }
+
+    found.path_mtu = low;
+    Some(found)
+}
+
+fn echo_attempt(pinger: &Pinger, seq: u16, timeout: Duration) -> Attempt {
+    let Ok(reply) = pinger.echo(seq, timeout) else {
+        return Attempt::lost();
+    };
+
+    match reply.outcome {
+        EchoOutcome::EchoReply => Attempt::passed(),
+        EchoOutcome::FragmentationNeeded { mtu } => Attempt {
+            result: ProbeResult::TooBig,
+            reported_mtu: mtu,
+            from: reply.from,
+        },
+        _ => Attempt::lost(),
+    }
+}
+
+fn ping_exe_attempt(target: &str, payload: u32, timeout: Duration) -> Attempt {
+    // -f sets don't-fragment, -l the payload size
+    let output = Command::new("ping")
+        .args([
+            "-f",
+            "-l",
+            &payload.to_string(),
+            "-n",
+            "1",
+            "-w",
+            &timeout.as_millis().to_string(),
+            target,
+        ])
+        .output();
+
+    match output {
+        Ok(output) => classify_ping_exe(&String::from_utf8_lossy(&output.stdout)),
+        Err(_) => Attempt::lost(),
+    }
+}
+
+/// Interpret the output of a single `ping -f -l` run
+fn classify_ping_exe(output: &str) -> Attempt {
+    let responder = Regex::new(r"Reply from (\S+?):\s").unwrap();
+
+    for line in output.lines() {
+        if line.to_lowercase().contains("needs to be fragmented") {
+            return Attempt {
+                result: ProbeResult::TooBig,
+                reported_mtu: None,
+                from: responder
+                    .captures(line)
+                    .and_then(|caps| caps[1].parse().ok()),
+            };
+        }
+        if responder.is_match(line) && line.contains("bytes=") {
+            return Attempt::passed();
+        }
+    }
+    Attempt::lost()
+}
+
+/// Name and MTU of the interface used to reach `ip`
+fn interface_mtu(ip: IpAddr, interface: Option<&str>) -> Option<(String, u32)> {
+    let family = if ip.is_ipv4() { "ipv4" } else { "ipv6" };
+    let output = netsh::run_command(&["interface", family, "show", "subinterfaces"]).ok()?;
+    let subinterfaces = parse_subinterfaces(&output);
+
+    match interface {
+        Some(wanted) => subinterfaces
+            .into_iter()
+            .find(|s| s.name.eq_ignore_ascii_case(wanted)),
+        // Without a route lookup, the busiest connected interface is the
+        // best guess for the one carrying traffic
+        None => subinterfaces
+            .into_iter()
+            .filter(|s| s.connected && !s.name.to_lowercase().contains("loopback"))
+            .max_by_key(|s| s.bytes_in),
+    }
+    .map(|s| (s.name, s.mtu))
+}
+
+#[derive(Debug, PartialEq)]
+struct Subinterface {
+    name: String,
+    mtu: u32,
+    connected: bool,
+    bytes_in: u64,
+}
+
+/// Parse `netsh interface ipv4 show subinterfaces`:
+/// "  1500                1  123456789   12345678  Wi-Fi"
+fn parse_subinterfaces(output: &str) -> Vec<Subinterface> {
+    let row = Regex::new(r"^\s*(\d+)\s+(\d+)\s+(\d+)\s+\d+\s+(\S.*?)\s*$").unwrap();
+
+    output
+        .lines()
+        .filter_map(|line| row.captures(line))
+        .filter_map(|caps| {
+            Some(Subinterface {
+                name: caps[4].to_string(),
+                mtu: caps[1].parse().ok()?,
+                // MediaSenseState 1 is connected
+                connected: &caps[2] == "1",
+                bytes_in: caps[3].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path whose bottleneck is `mtu`; `reports` whether the router says so
+    fn path(mtu: u32, reports: bool) -> impl FnMut(u32) -> Attempt {
+        move |size| {
+            if size <= mtu {
+                Attempt::passed()
+            } else if reports {
+                Attempt {
+                    result: ProbeResult::TooBig,
+                    reported_mtu: Some(mtu),
+                    from: "10.0.0.1".parse().ok(),
+                }
+            } else {
+                Attempt::lost()
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_reported_mtu() {
+        let found = find_path_mtu(68, 1500, path(1492, true)).unwrap();
+        assert_eq!(found.path_mtu, 1492);
+        assert!(found.fragmentation_reported);
+        assert_eq!(found.reported_by, "10.0.0.1".parse().ok());
+    }
+
+    #[test]
+    fn test_search_black_hole() {
+        let found = find_path_mtu(68, 1500, path(1400, false)).unwrap();
+        assert_eq!(found.path_mtu, 1400);
+        assert!(!found.fragmentation_reported);
+        // Every dropped size was retried
+        assert!(found
+            .probes
+            .iter()
+            .filter(|p| p.size == 1500)
+            .all(|p| p.result == ProbeResult::Lost));
+        assert_eq!(found.probes.iter().filter(|p| p.size == 1500).count(), 2);
+    }
+
+    #[test]
+    fn test_search_full_size_and_unreachable() {
+        let found = find_path_mtu(68, 1500, path(9000, false)).unwrap();
+        assert_eq!(found.path_mtu, 1500);
+        assert_eq!(found.probes.len(), 2);
+
+        assert!(find_path_mtu(68, 1500, |_| Attempt::lost()).is_none());
+    }
+
+    #[test]
+    fn test_classify_ping_exe() {
+        let passed = "Pinging 8.8.8.8 with 1464 bytes of data:\n\
+                      Reply from 8.8.8.8: bytes=1464 time=12ms TTL=117\n";
+        assert_eq!(classify_ping_exe(passed).result, ProbeResult::Passed);
+
+        let too_big = "Pinging 8.8.8.8 with 1472 bytes of data:\n\
+                       Reply from 192.168.1.1: Packet needs to be fragmented but DF set.\n";
+        let attempt = classify_ping_exe(too_big);
+        assert_eq!(attempt.result, ProbeResult::TooBig);
+        assert_eq!(attempt.from, "192.168.1.1".parse().ok());
+
+        let local = "Pinging 8.8.8.8 with 1600 bytes of data:\n\
+                     Packet needs to be fragmented but DF set.\n";
+        assert_eq!(classify_ping_exe(local).result, ProbeResult::TooBig);
+
+        let lost = "Pinging 8.8.8.8 with 1472 bytes of data:\nRequest timed out.\n";
+        assert_eq!(classify_ping_exe(lost).result, ProbeResult::Lost);
+    }
+
+    #[test]
+    fn test_parse_subinterfaces() {
+        let output = "
+   MTU  MediaSenseState   Bytes In  Bytes Out  Interface
+------  ---------------  ---------  ---------  -------------
+4294967295                1          0      54321  Loopback Pseudo-Interface 1
+  1500                1  987654321   12345678  Wi-Fi
+  1492                5          0          0  Ethernet 2
+";
+        let subinterfaces = parse_subinterfaces(output);
+
+        assert_eq!(subinterfaces.len(), 3);
+        assert_eq!(
+            subinterfaces[1],
+            Subinterface {
+                name: "Wi-Fi".to_string(),
+                mtu: 1500,
+                connected: true,
+                bytes_in: 987654321,
+            }
+        );
+        assert_eq!(subinterfaces[2].name, "Ethernet 2");
+        assert!(!subinterfaces[2].connected);
+    }
+}
@@ -1,57 +1,443 @@
 //! Ping command implementation
 
+use crate::commands::status;
 use crate::errors::{ExitCodes, NactlError};
+use crate::utils::icmp::{EchoOptions, EchoOutcome, Pinger, SocketMode};
 use crate::utils::output::{print_output, OutputFormat};
-use crate::utils::validation;
+use crate::utils::{interrupt, stats, validation};
 use regex::Regex;
 use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Serialize)]
+/// Time between echo requests, matching ping.exe
+pub const DEFAULT_INTERVAL_MS: u64 = 1000;
+
+/// Seconds between rolling summaries in continuous or timed runs
+pub const DEFAULT_SUMMARY_INTERVAL_SECS: u64 = 60;
+
+/// Echo payload bytes, matching ping.exe
+pub const DEFAULT_PING_SIZE: u32 = 32;
+
+/// Value used in statistics for a `time<1ms` reply: the midpoint of the
+/// only range ping.exe reports for it
+const SUB_MS_ESTIMATE_MS: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl IpFamily {
+    /// Family of a textual address, ignoring any `%zone` suffix
+    pub fn of(address: &str) -> Option<Self> {
+        let address = address.split('%').next().unwrap_or(address);
+        address.parse().ok().map(Self::of_ip)
+    }
+
+    pub fn of_ip(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => IpFamily::Ipv4,
+            IpAddr::V6(_) => IpFamily::Ipv6,
+        }
+    }
+
+    /// Family selected by the `-4` / `-6` flags, if either
+    pub fn from_flags(ipv4: bool, ipv6: bool) -> Option<Self> {
+        match (ipv4, ipv6) {
+            (true, _) => Some(IpFamily::Ipv4),
+            (_, true) => Some(IpFamily::Ipv6),
+            _ => None,
+        }
+    }
+
+    fn flag(self) -> &'static str {
+        match self {
+            IpFamily::Ipv4 => "-4",
+            IpFamily::Ipv6 => "-6",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum PingStatus {
+    Ok,
+    Timeout,
+    HostUnreachable,
+    NetUnreachable,
+    /// A router discarded the packet because its TTL ran out
+    TtlExpired,
+    /// The request could not be sent, e.g. no route or adapter down
+    GeneralFailure,
+    /// Packet exceeded the path MTU with the don't-fragment bit set
+    FragmentationNeeded,
+    /// TCP mode: the host answered but the port refused the connection
+    Refused,
+}
+
+/// Result of one TCP connect attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TcpOutcome {
+    Connected,
+    Refused,
+    Timeout,
+    Error,
+}
+
+/// How the echo requests were sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PingEngine {
+    /// In-process ICMP over an unprivileged datagram socket
+    IcmpDatagram,
+    /// In-process ICMP over a raw socket (elevated)
+    IcmpRaw,
+    /// Parsed output of the system ping command
+    PingExe,
+    /// TCP handshake timing
+    Tcp,
+}
+
+impl From<SocketMode> for PingEngine {
+    fn from(mode: SocketMode) -> Self {
+        match mode {
+            SocketMode::Datagram => PingEngine::IcmpDatagram,
+            SocketMode::Raw => PingEngine::IcmpRaw,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 struct PingResult {
     seq: u32,
+    status: PingStatus,
+    /// Address that answered, when it is not the target (e.g. a router)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    responder_ip: Option<String>,
     ttl: Option<u32>,
     time_ms: Option<f64>,
+    /// Reply arrived in under 1 ms, below ping's timer resolution
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    sub_ms: bool,
+    /// TCP mode connect result
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<TcpOutcome>,
 }
 
 #[derive(Debug, Serialize)]
 struct PingData {
     host: String,
+    engine: PingEngine,
+    /// Destination port in TCP mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
     resolved_ip: Option<String>,
+    /// Address family actually used, from the resolved address
+    address_family: Option<IpFamily>,
+    /// Local address requests were sent from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    /// ICMP payload bytes (not used in TCP mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ttl: Option<u32>,
+    dont_fragment: bool,
+    interval_ms: u64,
     packets_sent: u32,
     packets_received: u32,
     packet_loss_percent: f64,
     min_ms: Option<f64>,
     avg_ms: Option<f64>,
     max_ms: Option<f64>,
+    stddev_ms: Option<f64>,
+    /// RFC 3550 interarrival jitter
+    jitter_ms: Option<f64>,
+    p50_ms: Option<f64>,
+    p90_ms: Option<f64>,
+    p99_ms: Option<f64>,
+    /// Longest run of consecutive lost requests
+    max_loss_burst: u32,
+    /// Estimated VoIP R-factor (0-100) from latency, jitter and loss
+    r_factor: Option<f64>,
+    /// Estimated VoIP Mean Opinion Score (1.0-4.5)
+    mos: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     results: Vec<PingResult>,
 }
 
+impl PingResult {
+    fn new(seq: u32, status: PingStatus) -> Self {
+        Self {
+            seq,
+            status,
+            responder_ip: None,
+            ttl: None,
+            time_ms: None,
+            sub_ms: false,
+            outcome: None,
+        }
+    }
+}
+
+impl PingData {
+    fn new(host: &str, engine: PingEngine, count: u32) -> Self {
+        Self {
+            host: host.to_string(),
+            engine,
+            port: None,
+            resolved_ip: None,
+            address_family: None,
+            source: None,
+            size: Some(DEFAULT_PING_SIZE),
+            ttl: None,
+            dont_fragment: false,
+            interval_ms: DEFAULT_INTERVAL_MS,
+            packets_sent: count,
+            packets_received: 0,
+            packet_loss_percent: 100.0,
+            min_ms: None,
+            avg_ms: None,
+            max_ms: None,
+            stddev_ms: None,
+            jitter_ms: None,
+            p50_ms: None,
+            p90_ms: None,
+            p99_ms: None,
+            max_loss_burst: 0,
+            r_factor: None,
+            mos: None,
+            results: Vec::new(),
+        }
+    }
+
+    /// Same target, engine and settings, no samples
+    fn empty_copy(&self) -> Self {
+        Self {
+            port: self.port,
+            resolved_ip: self.resolved_ip.clone(),
+            address_family: self.address_family,
+            source: self.source.clone(),
+            size: self.size,
+            ttl: self.ttl,
+            dont_fragment: self.dont_fragment,
+            interval_ms: self.interval_ms,
+            ..Self::new(&self.host, self.engine, 0)
+        }
+    }
+
+    /// Record the request settings used for the run
+    fn set_options(&mut self, options: &PingOptions) {
+        self.port = options.tcp_port;
+        self.source = options.source.map(|ip| ip.to_string());
+        self.size = options.tcp_port.is_none().then_some(options.size);
+        self.ttl = options.ttl;
+        self.dont_fragment = options.dont_fragment;
+        self.interval_ms = options.interval;
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct PingResponse {
     success: bool,
     data: PingData,
 }
 
+/// Result for one of several targets
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum HostPing {
+    Data(Box<PingData>),
+    Failed { host: String, error: String },
+}
+
+#[derive(Debug, Serialize)]
+struct PingComparison {
+    total: usize,
+    /// Hosts that answered at least once
+    reachable: usize,
+    unreachable: Vec<String>,
+    /// Reachable host with the lowest average RTT
+    fastest: Option<String>,
+    slowest: Option<String>,
+    /// Reachable host with the most loss, if any lost packets
+    highest_loss: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct MultiPingData {
+    summary: PingComparison,
+    hosts: Vec<HostPing>,
+}
+
+#[derive(Debug, Serialize)]
+struct MultiPingResponse {
+    success: bool,
+    data: MultiPingData,
+}
+
+/// Rolling summary line emitted during continuous or timed runs
+#[derive(Debug, Serialize)]
+struct PingSummary<'a> {
+    event: &'static str,
+    timestamp_ms: u64,
+    #[serde(flatten)]
+    data: &'a PingData,
+}
+
+/// Options for `nactl ping`
+#[derive(Debug, Clone)]
+pub struct PingOptions {
+    pub count: u32,
+    /// Per-request timeout in milliseconds
+    pub timeout: u32,
+    pub family: Option<IpFamily>,
+    /// Time TCP handshakes to this port instead of sending ICMP
+    pub tcp_port: Option<u16>,
+    /// Milliseconds between requests
+    pub interval: u64,
+    /// Run until interrupted instead of stopping after `count`
+    pub continuous: bool,
+    /// Run for this long instead of stopping after `count`
+    pub duration: Option<Duration>,
+    /// Seconds between rolling summaries in continuous or timed runs
+    pub summary_interval: u64,
+    /// Write every sample to this CSV file
+    pub csv: Option<String>,
+    /// Local address to send from
+    pub source: Option<IpAddr>,
+    /// ICMP payload bytes
+    pub size: u32,
+    pub ttl: Option<u32>,
+    pub dont_fragment: bool,
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self {
+            count: 4,
+            timeout: 1000,
+            family: None,
+            tcp_port: None,
+            interval: DEFAULT_INTERVAL_MS,
+            continuous: false,
+            duration: None,
+            summary_interval: DEFAULT_SUMMARY_INTERVAL_SECS,
+            csv: None,
+            source: None,
+            size: DEFAULT_PING_SIZE,
+            ttl: None,
+            dont_fragment: false,
+        }
+    }
+}
+
+impl PingOptions {
+    /// Continuous and timed runs stream summaries instead of a single result
+    fn is_streaming(&self) -> bool {
+        self.continuous || self.duration.is_some()
+    }
+
+    /// ping.exe has no interval flag and cannot stream, so custom intervals,
+    /// continuous runs and CSV logs need one ping.exe run per sample
+    fn fits_single_ping_exe(&self) -> bool {
+        !self.is_streaming() && self.csv.is_none() && self.interval == DEFAULT_INTERVAL_MS
+    }
+
+    fn validate(&self) -> Result<(), NactlError> {
+        validation::validate_ping_size(self.size)?;
+        validation::validate_ping_interval(self.interval)?;
+        if let Some(ttl) = self.ttl {
+            validation::validate_ttl(ttl)?;
+        }
+        if let (Some(wanted), Some(source)) = (self.family, self.source) {
+            if wanted != IpFamily::of_ip(source) {
+                return Err(NactlError::invalid_arguments(format!(
+                    "Source {} is not in the requested address family",
+                    source
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Requested family, or the source address's family
+    fn effective_family(&self) -> Option<IpFamily> {
+        self.family.or(self.source.map(IpFamily::of_ip))
+    }
+
+    fn echo_options(&self) -> EchoOptions {
+        EchoOptions {
+            size: self.size as usize,
+            ttl: self.ttl,
+            dont_fragment: self.dont_fragment,
+            source: self.source,
+        }
+    }
+}
+
+/// Where each sample comes from
+enum Prober {
+    Icmp {
+        pinger: Pinger,
+        ip: IpAddr,
+    },
+    Tcp(SocketAddr),
+    /// One ping.exe run per sample, when no ICMP socket could be opened
+    PingExe,
+}
+
+impl Prober {
+    fn engine(&self) -> PingEngine {
+        match self {
+            Prober::Icmp { pinger, .. } => pinger.mode().into(),
+            Prober::Tcp(_) => PingEngine::Tcp,
+            Prober::PingExe => PingEngine::PingExe,
+        }
+    }
+
+    fn sample(&self, host: &str, seq: u32, options: &PingOptions) -> PingResult {
+        match self {
+            Prober::Icmp { pinger, ip } => icmp_sample(pinger, *ip, seq, options.timeout),
+            Prober::Tcp(target) => tcp_sample(*target, seq, options),
+            Prober::PingExe => {
+                let parsed = run_ping_exe(host, 1, options)
+                    .ok()
+                    .and_then(|data| data.results.into_iter().next());
+                PingResult {
+                    seq,
+                    ..parsed.unwrap_or_else(|| PingResult::new(seq, PingStatus::GeneralFailure))
+                }
+            }
+        }
+    }
+}
+
 pub fn execute(
-    host: &str,
-    count: u32,
-    timeout: u32,
+    hosts: &[String],
+    options: &PingOptions,
     format: OutputFormat,
 ) -> Result<u8, NactlError> {
-    // Validate input to prevent command injection
-    validation::validate_hostname(host)?;
-
-    // Run ping command
-    // Windows ping: -n count, -w timeout (in milliseconds)
-    let output = Command::new("ping")
-        .args(["-n", &count.to_string(), "-w", &timeout.to_string(), host])
-        .output()
-        .map_err(|e| NactlError::command_failed(format!("Failed to run ping: {}", e)))?;
+    // Validate every target before sending anything
+    options.validate()?;
+    for host in hosts {
+        validate_target(host, options.effective_family())?;
+    }
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let data = parse_ping_output(&output_str, host, count)?;
+    let [host] = hosts else {
+        return execute_many(hosts, options, format);
+    };
 
+    let data = ping_host(host, options)?;
     let response = PingResponse {
         success: data.packets_received > 0,
         data,
@@ -66,112 +452,975 @@ pub fn execute(
     }
 }
 
-fn parse_ping_output(output: &str, host: &str, count: u32) -> Result<PingData, NactlError> {
-    let mut data = PingData {
-        host: host.to_string(),
-        resolved_ip: None,
-        packets_sent: count,
-        packets_received: 0,
-        packet_loss_percent: 100.0,
-        min_ms: None,
-        avg_ms: None,
-        max_ms: None,
-        results: Vec::new(),
+/// Ping several hosts concurrently and compare them
+fn execute_many(
+    hosts: &[String],
+    options: &PingOptions,
+    format: OutputFormat,
+) -> Result<u8, NactlError> {
+    if hosts.is_empty() {
+        return Err(NactlError::invalid_arguments("No ping targets given"));
+    }
+    if options.is_streaming() || options.csv.is_some() {
+        return Err(NactlError::invalid_arguments(
+            "--continuous, --duration and --csv take a single target",
+        ));
+    }
+
+    let results: Vec<HostPing> = thread::scope(|scope| {
+        let handles: Vec<_> = hosts
+            .iter()
+            .map(|host| scope.spawn(move || ping_host(host, options)))
+            .collect();
+
+        handles
+            .into_iter()
+            .zip(hosts)
+            .map(|(handle, host)| match handle.join() {
+                Ok(Ok(data)) => HostPing::Data(Box::new(data)),
+                Ok(Err(e)) => HostPing::Failed {
+                    host: host.clone(),
+                    error: e.response.message,
+                },
+                Err(_) => HostPing::Failed {
+                    host: host.clone(),
+                    error: "Ping thread panicked".to_string(),
+                },
+            })
+            .collect()
+    });
+
+    let summary = compare(&results);
+    let any_reachable = summary.reachable > 0;
+    let response = MultiPingResponse {
+        success: any_reachable,
+        data: MultiPingData {
+            summary,
+            hosts: results,
+        },
     };
 
-    // Pattern to extract resolved IP from "Pinging hostname [IP]" or "Pinging IP"
-    let ip_pattern = Regex::new(r"Pinging\s+\S+\s+\[?(\d+\.\d+\.\d+\.\d+)\]?").unwrap();
-    if let Some(caps) = ip_pattern.captures(output) {
-        data.resolved_ip = Some(caps[1].to_string());
+    print_output(&response, format)?;
+
+    if any_reachable {
+        Ok(ExitCodes::Success as u8)
+    } else {
+        Ok(ExitCodes::Timeout as u8)
     }
+}
 
-    // Pattern for individual ping replies
-    // "Reply from 142.250.80.46: bytes=32 time=12ms TTL=117"
-    let reply_pattern =
-        Regex::new(r"Reply from (\d+\.\d+\.\d+\.\d+):.*?(?:time[<=](\d+)ms)?.*?TTL=(\d+)").unwrap();
+/// Rank hosts against each other
+fn compare(results: &[HostPing]) -> PingComparison {
+    let pinged: Vec<&PingData> = results
+        .iter()
+        .filter_map(|r| match r {
+            HostPing::Data(data) => Some(data.as_ref()),
+            HostPing::Failed { .. } => None,
+        })
+        .collect();
+    let reachable: Vec<&PingData> = pinged
+        .into_iter()
+        .filter(|d| d.packets_received > 0)
+        .collect();
+
+    let by_avg = |a: &&PingData, b: &&PingData| {
+        a.avg_ms
+            .unwrap_or(f64::MAX)
+            .total_cmp(&b.avg_ms.unwrap_or(f64::MAX))
+    };
+
+    PingComparison {
+        total: results.len(),
+        reachable: reachable.len(),
+        unreachable: results
+            .iter()
+            .filter(|r| match r {
+                HostPing::Data(data) => data.packets_received == 0,
+                HostPing::Failed { .. } => true,
+            })
+            .map(|r| match r {
+                HostPing::Data(data) => data.host.clone(),
+                HostPing::Failed { host, .. } => host.clone(),
+            })
+            .collect(),
+        fastest: reachable
+            .iter()
+            .copied()
+            .min_by(by_avg)
+            .map(|d| d.host.clone()),
+        slowest: reachable
+            .iter()
+            .copied()
+            .max_by(by_avg)
+            .map(|d| d.host.clone()),
+        highest_loss: reachable
+            .iter()
+            .filter(|d| d.packet_loss_percent > 0.0)
+            .max_by(|a, b| a.packet_loss_percent.total_cmp(&b.packet_loss_percent))
+            .map(|d| d.host.clone()),
+    }
+}
+
+/// Read targets from a file, one per line; blank lines and `#` comments
+/// are ignored
+pub fn read_targets(path: &str) -> Result<Vec<String>, NactlError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        NactlError::invalid_input(format!("Failed to read targets file '{}': {}", path, e))
+    })?;
+
+    Ok(contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Reject unsafe hostnames and literals of the wrong family
+pub fn validate_target(host: &str, family: Option<IpFamily>) -> Result<(), NactlError> {
+    // Validate input to prevent command injection
+    validation::validate_hostname(host)?;
+
+    if let (Some(wanted), Some(literal)) = (family, IpFamily::of(host)) {
+        if wanted != literal {
+            return Err(NactlError::invalid_arguments(format!(
+                "{} is not an {} address",
+                host,
+                if wanted == IpFamily::Ipv4 {
+                    "IPv4"
+                } else {
+                    "IPv6"
+                }
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Ping one validated host with whichever engine is available
+fn ping_host(host: &str, options: &PingOptions) -> Result<PingData, NactlError> {
+    let resolved = resolve_target(host, options.effective_family());
+    if let (Some(ip), Some(source)) = (resolved, options.source) {
+        if ip.is_ipv4() != source.is_ipv4() {
+            return Err(NactlError::invalid_arguments(format!(
+                "{} resolved to {}, which cannot be reached from source {}",
+                host, ip, source
+            )));
+        }
+    }
+
+    let prober = match (resolved, options.tcp_port) {
+        (Some(ip), Some(port)) => Prober::Tcp(SocketAddr::new(ip, port)),
+        (None, Some(_)) => {
+            return Err(NactlError::network_error(format!(
+                "Could not resolve host: {}",
+                host
+            )))
+        }
+        (Some(ip), None) => match Pinger::new(ip, &options.echo_options()) {
+            Ok(pinger) => Prober::Icmp { pinger, ip },
+            // No ICMP socket without elevation on Windows
+            Err(_) => Prober::PingExe,
+        },
+        (None, None) => Prober::PingExe,
+    };
+
+    match prober {
+        // A single ping.exe run parses best, and lets ping.exe report
+        // resolution failures the way it always has
+        Prober::PingExe if options.fits_single_ping_exe() => {
+            let mut data = run_ping_exe(host, options.count, options)?;
+            data.set_options(options);
+            Ok(data)
+        }
+        _ => sample_loop(host, resolved, &prober, options),
+    }
+}
+
+/// Send a single echo request and return the round-trip time on success
+pub fn probe(host: &str, timeout: u32) -> Option<f64> {
+    validation::validate_hostname(host).ok()?;
+
+    let options = PingOptions {
+        count: 1,
+        timeout,
+        ..PingOptions::default()
+    };
+
+    let data = match resolve_target(host, None) {
+        Some(ip) => match Pinger::new(ip, &EchoOptions::default()) {
+            Ok(pinger) => {
+                sample_loop(host, Some(ip), &Prober::Icmp { pinger, ip }, &options).ok()?
+            }
+            Err(_) => run_ping_exe(host, 1, &options).ok()?,
+        },
+        None => run_ping_exe(host, 1, &options).ok()?,
+    };
+
+    if data.packets_received > 0 {
+        Some(data.min_ms.unwrap_or(0.0))
+    } else {
+        None
+    }
+}
+
+/// Source address from `--source` or `--interface`: an IP address, or the
+/// first address of the named interface in the requested family
+pub fn resolve_source(value: &str, family: Option<IpFamily>) -> Result<IpAddr, NactlError> {
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        validation::validate_ip_address(value)?;
+        return Ok(ip);
+    }
+
+    validation::validate_interface_name(value)?;
+    status::interface_addresses(value)?
+        .into_iter()
+        .find(|&ip| family.is_none_or(|f| f == IpFamily::of_ip(ip)))
+        .ok_or_else(|| NactlError::interface_not_found(value))
+}
+
+/// First address for `host` in the requested family
+pub fn resolve_target(host: &str, family: Option<IpFamily>) -> Option<IpAddr> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Some(ip);
+    }
 
-    // Pattern for timeout
-    let timeout_pattern = Regex::new(r"Request timed out|Destination host unreachable").unwrap();
+    (host, 0)
+        .to_socket_addrs()
+        .ok()?
+        .map(|addr| addr.ip())
+        .find(|ip| match family {
+            Some(IpFamily::Ipv4) => ip.is_ipv4(),
+            Some(IpFamily::Ipv6) => ip.is_ipv6(),
+            None => true,
+        })
+}
+
+/// Take samples until the count, duration or Ctrl+C ends the run, emitting
+/// rolling summaries and CSV rows along the way
+fn sample_loop(
+    host: &str,
+    resolved: Option<IpAddr>,
+    prober: &Prober,
+    options: &PingOptions,
+) -> Result<PingData, NactlError> {
+    let streaming = options.is_streaming();
+    let mut csv = options.csv.as_deref().map(CsvLog::create).transpose()?;
+
+    let mut data = PingData::new(host, prober.engine(), 0);
+    data.set_options(options);
+    data.resolved_ip = resolved.map(|ip| ip.to_string());
+    data.address_family = data.resolved_ip.as_deref().and_then(IpFamily::of);
+
+    if streaming {
+        interrupt::install();
+    }
+    let deadline = options.duration.map(|d| Instant::now() + d);
+    let interval = Duration::from_millis(options.interval);
+    let summary_every = Duration::from_secs(options.summary_interval.max(1));
 
+    let mut window = data.empty_copy();
+    let mut window_started = Instant::now();
     let mut seq = 0u32;
-    let mut times: Vec<f64> = Vec::new();
 
-    for line in output.lines() {
-        if let Some(caps) = reply_pattern.captures(line) {
-            seq += 1;
+    loop {
+        let finished = if streaming {
+            interrupt::requested() || deadline.is_some_and(|d| Instant::now() >= d)
+        } else {
+            seq >= options.count
+        };
+        if finished {
+            break;
+        }
 
-            // Get IP if not already set
-            if data.resolved_ip.is_none() {
-                data.resolved_ip = Some(caps[1].to_string());
+        seq += 1;
+        let started = Instant::now();
+        let result = prober.sample(host, seq, options);
+
+        if let Some(csv) = csv.as_mut() {
+            csv.write(&result)?;
+        }
+        if streaming {
+            window.results.push(result.clone());
+            if window_started.elapsed() >= summary_every {
+                summarize(&mut window);
+                window.results.clear();
+                emit_summary(&window)?;
+                window = data.empty_copy();
+                window_started = Instant::now();
             }
+        }
+        data.results.push(result);
+
+        if streaming || seq < options.count {
+            pace(started, interval, deadline);
+        }
+    }
+
+    summarize(&mut data);
+    if streaming {
+        // Per-sample detail is in the summaries and CSV; long runs would
+        // otherwise produce an enormous final object
+        data.results.clear();
+    }
+    Ok(data)
+}
+
+/// Sleep until `interval` after `started`, waking early on Ctrl+C or deadline
+fn pace(started: Instant, interval: Duration, deadline: Option<Instant>) {
+    let mut until = started + interval;
+    if let Some(deadline) = deadline {
+        until = until.min(deadline);
+    }
+
+    while !interrupt::requested() {
+        let now = Instant::now();
+        if now >= until {
+            break;
+        }
+        thread::sleep((until - now).min(Duration::from_millis(100)));
+    }
+}
+
+/// Write a rolling summary as a single line, regardless of --pretty
+fn emit_summary(window: &PingData) -> Result<(), NactlError> {
+    let summary = PingSummary {
+        event: "summary",
+        timestamp_ms: now_ms(),
+        data: window,
+    };
+    let line = serde_json::to_string(&summary)
+        .map_err(|e| NactlError::general_error(format!("JSON serialization failed: {}", e)))?;
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", line)
+        .and_then(|_| stdout.flush())
+        .map_err(|e| NactlError::general_error(format!("Failed to write summary: {}", e)))
+}
+
+/// Per-sample CSV log for graphing drops
+struct CsvLog {
+    file: LineWriter<File>,
+    path: String,
+}
+
+impl CsvLog {
+    fn create(path: &str) -> Result<Self, NactlError> {
+        let file = File::create(path).map_err(|e| {
+            NactlError::invalid_input(format!("Failed to create CSV file '{}': {}", path, e))
+        })?;
+        let mut log = Self {
+            file: LineWriter::new(file),
+            path: path.to_string(),
+        };
+        log.write_line("timestamp_ms,seq,rtt_ms,status")?;
+        Ok(log)
+    }
+
+    fn write(&mut self, result: &PingResult) -> Result<(), NactlError> {
+        let rtt = result.time_ms.map(|t| t.to_string()).unwrap_or_default();
+        let status = serde_json::to_value(result.status)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+        self.write_line(&format!("{},{},{},{}", now_ms(), result.seq, rtt, status))
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), NactlError> {
+        writeln!(self.file, "{}", line).map_err(|e| {
+            NactlError::general_error(format!("Failed to write CSV file '{}': {}", self.path, e))
+        })
+    }
+}
+
+/// One echo request over an ICMP socket
+fn icmp_sample(pinger: &Pinger, ip: IpAddr, seq: u32, timeout: u32) -> PingResult {
+    let timeout = Duration::from_millis(timeout.max(1) as u64);
+    let mut result = PingResult::new(seq, PingStatus::GeneralFailure);
+
+    if let Ok(reply) = pinger.echo(seq as u16, timeout) {
+        result.status = match reply.outcome {
+            EchoOutcome::EchoReply => PingStatus::Ok,
+            EchoOutcome::Timeout => PingStatus::Timeout,
+            EchoOutcome::TimeExceeded => PingStatus::TtlExpired,
+            EchoOutcome::NetUnreachable => PingStatus::NetUnreachable,
+            EchoOutcome::HostUnreachable => PingStatus::HostUnreachable,
+            EchoOutcome::FragmentationNeeded { .. } => PingStatus::FragmentationNeeded,
+        };
+        result.responder_ip = reply.from.filter(|&from| from != ip).map(|a| a.to_string());
+        if result.status == PingStatus::Ok {
+            result.ttl = reply.ttl;
+            result.time_ms = Some(stats::round_to(reply.rtt_ms, 3));
+        }
+    }
+    result
+}
+
+/// One TCP handshake to `target`
+fn tcp_sample(target: SocketAddr, seq: u32, options: &PingOptions) -> PingResult {
+    let timeout = Duration::from_millis(options.timeout.max(1) as u64);
 
-            let time_ms = caps.get(2).and_then(|m| m.as_str().parse::<f64>().ok());
-            let ttl = caps.get(3).and_then(|m| m.as_str().parse::<u32>().ok());
+    let started = Instant::now();
+    let attempt = tcp_connect(target, options, timeout);
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
 
-            if let Some(t) = time_ms {
-                times.push(t);
+    let (status, outcome) = match &attempt {
+        Ok(_) => (PingStatus::Ok, TcpOutcome::Connected),
+        Err(e) => match e.kind() {
+            io::ErrorKind::ConnectionRefused => (PingStatus::Refused, TcpOutcome::Refused),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                (PingStatus::Timeout, TcpOutcome::Timeout)
             }
+            io::ErrorKind::HostUnreachable => (PingStatus::HostUnreachable, TcpOutcome::Error),
+            io::ErrorKind::NetworkUnreachable => (PingStatus::NetUnreachable, TcpOutcome::Error),
+            _ => (PingStatus::GeneralFailure, TcpOutcome::Error),
+        },
+    };
+    // Close right away; only the handshake is measured
+    drop(attempt);
+
+    PingResult {
+        time_ms: (status == PingStatus::Ok).then_some(stats::round_to(elapsed_ms, 3)),
+        outcome: Some(outcome),
+        ..PingResult::new(seq, status)
+    }
+}
 
-            data.results.push(PingResult { seq, ttl, time_ms });
-            data.packets_received += 1;
-        } else if timeout_pattern.is_match(line) {
-            seq += 1;
-            data.results.push(PingResult {
-                seq,
-                ttl: None,
-                time_ms: None,
-            });
+/// Connect from the requested source address and TTL, if any
+fn tcp_connect(target: SocketAddr, options: &PingOptions, timeout: Duration) -> io::Result<Socket> {
+    let socket = Socket::new(
+        Domain::for_address(target),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if let Some(source) = options.source {
+        socket.bind(&SocketAddr::new(source, 0).into())?;
+    }
+    if let Some(ttl) = options.ttl {
+        match target {
+            SocketAddr::V4(_) => socket.set_ttl(ttl)?,
+            SocketAddr::V6(_) => socket.set_unicast_hops_v6(ttl)?,
         }
     }
+    socket.connect_timeout(&target.into(), timeout)?;
+    Ok(socket)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Ping by running the system ping command and parsing its output
+fn run_ping_exe(host: &str, count: u32, options: &PingOptions) -> Result<PingData, NactlError> {
+    // Run ping command
+    // Windows ping: -n count, -w timeout (in milliseconds), -4/-6 family,
+    // -l size, -i TTL, -f don't fragment (IPv4 only), -S source address
+    let mut args = vec![
+        "-n".to_string(),
+        count.to_string(),
+        "-w".to_string(),
+        options.timeout.to_string(),
+        "-l".to_string(),
+        options.size.to_string(),
+    ];
+    let family = options.effective_family();
+    if let Some(family) = family {
+        args.push(family.flag().to_string());
+    }
+    if let Some(ttl) = options.ttl {
+        args.extend(["-i".to_string(), ttl.to_string()]);
+    }
+    if options.dont_fragment && family != Some(IpFamily::Ipv6) {
+        args.push("-f".to_string());
+    }
+    if let Some(source) = options.source {
+        args.extend(["-S".to_string(), source.to_string()]);
+    }
+    args.push(host.to_string());
+
+    let output = Command::new("ping")
+        .args(&args)
+        .output()
+        .map_err(|e| NactlError::command_failed(format!("Failed to run ping: {}", e)))?;
+
+    let output_str = String::from_utf8_lossy(&output.stdout);
+    parse_ping_output(&output_str, host, count)
+}
+
+/// Derive received count, loss and min/avg/max from per-reply results.
+/// Sub-millisecond replies count as `SUB_MS_ESTIMATE_MS`.
+fn summarize(data: &mut PingData) {
+    let times: Vec<f64> = data
+        .results
+        .iter()
+        .filter(|r| r.status == PingStatus::Ok)
+        .filter_map(|r| r.time_ms.or(r.sub_ms.then_some(SUB_MS_ESTIMATE_MS)))
+        .collect();
+
+    data.packets_received = data
+        .results
+        .iter()
+        .filter(|r| r.status == PingStatus::Ok)
+        .count() as u32;
+    data.packets_sent = data.packets_sent.max(data.results.len() as u32);
 
-    // Calculate statistics
     if !times.is_empty() {
         data.min_ms = times.iter().cloned().reduce(f64::min);
         data.max_ms = times.iter().cloned().reduce(f64::max);
         data.avg_ms = Some(times.iter().sum::<f64>() / times.len() as f64);
     }
 
-    // Calculate packet loss
     if data.packets_sent > 0 {
         data.packet_loss_percent =
             ((data.packets_sent - data.packets_received) as f64 / data.packets_sent as f64) * 100.0;
     }
 
-    // Parse statistics line as fallback
-    // "Packets: Sent = 4, Received = 4, Lost = 0 (0% loss)"
-    let stats_pattern =
-        Regex::new(r"Sent\s*=\s*(\d+),\s*Received\s*=\s*(\d+),\s*Lost\s*=\s*(\d+)\s*\((\d+)%")
-            .unwrap();
+    data.stddev_ms = stats::stddev(&times).map(|v| stats::round_to(v, 3));
+    data.jitter_ms = stats::jitter(&times).map(|v| stats::round_to(v, 3));
+    data.p50_ms = stats::percentile(&times, 50.0);
+    data.p90_ms = stats::percentile(&times, 90.0);
+    data.p99_ms = stats::percentile(&times, 99.0);
+
+    let received: Vec<bool> = data
+        .results
+        .iter()
+        .map(|r| r.status == PingStatus::Ok)
+        .collect();
+    data.max_loss_burst = stats::longest_loss_burst(&received);
+
+    if let Some(avg) = data.avg_ms {
+        let r = stats::r_factor(avg, data.jitter_ms.unwrap_or(0.0), data.packet_loss_percent);
+        data.r_factor = Some(stats::round_to(r, 1));
+        data.mos = Some(stats::round_to(stats::mos(r), 2));
+    }
+}
+
+fn parse_ping_output(output: &str, host: &str, count: u32) -> Result<PingData, NactlError> {
+    let mut data = PingData::new(host, PingEngine::PingExe, count);
+
+    // Pattern to extract resolved IP from "Pinging hostname [IP]" or "Pinging IP"
+    // "Pinging google.com [2607:f8b0:4004:c07::66] with 32 bytes of data:"
+    let ip_pattern = Regex::new(r"Pinging\s+(\S+)(?:\s+\[([^\]\s]+)\])?").unwrap();
+    if let Some(caps) = ip_pattern.captures(output) {
+        let address = caps.get(2).or_else(|| caps.get(1)).map(|m| m.as_str());
+        data.resolved_ip = address
+            .filter(|a| IpFamily::of(a).is_some())
+            .map(String::from);
+    }
+
+    // "Reply from 142.250.80.46: bytes=32 time=12ms TTL=117"
+    // "Reply from 192.168.1.1: Destination host unreachable."
+    // "Reply from 2607:f8b0:4004:c07::66: time<1ms" (IPv6 has no TTL)
+    let responder_pattern = Regex::new(r"Reply from (\S+?):\s").unwrap();
+    let time_pattern = Regex::new(r"time([<=])(\d+)ms").unwrap();
+    let ttl_pattern = Regex::new(r"TTL=(\d+)").unwrap();
 
+    let mut seq = 0u32;
+
+    for line in output.lines() {
+        let Some(status) = classify_line(line, &time_pattern) else {
+            continue;
+        };
+        seq += 1;
+
+        let responder = responder_pattern.captures(line).map(|c| c[1].to_string());
+        if status == PingStatus::Ok && data.resolved_ip.is_none() {
+            data.resolved_ip = responder.clone();
+        }
+
+        let mut result = PingResult::new(seq, status);
+        result.responder_ip = responder.filter(|ip| data.resolved_ip.as_ref() != Some(ip));
+
+        if status == PingStatus::Ok {
+            if let Some(caps) = time_pattern.captures(line) {
+                // "time<1ms" only says the reply was faster than the timer
+                // resolution, so it has no exact value
+                if &caps[1] == "<" {
+                    result.sub_ms = true;
+                } else {
+                    result.time_ms = caps[2].parse::<f64>().ok();
+                }
+            }
+            result.ttl = ttl_pattern
+                .captures(line)
+                .and_then(|c| c[1].parse::<u32>().ok());
+        }
+
+        data.results.push(result);
+    }
+
+    data.address_family = data.resolved_ip.as_deref().and_then(IpFamily::of);
+
+    // "Packets: Sent = 4, Received = 4, Lost = 0 (0% loss)"
+    // Only the sent count is taken from the summary: ping counts unreachable
+    // replies from a router as received, so loss comes from reply statuses
+    let stats_pattern = Regex::new(r"Sent\s*=\s*(\d+),\s*Received\s*=\s*(\d+)").unwrap();
     if let Some(caps) = stats_pattern.captures(output) {
         if let Ok(sent) = caps[1].parse::<u32>() {
             data.packets_sent = sent;
         }
-        if let Ok(received) = caps[2].parse::<u32>() {
-            data.packets_received = received;
-        }
-        if let Ok(loss) = caps[4].parse::<f64>() {
-            data.packet_loss_percent = loss;
-        }
     }
+    summarize(&mut data);
 
-    // Parse min/max/avg from statistics
+    // Parse min/max/avg from statistics when some replies had no usable time
     // "Minimum = 12ms, Maximum = 18ms, Average = 15ms"
     let timing_pattern =
         Regex::new(r"Minimum\s*=\s*(\d+)ms,\s*Maximum\s*=\s*(\d+)ms,\s*Average\s*=\s*(\d+)ms")
             .unwrap();
 
-    if let Some(caps) = timing_pattern.captures(output) {
-        if data.min_ms.is_none() {
+    let untimed = data
+        .results
+        .iter()
+        .any(|r| r.status == PingStatus::Ok && r.time_ms.is_none() && !r.sub_ms);
+    if data.results.is_empty() || untimed {
+        if let Some(caps) = timing_pattern.captures(output) {
             data.min_ms = caps[1].parse::<f64>().ok();
-        }
-        if data.max_ms.is_none() {
             data.max_ms = caps[2].parse::<f64>().ok();
-        }
-        if data.avg_ms.is_none() {
             data.avg_ms = caps[3].parse::<f64>().ok();
         }
     }
 
     Ok(data)
 }
+
+/// Status of a single echo attempt, or `None` for lines that are not one
+fn classify_line(line: &str, time_pattern: &Regex) -> Option<PingStatus> {
+    let lower = line.to_lowercase();
+
+    if lower.contains("destination host unreachable") {
+        Some(PingStatus::HostUnreachable)
+    } else if lower.contains("destination net unreachable") {
+        Some(PingStatus::NetUnreachable)
+    } else if lower.contains("ttl expired in transit") {
+        Some(PingStatus::TtlExpired)
+    } else if lower.contains("request timed out") {
+        Some(PingStatus::Timeout)
+    } else if lower.contains("needs to be fragmented") {
+        Some(PingStatus::FragmentationNeeded)
+    } else if lower.contains("general failure") || lower.contains("transmit failed") {
+        Some(PingStatus::GeneralFailure)
+    } else if lower.starts_with("reply from") && time_pattern.is_match(line) {
+        Some(PingStatus::Ok)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED_OUTPUT: &str = "\
+Pinging 192.168.1.50 with 32 bytes of data:
+Reply from 192.168.1.50: bytes=32 time=3ms TTL=64
+Reply from 192.168.1.20: Destination host unreachable.
+Request timed out.
+Reply from 192.168.1.50: bytes=32 time<1ms TTL=64
+
+Ping statistics for 192.168.1.50:
+    Packets: Sent = 4, Received = 3, Lost = 1 (25% loss),
+Approximate round trip times in milli-seconds:
+    Minimum = 0ms, Maximum = 3ms, Average = 1ms
+";
+
+    #[test]
+    fn test_parse_reply_statuses() {
+        let data = parse_ping_output(MIXED_OUTPUT, "192.168.1.50", 4).unwrap();
+        let statuses: Vec<PingStatus> = data.results.iter().map(|r| r.status).collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                PingStatus::Ok,
+                PingStatus::HostUnreachable,
+                PingStatus::Timeout,
+                PingStatus::Ok
+            ]
+        );
+        // The unreachable reply is not counted, unlike ping's own summary
+        assert_eq!(data.packets_received, 2);
+        assert_eq!(data.packet_loss_percent, 50.0);
+        assert_eq!(
+            data.results[1].responder_ip.as_deref(),
+            Some("192.168.1.20")
+        );
+        assert_eq!(data.results[0].responder_ip, None);
+    }
+
+    #[test]
+    fn test_parse_sub_millisecond() {
+        let data = parse_ping_output(MIXED_OUTPUT, "192.168.1.50", 4).unwrap();
+
+        assert!(data.results[3].sub_ms);
+        assert_eq!(data.results[3].time_ms, None);
+        assert_eq!(data.results[3].ttl, Some(64));
+        // The sub-millisecond reply counts as 0.5ms rather than being dropped
+        assert_eq!(data.min_ms, Some(0.5));
+        assert_eq!(data.max_ms, Some(3.0));
+        assert_eq!(data.avg_ms, Some(1.75));
+    }
+
+    #[test]
+    fn test_parse_all_sub_millisecond() {
+        let output = "\
+Pinging 192.168.1.1 with 32 bytes of data:
+Reply from 192.168.1.1: bytes=32 time<1ms TTL=64
+Reply from 192.168.1.1: bytes=32 time<1ms TTL=64
+Reply from 192.168.1.1: bytes=32 time<1ms TTL=64
+Reply from 192.168.1.1: bytes=32 time<1ms TTL=64
+";
+        let data = parse_ping_output(output, "192.168.1.1", 4).unwrap();
+
+        assert_eq!(data.packets_received, 4);
+        assert_eq!(data.min_ms, Some(0.5));
+        assert_eq!(data.avg_ms, Some(0.5));
+        assert_eq!(data.p99_ms, Some(0.5));
+        assert_eq!(data.jitter_ms, Some(0.0));
+        assert!(data.mos.is_some());
+    }
+
+    #[test]
+    fn test_summary_quality() {
+        let output = "\
+Pinging 10.0.0.1 with 32 bytes of data:
+Reply from 10.0.0.1: bytes=32 time=20ms TTL=64
+Request timed out.
+Request timed out.
+Reply from 10.0.0.1: bytes=32 time=36ms TTL=64
+Reply from 10.0.0.1: bytes=32 time=20ms TTL=64
+";
+        let data = parse_ping_output(output, "10.0.0.1", 5).unwrap();
+
+        assert_eq!(data.max_loss_burst, 2);
+        assert_eq!(data.p50_ms, Some(20.0));
+        assert_eq!(data.p99_ms, Some(36.0));
+        // Two 16ms swings: 16/16 = 1, then 1 + (16 - 1)/16
+        assert_eq!(data.jitter_ms, Some(1.938));
+        assert_eq!(data.r_factor, Some(0.0));
+        assert_eq!(data.mos, Some(1.0));
+    }
+
+    #[test]
+    fn test_parse_failures() {
+        let output = "\
+Pinging 8.8.8.8 with 32 bytes of data:
+Reply from 10.0.0.1: TTL expired in transit.
+Reply from 10.0.0.1: Destination net unreachable.
+PING: transmit failed. General failure.
+";
+        let data = parse_ping_output(output, "8.8.8.8", 3).unwrap();
+        let statuses: Vec<PingStatus> = data.results.iter().map(|r| r.status).collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                PingStatus::TtlExpired,
+                PingStatus::NetUnreachable,
+                PingStatus::GeneralFailure
+            ]
+        );
+        assert_eq!(data.packets_received, 0);
+        assert_eq!(data.packet_loss_percent, 100.0);
+    }
+
+    #[test]
+    fn test_parse_ipv6() {
+        let output = "\
+Pinging google.com [2607:f8b0:4004:c07::66] with 32 bytes of data:
+Reply from 2607:f8b0:4004:c07::66: time=12ms
+Reply from 2607:f8b0:4004:c07::66: time<1ms
+Request timed out.
+";
+        let data = parse_ping_output(output, "google.com", 3).unwrap();
+
+        assert_eq!(data.resolved_ip.as_deref(), Some("2607:f8b0:4004:c07::66"));
+        assert_eq!(data.address_family, Some(IpFamily::Ipv6));
+        assert_eq!(data.packets_received, 2);
+        assert_eq!(data.results[0].time_ms, Some(12.0));
+        assert_eq!(data.results[0].ttl, None);
+        assert_eq!(data.results[0].responder_ip, None);
+        assert!(data.results[1].sub_ms);
+    }
+
+    #[test]
+    fn test_parse_ipv4_literal() {
+        let output = "Pinging 1.1.1.1 with 32 bytes of data:\nReply from 1.1.1.1: bytes=32 time=9ms TTL=57\n";
+        let data = parse_ping_output(output, "1.1.1.1", 1).unwrap();
+
+        assert_eq!(data.resolved_ip.as_deref(), Some("1.1.1.1"));
+        assert_eq!(data.address_family, Some(IpFamily::Ipv4));
+    }
+
+    #[test]
+    fn test_ip_family_of() {
+        assert_eq!(IpFamily::of("fe80::1%12"), Some(IpFamily::Ipv6));
+        assert_eq!(IpFamily::of("10.0.0.1"), Some(IpFamily::Ipv4));
+        assert_eq!(IpFamily::of("example.com"), None);
+    }
+
+    fn loopback(prober: Prober, options: &PingOptions) -> PingData {
+        let ip = "127.0.0.1".parse().unwrap();
+        sample_loop("127.0.0.1", Some(ip), &prober, options).unwrap()
+    }
+
+    fn single() -> PingOptions {
+        PingOptions {
+            count: 1,
+            ..PingOptions::default()
+        }
+    }
+
+    #[test]
+    #[ignore = "needs unprivileged ICMP sockets (ping_group_range), root or CAP_NET_RAW; run with --ignored"]
+    fn test_native_ping_loopback() {
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let pinger = Pinger::new(ip, &EchoOptions::default()).unwrap();
+
+        let data = loopback(Prober::Icmp { pinger, ip }, &single());
+        assert_eq!(data.packets_received, 1);
+        assert_eq!(data.results[0].status, PingStatus::Ok);
+        assert_eq!(data.results[0].responder_ip, None);
+        assert!(data.min_ms.is_some());
+        assert_ne!(data.engine, PingEngine::PingExe);
+    }
+
+    #[test]
+    fn test_tcp_ping_connected() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+
+        let options = PingOptions {
+            tcp_port: Some(target.port()),
+            ..single()
+        };
+        let data = loopback(Prober::Tcp(target), &options);
+        assert_eq!(data.engine, PingEngine::Tcp);
+        assert_eq!(data.port, Some(target.port()));
+        assert_eq!(data.packets_received, 1);
+        assert_eq!(data.packet_loss_percent, 0.0);
+        assert_eq!(data.results[0].outcome, Some(TcpOutcome::Connected));
+        assert!(data.avg_ms.is_some());
+    }
+
+    #[test]
+    fn test_timed_run_with_csv() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap();
+        let csv = std::env::temp_dir().join(format!("nactl-ping-{}.csv", std::process::id()));
+
+        let options = PingOptions {
+            interval: 200,
+            duration: Some(Duration::from_millis(1000)),
+            csv: Some(csv.to_string_lossy().to_string()),
+            ..PingOptions::default()
+        };
+        let data = loopback(Prober::Tcp(target), &options);
+
+        let rows = std::fs::read_to_string(&csv).unwrap();
+        std::fs::remove_file(&csv).ok();
+        let lines: Vec<&str> = rows.lines().collect();
+
+        assert_eq!(lines[0], "timestamp_ms,seq,rtt_ms,status");
+        assert!(lines[1].ends_with(",ok"));
+        assert_eq!(lines.len() as u32, data.packets_sent + 1);
+        assert!(data.packets_sent >= 4);
+        // The final aggregate leaves per-sample rows to the CSV
+        assert!(data.results.is_empty());
+        assert_eq!(data.packet_loss_percent, 0.0);
+    }
+
+    #[test]
+    fn test_compare_hosts() {
+        let ping = |host: &str, times: &[Option<f64>]| {
+            let mut data = PingData::new(host, PingEngine::IcmpRaw, 0);
+            for (i, t) in times.iter().enumerate() {
+                let status = if t.is_some() {
+                    PingStatus::Ok
+                } else {
+                    PingStatus::Timeout
+                };
+                data.results.push(PingResult {
+                    time_ms: *t,
+                    ..PingResult::new(i as u32 + 1, status)
+                });
+            }
+            summarize(&mut data);
+            HostPing::Data(Box::new(data))
+        };
+
+        let results = vec![
+            ping("192.168.1.1", &[Some(2.0), Some(3.0)]),
+            ping("8.8.8.8", &[Some(20.0), None]),
+            ping("vpn.example.com", &[None, None]),
+            HostPing::Failed {
+                host: "nowhere.invalid".to_string(),
+                error: "Could not resolve host".to_string(),
+            },
+        ];
+        let summary = compare(&results);
+
+        assert_eq!(summary.total, 4);
+        assert_eq!(summary.reachable, 2);
+        assert_eq!(
+            summary.unreachable,
+            vec!["vpn.example.com", "nowhere.invalid"]
+        );
+        assert_eq!(summary.fastest.as_deref(), Some("192.168.1.1"));
+        assert_eq!(summary.slowest.as_deref(), Some("8.8.8.8"));
+        assert_eq!(summary.highest_loss.as_deref(), Some("8.8.8.8"));
+    }
+
+    #[test]
+    fn test_request_options() {
+        let options = PingOptions {
+            source: "127.0.0.1".parse().ok(),
+            size: 1200,
+            ttl: Some(8),
+            dont_fragment: true,
+            ..single()
+        };
+        assert!(options.validate().is_ok());
+        assert_eq!(options.effective_family(), Some(IpFamily::Ipv4));
+
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        if let Ok(pinger) = Pinger::new(ip, &options.echo_options()) {
+            let data = loopback(Prober::Icmp { pinger, ip }, &options);
+            assert_eq!(data.packets_received, 1);
+            assert_eq!(data.source.as_deref(), Some("127.0.0.1"));
+            assert_eq!(data.size, Some(1200));
+            assert_eq!(data.ttl, Some(8));
+            assert!(data.dont_fragment);
+        }
+
+        let mismatched = PingOptions {
+            family: Some(IpFamily::Ipv6),
+            ..options.clone()
+        };
+        assert!(mismatched.validate().is_err());
+        let too_big = PingOptions {
+            size: 70000,
+            ..options
+        };
+        assert!(too_big.validate().is_err());
+    }
+
+    #[test]
+    fn test_tcp_ping_refused() {
+        // Bind and release a port so nothing is listening on it
+        let target = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let data = loopback(Prober::Tcp(target), &single());
+        assert_eq!(data.packets_received, 0);
+        assert_eq!(data.packet_loss_percent, 100.0);
+        assert_eq!(data.results[0].status, PingStatus::Refused);
+        assert_eq!(data.results[0].outcome, Some(TcpOutcome::Refused));
+        assert_eq!(data.results[0].time_ms, None);
+    }
+}
@@ -0,0 +1,341 @@
+//! Captive portal detection command implementation
+
+use crate::commands::connectivity::{self, ProbeVerdict};
+use crate::errors::{ExitCodes, NactlError};
+use crate::utils::http::{self, Url};
+use crate::utils::output::{print_output, OutputFormat};
+use crate::utils::validation;
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+/// Microsoft NCSI probe endpoint
+const NCSI_URL: &str = "http://www.msftconnecttest.com/connecttest.txt";
+const NCSI_BODY: &str = "Microsoft Connect Test";
+
+/// Android/Chrome style endpoint that returns an empty 204
+const GENERATE_204_URL: &str = "http://connectivitycheck.gstatic.com/generate_204";
+
+/// NCSI DNS probe host and the address it always resolves to
+const NCSI_DNS_HOST: &str = "dns.msftncsi.com";
+const NCSI_DNS_ADDRESS: Ipv4Addr = Ipv4Addr::new(131, 107, 255, 255);
+
+/// A well-known or custom HTTP probe
+struct ProbeSpec {
+    name: String,
+    url: String,
+    expected_status: u16,
+    expected_body: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ProbeOutcome {
+    /// Expected status and content
+    Ok,
+    /// Redirected to another location
+    Redirect,
+    /// Response differed from what the endpoint always returns
+    ContentMismatch,
+    /// Public probe host resolved to a private or loopback address
+    DnsMismatch,
+    /// No HTTP response
+    Unreachable,
+}
+
+#[derive(Debug, Serialize)]
+struct PortalProbe {
+    name: String,
+    url: String,
+    expected_status: u16,
+    result: ProbeOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolved_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    http_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DnsCheck {
+    host: String,
+    expected: String,
+    answers: Vec<String>,
+    matched: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct PortalData {
+    portal_detected: bool,
+    internet_access: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    login_url: Option<String>,
+    probes: Vec<PortalProbe>,
+    dns_checks: Vec<DnsCheck>,
+}
+
+#[derive(Debug, Serialize)]
+struct PortalResponse {
+    success: bool,
+    data: PortalData,
+}
+
+/// Probe well-known endpoints for signs of a captive portal
+pub fn check(
+    url: Option<&str>,
+    expect_status: u16,
+    expect_body: Option<&str>,
+    timeout: u32,
+    format: OutputFormat,
+) -> Result<u8, NactlError> {
+    let mut specs = vec![
+        ProbeSpec {
+            name: "ncsi".to_string(),
+            url: NCSI_URL.to_string(),
+            expected_status: 200,
+            expected_body: Some(NCSI_BODY.to_string()),
+        },
+        ProbeSpec {
+            name: "generate_204".to_string(),
+            url: GENERATE_204_URL.to_string(),
+            expected_status: 204,
+            expected_body: None,
+        },
+    ];
+
+    if let Some(custom) = url {
+        // Validate up front so a bad URL is an argument error, not a probe failure
+        let parsed = Url::parse(custom)?;
+        validation::validate_hostname(&parsed.host)?;
+
+        specs.push(ProbeSpec {
+            name: "custom".to_string(),
+            url: custom.to_string(),
+            expected_status: expect_status,
+            expected_body: expect_body.map(String::from),
+        });
+    }
+
+    let timeout = Duration::from_millis(timeout.max(1) as u64);
+
+    let mut login_url: Option<String> = None;
+    let mut probes = Vec::new();
+    for spec in &specs {
+        let (probe, found_url) = run_probe(spec, timeout);
+        if login_url.is_none() {
+            login_url = found_url;
+        }
+        probes.push(probe);
+    }
+
+    let dns_checks = vec![check_ncsi_dns(timeout)];
+
+    let portal_detected = probes.iter().any(|p| {
+        matches!(
+            p.result,
+            ProbeOutcome::Redirect | ProbeOutcome::ContentMismatch | ProbeOutcome::DnsMismatch
+        )
+    }) || dns_checks
+        .iter()
+        .any(|c| !c.matched && !c.answers.is_empty());
+
+    let internet_access = probes.iter().all(|p| matches!(p.result, ProbeOutcome::Ok));
+
+    let response = PortalResponse {
+        success: true,
+        data: PortalData {
+            portal_detected,
+            internet_access,
+            login_url,
+            probes,
+            dns_checks,
+        },
+    };
+
+    print_output(&response, format)?;
+    Ok(ExitCodes::Success as u8)
+}
+
+/// Run a single HTTP probe, returning its result and any login URL found
+fn run_probe(spec: &ProbeSpec, timeout: Duration) -> (PortalProbe, Option<String>) {
+    let mut probe = PortalProbe {
+        name: spec.name.clone(),
+        url: spec.url.clone(),
+        expected_status: spec.expected_status,
+        result: ProbeOutcome::Unreachable,
+        resolved_ip: None,
+        http_status: None,
+        location: None,
+        latency_ms: None,
+        detail: None,
+    };
+
+    let url = match Url::parse(&spec.url) {
+        Ok(url) => url,
+        Err(e) => {
+            probe.detail = Some(e.response.message);
+            return (probe, None);
+        }
+    };
+
+    let addr = match url.host.parse::<IpAddr>() {
+        Ok(ip) => std::net::SocketAddr::new(ip, url.port),
+        Err(_) => match connectivity::resolve_host(&url.host, url.port, timeout) {
+            Ok((addrs, _)) => addrs[0],
+            Err(detail) => {
+                probe.detail = Some(detail);
+                return (probe, None);
+            }
+        },
+    };
+    probe.resolved_ip = Some(addr.ip().to_string());
+
+    let response = http::get_from(addr, &url, timeout);
+    let verdict = connectivity::check_response(
+        &response,
+        spec.expected_status,
+        spec.expected_body.as_deref(),
+    );
+
+    let mut login_url = None;
+    if let Ok(resp) = &response {
+        probe.http_status = Some(resp.status);
+        probe.latency_ms = Some(resp.elapsed_ms);
+        probe.location = resp.header("Location").map(String::from);
+        if !matches!(verdict, ProbeVerdict::Expected) {
+            login_url = connectivity::find_login_url(resp);
+        }
+    }
+
+    match verdict {
+        ProbeVerdict::Expected => probe.result = ProbeOutcome::Ok,
+        ProbeVerdict::Redirected(_) => {
+            probe.result = ProbeOutcome::Redirect;
+            probe.detail = Some("Probe was redirected".to_string());
+        }
+        ProbeVerdict::Unexpected(detail) => {
+            probe.result = ProbeOutcome::ContentMismatch;
+            probe.detail = Some(detail);
+        }
+        ProbeVerdict::Unreachable(detail) => {
+            probe.result = ProbeOutcome::Unreachable;
+            probe.detail = Some(detail);
+        }
+    }
+
+    // A public probe host answered from a private address is a DNS hijack,
+    // even when whatever answered there happens to serve the right content
+    let hijacked = url.host.parse::<IpAddr>().is_err() && is_local_address(&addr.ip());
+    if hijacked && !matches!(probe.result, ProbeOutcome::Redirect) {
+        probe.result = ProbeOutcome::DnsMismatch;
+        probe.detail = Some(format!(
+            "{} resolved to local address {}",
+            url.host,
+            addr.ip()
+        ));
+    }
+
+    (probe, login_url)
+}
+
+/// NCSI's DNS probe host always resolves to a fixed public address
+fn check_ncsi_dns(timeout: Duration) -> DnsCheck {
+    let mut check = DnsCheck {
+        host: NCSI_DNS_HOST.to_string(),
+        expected: NCSI_DNS_ADDRESS.to_string(),
+        answers: Vec::new(),
+        matched: false,
+        detail: None,
+    };
+
+    match connectivity::resolve_host(NCSI_DNS_HOST, 80, timeout) {
+        Ok((addrs, _)) => {
+            check.answers = addrs.iter().map(|a| a.ip().to_string()).collect();
+            check.answers.dedup();
+            check.matched = addrs.iter().any(|a| a.ip() == IpAddr::V4(NCSI_DNS_ADDRESS));
+            if !check.matched {
+                check.detail = Some("DNS answer differs from the published address".to_string());
+            }
+        }
+        Err(detail) => check.detail = Some(detail),
+    }
+
+    check
+}
+
+/// Addresses a public probe host should never resolve to
+fn is_local_address(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified()
+        }
+        IpAddr::V6(v6) => v6.is_loopback() || v6.is_unspecified(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::http::stand_in_server;
+
+    fn spec_for(port: u16, expected_status: u16, expected_body: Option<&str>) -> ProbeSpec {
+        ProbeSpec {
+            name: "custom".to_string(),
+            url: format!("http://127.0.0.1:{}/generate_204", port),
+            expected_status,
+            expected_body: expected_body.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_probe_generate_204_ok() {
+        let port = stand_in_server("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n");
+        let (probe, login_url) = run_probe(&spec_for(port, 204, None), Duration::from_secs(2));
+
+        assert!(matches!(probe.result, ProbeOutcome::Ok));
+        assert_eq!(probe.http_status, Some(204));
+        assert!(login_url.is_none());
+    }
+
+    #[test]
+    fn test_probe_injected_content() {
+        let port = stand_in_server(
+            "HTTP/1.1 200 OK\r\n\r\n<html><meta http-equiv=\"refresh\" content=\"0;url=http://192.168.3.1/login\"></html>",
+        );
+        let (probe, login_url) = run_probe(&spec_for(port, 204, None), Duration::from_secs(2));
+
+        assert!(matches!(probe.result, ProbeOutcome::ContentMismatch));
+        assert_eq!(login_url.as_deref(), Some("http://192.168.3.1/login"));
+    }
+
+    #[test]
+    fn test_probe_redirect() {
+        let port = stand_in_server(
+            "HTTP/1.1 307 Temporary Redirect\r\nLocation: https://hotspot.example.net/?orig=x\r\n\r\n",
+        );
+        let (probe, login_url) = run_probe(
+            &spec_for(port, 200, Some(NCSI_BODY)),
+            Duration::from_secs(2),
+        );
+
+        assert!(matches!(probe.result, ProbeOutcome::Redirect));
+        assert_eq!(
+            login_url.as_deref(),
+            Some("https://hotspot.example.net/?orig=x")
+        );
+    }
+
+    #[test]
+    fn test_is_local_address() {
+        assert!(is_local_address(&"10.0.0.1".parse().unwrap()));
+        assert!(is_local_address(&"172.16.5.4".parse().unwrap()));
+        assert!(!is_local_address(&"131.107.255.255".parse().unwrap()));
+    }
+}
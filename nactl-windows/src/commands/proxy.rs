@@ -4,26 +4,26 @@ use crate::errors::{ExitCodes, NactlError};
 use crate::utils::admin;
 use crate::utils::output::{print_output, OutputFormat};
 use crate::utils::registry;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize)]
-struct ProxyEndpoint {
-    enabled: bool,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyEndpoint {
+    pub enabled: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    server: Option<String>,
+    pub server: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    port: Option<u16>,
+    pub port: Option<u16>,
 }
 
-#[derive(Debug, Serialize)]
-struct ProxyConfig {
-    http_proxy: ProxyEndpoint,
-    https_proxy: ProxyEndpoint,
-    socks_proxy: ProxyEndpoint,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    pub http_proxy: ProxyEndpoint,
+    pub https_proxy: ProxyEndpoint,
+    pub socks_proxy: ProxyEndpoint,
     #[serde(skip_serializing_if = "Option::is_none")]
-    auto_config_url: Option<String>,
+    pub auto_config_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    bypass_list: Option<Vec<String>>,
+    pub bypass_list: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -83,7 +83,8 @@ pub fn clear(format: OutputFormat) -> Result<u8, NactlError> {
     Ok(ExitCodes::Success as u8)
 }
 
-fn read_proxy_config() -> Result<ProxyConfig, NactlError> {
+/// Read the current user's WinINet proxy settings
+pub fn read_proxy_config() -> Result<ProxyConfig, NactlError> {
     // Read from Windows Registry:
     // HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings
     // - ProxyEnable (DWORD): 0 or 1
@@ -0,0 +1,500 @@
+//! Subnet host discovery command implementation
+
+use crate::commands::ping;
+use crate::errors::{ExitCodes, NactlError};
+use crate::utils::output::{print_output, OutputFormat};
+use crate::utils::{netsh, resolve, stats, validation};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Ports tried by TCP discovery: SSH, HTTP, HTTPS, SMB, RDP, raw printing
+pub const DEFAULT_SCAN_PORTS: &str = "22,80,443,445,3389,9100";
+
+/// Largest sweep allowed (a /20)
+const MAX_SCAN_ADDRESSES: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Icmp,
+    Tcp,
+    Both,
+}
+
+impl Method {
+    fn parse(method: &str) -> Result<Self, NactlError> {
+        match method.to_lowercase().as_str() {
+            "icmp" => Ok(Method::Icmp),
+            "tcp" => Ok(Method::Tcp),
+            "both" => Ok(Method::Both),
+            _ => Err(NactlError::invalid_arguments(
+                "Method must be 'icmp', 'tcp' or 'both'",
+            )),
+        }
+    }
+
+    fn uses_icmp(self) -> bool {
+        self != Method::Tcp
+    }
+
+    fn uses_tcp(self) -> bool {
+        self != Method::Icmp
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ScanHost {
+    ip: String,
+    rtt_ms: Option<f64>,
+    hostname: Option<String>,
+    mac: Option<String>,
+    open_ports: Vec<u16>,
+    /// How the host was found: icmp, tcp and/or arp
+    detected_by: Vec<&'static str>,
+}
+
+impl ScanHost {
+    fn new(ip: Ipv4Addr) -> Self {
+        ScanHost {
+            ip: ip.to_string(),
+            rtt_ms: None,
+            hostname: None,
+            mac: None,
+            open_ports: Vec::new(),
+            detected_by: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ScanHostsData {
+    network: String,
+    method: String,
+    ports: Vec<u16>,
+    addresses_scanned: usize,
+    alive_count: usize,
+    elapsed_ms: u64,
+    hosts: Vec<ScanHost>,
+}
+
+#[derive(Debug, Serialize)]
+struct ScanHostsResponse {
+    success: bool,
+    data: ScanHostsData,
+}
+
+/// Sweep a subnet for live hosts
+pub fn hosts(
+    cidr: &str,
+    method: &str,
+    ports: &str,
+    timeout: u32,
+    concurrency: usize,
+    format: OutputFormat,
+) -> Result<u8, NactlError> {
+    let (network, prefix) = parse_cidr(cidr)?;
+    let method = Method::parse(method)?;
+    let ports = if method.uses_tcp() {
+        parse_ports(ports)?
+    } else {
+        Vec::new()
+    };
+
+    let addresses = hosts_in(network, prefix);
+    let started = Instant::now();
+
+    let probed = parallel_map(&addresses, concurrency, |&ip| {
+        probe_host(ip, method, &ports, timeout)
+    });
+    let mut found: HashMap<Ipv4Addr, ScanHost> = addresses
+        .iter()
+        .zip(probed)
+        .filter_map(|(&ip, host)| host.map(|h| (ip, h)))
+        .collect();
+
+    // Probing populated the neighbor table; firewalled hosts that ignored
+    // ICMP and TCP still had to answer ARP. Only entries confirmed just now
+    // count as hosts: stale ones may be devices that left long ago, so they
+    // only lend a MAC to hosts the probes found.
+    for neighbor in read_neighbors() {
+        if addresses.binary_search(&neighbor.ip).is_err() {
+            continue;
+        }
+        if neighbor.state.is_live() {
+            let host = found
+                .entry(neighbor.ip)
+                .or_insert_with(|| ScanHost::new(neighbor.ip));
+            host.mac = Some(neighbor.mac);
+            host.detected_by.push("arp");
+        } else if let Some(host) = found.get_mut(&neighbor.ip) {
+            host.mac = Some(neighbor.mac);
+        }
+    }
+
+    let mut hosts: Vec<ScanHost> = found.into_values().collect();
+    hosts.sort_by_key(|h| h.ip.parse::<Ipv4Addr>().ok());
+
+    let names = parallel_map(&hosts, concurrency, |host| {
+        host.ip
+            .parse::<IpAddr>()
+            .ok()
+            .and_then(resolve::reverse_lookup)
+    });
+    for (host, name) in hosts.iter_mut().zip(names) {
+        host.hostname = name;
+    }
+
+    let response = ScanHostsResponse {
+        success: true,
+        data: ScanHostsData {
+            network: format!("{}/{}", network, prefix),
+            method: format!("{:?}", method).to_lowercase(),
+            ports,
+            addresses_scanned: addresses.len(),
+            alive_count: hosts.len(),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            hosts,
+        },
+    };
+
+    print_output(&response, format)?;
+    Ok(ExitCodes::Success as u8)
+}
+
+/// Parse `a.b.c.d/nn` into its network address and prefix length
+fn parse_cidr(cidr: &str) -> Result<(Ipv4Addr, u8), NactlError> {
+    let (address, prefix) = cidr.split_once('/').ok_or_else(|| {
+        NactlError::invalid_arguments(format!(
+            "Expected a subnet like 192.168.1.0/24, got '{}'",
+            cidr
+        ))
+    })?;
+
+    validation::validate_ip_address(address)?;
+    let address: Ipv4Addr = address.parse().map_err(|_| {
+        NactlError::invalid_arguments(format!("Only IPv4 subnets can be scanned: {}", cidr))
+    })?;
+    let prefix: u8 = match prefix.parse() {
+        Ok(p) if p <= 32 => p,
+        _ => {
+            return Err(NactlError::invalid_arguments(format!(
+                "Invalid prefix length: /{}",
+                prefix
+            )))
+        }
+    };
+
+    let size = 1u64 << (32 - prefix as u32);
+    if size > MAX_SCAN_ADDRESSES as u64 {
+        return Err(NactlError::invalid_arguments(format!(
+            "Subnet too large: /{} has {} addresses (max {}, i.e. /20)",
+            prefix, size, MAX_SCAN_ADDRESSES
+        )));
+    }
+
+    let mask = if prefix == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix as u32)
+    };
+    Ok((Ipv4Addr::from(u32::from(address) & mask), prefix))
+}
+
+/// Usable host addresses, excluding network and broadcast where they exist
+fn hosts_in(network: Ipv4Addr, prefix: u8) -> Vec<Ipv4Addr> {
+    let start = u32::from(network);
+    let size = 1u32 << (32 - prefix as u32);
+
+    // /31 point-to-point links and /32 single hosts have no broadcast
+    let range = if prefix >= 31 {
+        start..start + size
+    } else {
+        start + 1..start + size - 1
+    };
+    range.map(Ipv4Addr::from).collect()
+}
+
+fn parse_ports(list: &str) -> Result<Vec<u16>, NactlError> {
+    let ports: Vec<u16> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.parse::<u16>() {
+            Ok(port) if port > 0 => Ok(port),
+            _ => Err(NactlError::invalid_arguments(format!(
+                "Invalid port '{}'",
+                s
+            ))),
+        })
+        .collect::<Result<_, _>>()?;
+
+    if ports.is_empty() {
+        return Err(NactlError::invalid_arguments(
+            "At least one port is required for TCP discovery",
+        ));
+    }
+    Ok(ports)
+}
+
+/// Probe one address, returning it if anything answered
+fn probe_host(ip: Ipv4Addr, method: Method, ports: &[u16], timeout: u32) -> Option<ScanHost> {
+    let mut host = ScanHost::new(ip);
+
+    if method.uses_icmp() {
+        if let Some(rtt) = ping::probe(&host.ip, timeout) {
+            host.rtt_ms = Some(rtt);
+            host.detected_by.push("icmp");
+        }
+    }
+
+    if method.uses_tcp() {
+        let timeout = Duration::from_millis(timeout.max(1) as u64);
+        let mut answered = false;
+
+        for &port in ports {
+            let started = Instant::now();
+            let result = TcpStream::connect_timeout(&SocketAddr::new(ip.into(), port), timeout);
+            let elapsed = stats::round_to(started.elapsed().as_secs_f64() * 1000.0, 3);
+
+            // A refused connection still proves something is at the address
+            let responded = match &result {
+                Ok(_) => {
+                    host.open_ports.push(port);
+                    true
+                }
+                Err(e) => e.kind() == io::ErrorKind::ConnectionRefused,
+            };
+            if responded {
+                answered = true;
+                host.rtt_ms.get_or_insert(elapsed);
+            }
+        }
+
+        if answered {
+            host.detected_by.push("tcp");
+        }
+    }
+
+    (!host.detected_by.is_empty()).then_some(host)
+}
+
+/// Neighbor cache state, as shown by `netsh interface ipv4 show neighbors`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NeighborState {
+    Reachable,
+    Delay,
+    Probe,
+    Stale,
+    Permanent,
+    Other,
+}
+
+impl NeighborState {
+    fn parse(s: &str) -> Self {
+        // "Reachable (Router)" carries a suffix
+        match s
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .to_lowercase()
+            .as_str()
+        {
+            "reachable" => NeighborState::Reachable,
+            "delay" => NeighborState::Delay,
+            "probe" => NeighborState::Probe,
+            "stale" => NeighborState::Stale,
+            "permanent" => NeighborState::Permanent,
+            _ => NeighborState::Other,
+        }
+    }
+
+    /// The device answered recently (or is being confirmed right now)
+    fn is_live(self) -> bool {
+        matches!(
+            self,
+            NeighborState::Reachable | NeighborState::Delay | NeighborState::Probe
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Neighbor {
+    ip: Ipv4Addr,
+    mac: String,
+    state: NeighborState,
+}
+
+/// IPv4 neighbor table entries with a real unicast MAC
+fn read_neighbors() -> Vec<Neighbor> {
+    netsh::run_command(&["interface", "ipv4", "show", "neighbors"])
+        .map(|output| parse_neighbors(&output))
+        .unwrap_or_default()
+}
+
+/// Parse `netsh interface ipv4 show neighbors` output:
+/// "192.168.1.1                                   a4-2b-b0-11-22-33  Reachable"
+fn parse_neighbors(output: &str) -> Vec<Neighbor> {
+    let entry_pattern = Regex::new(
+        r"^\s*(\d+\.\d+\.\d+\.\d+)\s+([0-9a-fA-F]{2}(?:[-:][0-9a-fA-F]{2}){5})\s+(\S.*?)\s*$",
+    )
+    .unwrap();
+
+    output
+        .lines()
+        .filter_map(|line| entry_pattern.captures(line))
+        .filter_map(|caps| {
+            let ip: Ipv4Addr = caps[1].parse().ok()?;
+            let mac = caps[2].to_lowercase().replace('-', ":");
+
+            // Broadcast, multicast (01:00:5e:...) and unresolved
+            // (00:00:00:00:00:00) entries are not devices
+            let first_octet = u8::from_str_radix(&mac[..2], 16).ok()?;
+            if mac == "ff:ff:ff:ff:ff:ff" || mac == "00:00:00:00:00:00" || first_octet & 1 == 1 {
+                return None;
+            }
+            Some(Neighbor {
+                ip,
+                mac,
+                state: NeighborState::parse(&caps[3]),
+            })
+        })
+        .collect()
+}
+
+/// Run `f` over `items` on up to `workers` threads, preserving order
+fn parallel_map<T, R, F>(items: &[T], workers: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, items.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                if let Ok(mut results) = results.lock() {
+                    results[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cidr() {
+        assert_eq!(
+            parse_cidr("192.168.1.77/24").unwrap(),
+            (Ipv4Addr::new(192, 168, 1, 0), 24)
+        );
+        assert!(parse_cidr("192.168.1.0").is_err());
+        assert!(parse_cidr("192.168.1.0/33").is_err());
+        assert!(parse_cidr("10.0.0.0/8").is_err());
+        assert!(parse_cidr("192.168.1.0;rm/24").is_err());
+    }
+
+    #[test]
+    fn test_hosts_in() {
+        let hosts = hosts_in(Ipv4Addr::new(192, 168, 1, 0), 24);
+        assert_eq!(hosts.len(), 254);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 1, 254));
+
+        assert_eq!(hosts_in(Ipv4Addr::new(10, 0, 0, 4), 30).len(), 2);
+        assert_eq!(hosts_in(Ipv4Addr::new(10, 0, 0, 4), 32).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_neighbors() {
+        let output = "
+Interface 11: Wi-Fi
+
+
+Internet Address                              Physical Address   Type
+--------------------------------------------  -----------------  -----------
+192.168.1.1                                   a4-2b-b0-11-22-33  Reachable (Router)
+192.168.1.45                                  00-1b-a9-44-55-66  Stale
+192.168.1.50                                  00-1b-a9-77-88-99  Delay
+192.168.1.60                                  00-00-00-00-00-00  Unreachable
+192.168.1.255                                 ff-ff-ff-ff-ff-ff  Permanent
+224.0.0.22                                    01-00-5e-00-00-16  Permanent
+";
+        let neighbors = parse_neighbors(output);
+        let summary: Vec<(Ipv4Addr, &str, NeighborState)> = neighbors
+            .iter()
+            .map(|n| (n.ip, n.mac.as_str(), n.state))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    Ipv4Addr::new(192, 168, 1, 1),
+                    "a4:2b:b0:11:22:33",
+                    NeighborState::Reachable
+                ),
+                (
+                    Ipv4Addr::new(192, 168, 1, 45),
+                    "00:1b:a9:44:55:66",
+                    NeighborState::Stale
+                ),
+                (
+                    Ipv4Addr::new(192, 168, 1, 50),
+                    "00:1b:a9:77:88:99",
+                    NeighborState::Delay
+                ),
+            ]
+        );
+
+        // A stale entry may be a device that has since left
+        assert!(neighbors[0].state.is_live());
+        assert!(!neighbors[1].state.is_live());
+        assert!(neighbors[2].state.is_live());
+    }
+
+    #[test]
+    fn test_probe_host_tcp() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let host = probe_host(Ipv4Addr::LOCALHOST, Method::Tcp, &[closed, open], 500).unwrap();
+        assert_eq!(host.open_ports, vec![open]);
+        assert_eq!(host.detected_by, vec!["tcp"]);
+        assert!(host.rtt_ms.is_some());
+    }
+
+    #[test]
+    fn test_parallel_map_keeps_order() {
+        let items: Vec<u32> = (0..100).collect();
+        let doubled = parallel_map(&items, 8, |&n| n * 2);
+        assert_eq!(doubled, (0..100).map(|n| n * 2).collect::<Vec<_>>());
+    }
+}
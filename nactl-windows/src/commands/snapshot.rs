@@ -0,0 +1,405 @@
+//! Status snapshot save and compare
+//!
+//! A snapshot captures a machine's known-good network state. Comparing the
+//! current state against it produces a field-by-field diff where each change
+//! is classified as expected (normal churn such as DHCP renewals or roaming)
+//! or suspicious (the kind of drift that breaks connectivity).
+
+use crate::commands::proxy::{self, ProxyConfig};
+use crate::commands::status::NetworkStatus;
+use crate::errors::{ExitCodes, NactlError};
+use crate::utils::output::{print_output, OutputFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Snapshot file format version
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    pub version: u32,
+    pub captured_at_ms: u64,
+    pub status: NetworkStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeClass {
+    Expected,
+    Suspicious,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+    pub classification: ChangeClass,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CompareData {
+    baseline: String,
+    baseline_captured_at_ms: u64,
+    drifted: bool,
+    suspicious_count: usize,
+    expected_count: usize,
+    changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+struct CompareResponse {
+    success: bool,
+    data: CompareData,
+}
+
+/// Write the current status and proxy configuration to `path`
+pub fn save(path: &str, status: &NetworkStatus) -> Result<(), NactlError> {
+    let snapshot = StatusSnapshot {
+        version: SNAPSHOT_VERSION,
+        captured_at_ms: now_ms(),
+        status: status.clone(),
+        proxy: proxy::read_proxy_config().ok(),
+    };
+
+    let json = serde_json::to_string_pretty(&snapshot)
+        .map_err(|e| NactlError::general_error(format!("JSON serialization failed: {}", e)))?;
+
+    fs::write(path, json).map_err(|e| {
+        NactlError::general_error(format!("Failed to write snapshot '{}': {}", path, e))
+    })
+}
+
+/// Compare the current status against the snapshot saved at `path`
+pub fn compare(
+    path: &str,
+    current: &NetworkStatus,
+    format: OutputFormat,
+) -> Result<u8, NactlError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        NactlError::invalid_input(format!("Failed to read snapshot '{}': {}", path, e))
+    })?;
+    let baseline: StatusSnapshot = serde_json::from_str(&contents).map_err(|e| {
+        NactlError::invalid_input(format!("Invalid snapshot file '{}': {}", path, e))
+    })?;
+
+    let current = StatusSnapshot {
+        version: SNAPSHOT_VERSION,
+        captured_at_ms: now_ms(),
+        status: current.clone(),
+        proxy: proxy::read_proxy_config().ok(),
+    };
+
+    let changes = diff(&baseline, &current);
+    let suspicious_count = changes
+        .iter()
+        .filter(|c| c.classification == ChangeClass::Suspicious)
+        .count();
+
+    let response = CompareResponse {
+        success: true,
+        data: CompareData {
+            baseline: path.to_string(),
+            baseline_captured_at_ms: baseline.captured_at_ms,
+            drifted: suspicious_count > 0,
+            suspicious_count,
+            expected_count: changes.len() - suspicious_count,
+            changes,
+        },
+    };
+
+    print_output(&response, format)?;
+    Ok(ExitCodes::Success as u8)
+}
+
+/// Field-by-field differences between two snapshots
+pub fn diff(baseline: &StatusSnapshot, current: &StatusSnapshot) -> Vec<FieldChange> {
+    let mut before = Map::new();
+    let mut after = Map::new();
+    flatten("status", &status_value(&baseline.status), &mut before);
+    flatten("status", &status_value(&current.status), &mut after);
+    flatten(
+        "proxy",
+        &serde_json::to_value(&baseline.proxy).unwrap_or_default(),
+        &mut before,
+    );
+    flatten(
+        "proxy",
+        &serde_json::to_value(&current.proxy).unwrap_or_default(),
+        &mut after,
+    );
+
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    let context = Context::new(&baseline.status, &current.status);
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let old = before.get(field).cloned().unwrap_or(Value::Null);
+            let new = after.get(field).cloned().unwrap_or(Value::Null);
+            if old == new {
+                return None;
+            }
+
+            let (classification, reason) = classify(field, &old, &new, &context);
+            Some(FieldChange {
+                field: field.clone(),
+                before: old,
+                after: new,
+                classification,
+                reason: reason.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Status without the live connectivity assessment, which is not a setting
+fn status_value(status: &NetworkStatus) -> Value {
+    let mut status = status.clone();
+    status.connectivity = None;
+    serde_json::to_value(&status).unwrap_or_default()
+}
+
+/// Flatten nested objects into dotted paths; arrays stay whole so order
+/// changes (e.g. DNS server order) show up as a single field change
+fn flatten(prefix: &str, value: &Value, out: &mut Map<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                flatten(&format!("{}.{}", prefix, key), child, out);
+            }
+        }
+        Value::Null => {}
+        other => {
+            out.insert(prefix.to_string(), other.clone());
+        }
+    }
+}
+
+/// Facts about the two states that change how individual fields are judged
+struct Context {
+    network_changed: bool,
+    same_subnet: bool,
+}
+
+impl Context {
+    fn new(before: &NetworkStatus, after: &NetworkStatus) -> Self {
+        let network_changed = before.ssid != after.ssid || before.interface != after.interface;
+
+        let same_subnet = match (
+            subnet(before.ip_address.as_deref(), before.subnet_mask.as_deref()),
+            subnet(after.ip_address.as_deref(), after.subnet_mask.as_deref()),
+        ) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        };
+
+        Self {
+            network_changed,
+            same_subnet,
+        }
+    }
+}
+
+fn subnet(ip: Option<&str>, mask: Option<&str>) -> Option<(u32, u32)> {
+    let ip: Ipv4Addr = ip?.parse().ok()?;
+    let mask: Ipv4Addr = mask?.parse().ok()?;
+    let mask = u32::from(mask);
+    Some((u32::from(ip) & mask, mask))
+}
+
+fn classify(
+    field: &str,
+    before: &Value,
+    after: &Value,
+    ctx: &Context,
+) -> (ChangeClass, &'static str) {
+    use ChangeClass::{Expected, Suspicious};
+
+    if field.starts_with("proxy.") {
+        return (Suspicious, "Proxy configuration changed");
+    }
+
+    match field.trim_start_matches("status.") {
+        "connected" if after == &Value::Bool(false) => (Suspicious, "Connection lost"),
+        "connected" => (Expected, "Connection restored"),
+        "type" | "interface" => (Expected, "Different adapter in use"),
+        "ssid" => (Expected, "Joined a different network"),
+        "bssid" if ctx.network_changed => (Expected, "Different network"),
+        "bssid" => (Expected, "Roamed to another access point"),
+        "signal_strength" | "signal_rssi" | "channel" | "frequency" | "link_speed" => {
+            (Expected, "Radio conditions vary")
+        }
+        "ip_address" => {
+            let apipa = after
+                .as_str()
+                .map(|ip| ip.starts_with("169.254."))
+                .unwrap_or(false);
+            if apipa {
+                (Suspicious, "Self-assigned address (DHCP failed)")
+            } else if after.is_null() {
+                (Suspicious, "IPv4 address lost")
+            } else if ctx.network_changed {
+                (Expected, "Different network")
+            } else if ctx.same_subnet {
+                (Expected, "New DHCP lease in the same subnet")
+            } else {
+                (
+                    Suspicious,
+                    "Address moved to a different subnet on the same network",
+                )
+            }
+        }
+        "ipv6_addresses" => (Expected, "IPv6 privacy addresses rotate"),
+        "mac_address" if ctx.network_changed => (Expected, "Different adapter or per-network MAC"),
+        "mac_address" => (Suspicious, "Hardware address changed on the same network"),
+        _ if ctx.network_changed => (Expected, "Different network"),
+        "dns_servers" if is_reordered(before, after) => (Suspicious, "DNS server order changed"),
+        "subnet_mask" => (Suspicious, "Subnet mask changed on the same network"),
+        "gateway" => (Suspicious, "Default gateway changed on the same network"),
+        "dns_servers" => (Suspicious, "DNS servers changed on the same network"),
+        _ => (Suspicious, "Configuration changed"),
+    }
+}
+
+/// Same entries in a different order
+fn is_reordered(before: &Value, after: &Value) -> bool {
+    match (before.as_array(), after.as_array()) {
+        (Some(a), Some(b)) if a.len() == b.len() => {
+            let mut a: Vec<String> = a.iter().map(Value::to_string).collect();
+            let mut b: Vec<String> = b.iter().map(Value::to_string).collect();
+            a.sort();
+            b.sort();
+            a == b
+        }
+        _ => false,
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(status: NetworkStatus) -> StatusSnapshot {
+        StatusSnapshot {
+            version: SNAPSHOT_VERSION,
+            captured_at_ms: 0,
+            status,
+            proxy: None,
+        }
+    }
+
+    fn office() -> NetworkStatus {
+        NetworkStatus {
+            connected: true,
+            interface: Some("Wi-Fi".to_string()),
+            ssid: Some("Office".to_string()),
+            bssid: Some("AA:BB:CC:00:00:01".to_string()),
+            ip_address: Some("192.168.1.20".to_string()),
+            subnet_mask: Some("255.255.255.0".to_string()),
+            gateway: Some("192.168.1.1".to_string()),
+            dns_servers: Some(vec!["192.168.1.1".to_string(), "8.8.8.8".to_string()]),
+            ..NetworkStatus::default()
+        }
+    }
+
+    fn find<'a>(changes: &'a [FieldChange], field: &str) -> &'a FieldChange {
+        changes.iter().find(|c| c.field == field).unwrap()
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        assert!(diff(&snapshot(office()), &snapshot(office())).is_empty());
+    }
+
+    #[test]
+    fn test_diff_same_network_drift() {
+        let mut drifted = office();
+        drifted.ip_address = Some("192.168.1.57".to_string());
+        drifted.bssid = Some("AA:BB:CC:00:00:02".to_string());
+        drifted.gateway = Some("192.168.1.254".to_string());
+        drifted.dns_servers = Some(vec!["8.8.8.8".to_string(), "192.168.1.1".to_string()]);
+
+        let changes = diff(&snapshot(office()), &snapshot(drifted));
+        assert_eq!(changes.len(), 4);
+        assert_eq!(
+            find(&changes, "status.ip_address").classification,
+            ChangeClass::Expected
+        );
+        assert_eq!(
+            find(&changes, "status.bssid").classification,
+            ChangeClass::Expected
+        );
+        assert_eq!(
+            find(&changes, "status.gateway").classification,
+            ChangeClass::Suspicious
+        );
+        let dns = find(&changes, "status.dns_servers");
+        assert_eq!(dns.classification, ChangeClass::Suspicious);
+        assert_eq!(dns.reason, "DNS server order changed");
+    }
+
+    #[test]
+    fn test_diff_different_network() {
+        let mut home = office();
+        home.ssid = Some("Home".to_string());
+        home.ip_address = Some("10.0.0.8".to_string());
+        home.gateway = Some("10.0.0.1".to_string());
+
+        let changes = diff(&snapshot(office()), &snapshot(home));
+        assert!(changes
+            .iter()
+            .all(|c| c.classification == ChangeClass::Expected));
+    }
+
+    #[test]
+    fn test_diff_apipa_and_proxy() {
+        let mut broken = snapshot(office());
+        broken.status.ip_address = Some("169.254.3.9".to_string());
+        broken.proxy = Some(ProxyConfig {
+            http_proxy: proxy::ProxyEndpoint {
+                enabled: true,
+                server: Some("10.9.9.9".to_string()),
+                port: Some(3128),
+            },
+            https_proxy: proxy::ProxyEndpoint {
+                enabled: false,
+                server: None,
+                port: None,
+            },
+            socks_proxy: proxy::ProxyEndpoint {
+                enabled: false,
+                server: None,
+                port: None,
+            },
+            auto_config_url: None,
+            bypass_list: None,
+        });
+
+        let changes = diff(&snapshot(office()), &broken);
+        assert_eq!(
+            find(&changes, "status.ip_address").reason,
+            "Self-assigned address (DHCP failed)"
+        );
+        assert_eq!(
+            find(&changes, "proxy.http_proxy.server").classification,
+            ChangeClass::Suspicious
+        );
+    }
+}
@@ -1,13 +1,16 @@
 //! Network status command implementation
 
+use crate::commands::connectivity::{self, Connectivity, ProbeConfig};
+use crate::commands::snapshot;
 use crate::errors::{ExitCodes, NactlError};
 use crate::utils::netsh;
 use crate::utils::output::{print_output, OutputFormat};
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::process::Command;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkStatus {
     pub connected: bool,
     #[serde(rename = "type")]
@@ -34,9 +37,13 @@ pub struct NetworkStatus {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gateway: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub ipv6_addresses: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dns_servers: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mac_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", skip_deserializing)]
+    pub connectivity: Option<Connectivity>,
 }
 
 impl Default for NetworkStatus {
@@ -55,8 +62,10 @@ impl Default for NetworkStatus {
             ip_address: None,
             subnet_mask: None,
             gateway: None,
+            ipv6_addresses: None,
             dns_servers: None,
             mac_address: None,
+            connectivity: None,
         }
     }
 }
@@ -64,10 +73,54 @@ impl Default for NetworkStatus {
 #[derive(Debug, Serialize)]
 struct StatusResponse {
     success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
     data: NetworkStatus,
 }
 
-pub fn execute(format: OutputFormat, interface: Option<&str>) -> Result<u8, NactlError> {
+pub fn execute(
+    format: OutputFormat,
+    interface: Option<&str>,
+    probe: Option<&ProbeConfig>,
+    save: Option<&str>,
+    compare: Option<&str>,
+) -> Result<u8, NactlError> {
+    let mut status = collect(interface);
+
+    if let Some(path) = compare {
+        return snapshot::compare(path, &status, format);
+    }
+
+    // `connected` only reflects the link; the assessment tells whether the
+    // internet is actually reachable through it
+    if let Some(config) = probe {
+        status.connectivity = Some(connectivity::assess(
+            status.ip_address.as_deref(),
+            status.gateway.as_deref(),
+            config,
+        ));
+    }
+
+    let message = match save {
+        Some(path) => {
+            snapshot::save(path, &status)?;
+            Some(format!("Snapshot saved to {}", path))
+        }
+        None => None,
+    };
+
+    let response = StatusResponse {
+        success: true,
+        message,
+        data: status,
+    };
+
+    print_output(&response, format)?;
+    Ok(ExitCodes::Success as u8)
+}
+
+/// Gather link and IP configuration for the selected (or default) interface
+pub fn collect(interface: Option<&str>) -> NetworkStatus {
     let mut status = NetworkStatus::default();
 
     // Get Wi-Fi interface info using netsh
@@ -93,6 +146,9 @@ pub fn execute(format: OutputFormat, interface: Option<&str>) -> Result<u8, Nact
         if status.mac_address.is_none() {
             status.mac_address = ip_info.mac_address;
         }
+        if status.ipv6_addresses.is_none() {
+            status.ipv6_addresses = ip_info.ipv6_addresses;
+        }
 
         // Update connection status based on IP
         if status.ip_address.is_some() {
@@ -100,13 +156,7 @@ pub fn execute(format: OutputFormat, interface: Option<&str>) -> Result<u8, Nact
         }
     }
 
-    let response = StatusResponse {
-        success: true,
-        data: status,
-    };
-
-    print_output(&response, format)?;
-    Ok(ExitCodes::Success as u8)
+    status
 }
 
 fn get_wifi_status(interface: Option<&str>) -> Result<NetworkStatus, NactlError> {
@@ -180,6 +230,25 @@ fn get_wifi_status(interface: Option<&str>) -> Result<NetworkStatus, NactlError>
     Ok(status)
 }
 
+/// Routable addresses of a named interface, IPv4 first
+pub fn interface_addresses(interface: &str) -> Result<Vec<IpAddr>, NactlError> {
+    let status = get_ip_config(Some(interface))?;
+
+    // Link-local IPv6 addresses need a zone and cannot be used as a source
+    let addresses: Vec<IpAddr> = status
+        .ip_address
+        .into_iter()
+        .chain(status.ipv6_addresses.unwrap_or_default())
+        .filter_map(|a| a.parse().ok())
+        .filter(|ip| !matches!(ip, IpAddr::V6(v6) if v6.segments()[0] & 0xffc0 == 0xfe80))
+        .collect();
+
+    if addresses.is_empty() {
+        return Err(NactlError::interface_not_found(interface));
+    }
+    Ok(addresses)
+}
+
 fn get_ip_config(interface: Option<&str>) -> Result<NetworkStatus, NactlError> {
     let output = Command::new("ipconfig")
         .arg("/all")
@@ -187,22 +256,30 @@ fn get_ip_config(interface: Option<&str>) -> Result<NetworkStatus, NactlError> {
         .map_err(|e| NactlError::command_failed(format!("Failed to run ipconfig: {}", e)))?;
 
     let output_str = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_ipconfig(&output_str, interface))
+}
 
+fn parse_ipconfig(output: &str, interface: Option<&str>) -> NetworkStatus {
     let mut status = NetworkStatus::default();
     let mut in_target_adapter = false;
     let mut dns_servers: Vec<String> = Vec::new();
+    let mut ipv6_addresses: Vec<String> = Vec::new();
     let mut collecting_dns = false;
+    let mut collecting_gateway = false;
 
     // Patterns for matching
     let adapter_pattern = Regex::new(r"(?i)(Wireless|Wi-Fi|Ethernet).*adapter.*:").unwrap();
     let ipv4_pattern = Regex::new(r"IPv4 Address.*:\s*(\d+\.\d+\.\d+\.\d+)").unwrap();
     let subnet_pattern = Regex::new(r"Subnet Mask.*:\s*(\d+\.\d+\.\d+\.\d+)").unwrap();
     let gateway_pattern = Regex::new(r"Default Gateway.*:\s*(\d+\.\d+\.\d+\.\d+)").unwrap();
+    let gateway_label = Regex::new(r"Default Gateway[ .]*:").unwrap();
+    // "IPv6 Address", "Temporary IPv6 Address", "Link-local IPv6 Address"
+    let ipv6_pattern = Regex::new(r"IPv6 Address[ .]*:\s*([0-9a-fA-F:]+)").unwrap();
     let dns_pattern = Regex::new(r"DNS Servers.*:\s*(\d+\.\d+\.\d+\.\d+)").unwrap();
     let mac_pattern = Regex::new(r"Physical Address.*:\s*([0-9A-Fa-f-]+)").unwrap();
     let ip_continuation = Regex::new(r"^\s+(\d+\.\d+\.\d+\.\d+)").unwrap();
 
-    for line in output_str.lines() {
+    for line in output.lines() {
         // Check for adapter header
         if adapter_pattern.is_match(line) {
             // If we already found our target, stop
@@ -219,6 +296,7 @@ fn get_ip_config(interface: Option<&str>) -> Result<NetworkStatus, NactlError> {
                     || line.to_lowercase().contains("wireless");
             }
             collecting_dns = false;
+            collecting_gateway = false;
             continue;
         }
 
@@ -233,9 +311,21 @@ fn get_ip_config(interface: Option<&str>) -> Result<NetworkStatus, NactlError> {
         } else if let Some(caps) = subnet_pattern.captures(line) {
             status.subnet_mask = Some(caps[1].to_string());
             collecting_dns = false;
+        } else if let Some(caps) = ipv6_pattern.captures(line) {
+            ipv6_addresses.push(caps[1].to_lowercase());
+            collecting_dns = false;
         } else if let Some(caps) = gateway_pattern.captures(line) {
             status.gateway = Some(caps[1].to_string());
             collecting_dns = false;
+        } else if gateway_label.is_match(line) {
+            // IPv6 gateway listed first; the IPv4 one follows on its own line
+            collecting_gateway = true;
+            collecting_dns = false;
+        } else if collecting_gateway {
+            if let Some(caps) = ip_continuation.captures(line) {
+                status.gateway = Some(caps[1].to_string());
+            }
+            collecting_gateway = false;
         } else if let Some(caps) = dns_pattern.captures(line) {
             dns_servers.push(caps[1].to_string());
             collecting_dns = true;
@@ -256,10 +346,67 @@ fn get_ip_config(interface: Option<&str>) -> Result<NetworkStatus, NactlError> {
     if !dns_servers.is_empty() {
         status.dns_servers = Some(dns_servers);
     }
+    if !ipv6_addresses.is_empty() {
+        status.ipv6_addresses = Some(ipv6_addresses);
+    }
 
-    Ok(status)
+    status
 }
 
 fn extract_value(line: &str) -> Option<&str> {
     line.split_once(':').map(|(_, v)| v.trim())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPCONFIG_SAMPLE: &str = "
+Windows IP Configuration
+
+   Host Name . . . . . . . . . . . . : DESKTOP-01
+
+Ethernet adapter Ethernet:
+
+   Media State . . . . . . . . . . . : Media disconnected
+   Physical Address. . . . . . . . . : 00-11-22-33-44-55
+
+Wireless LAN adapter Wi-Fi:
+
+   Connection-specific DNS Suffix  . : lan
+   Physical Address. . . . . . . . . : A4-B1-C2-D3-E4-F5
+   DHCP Enabled. . . . . . . . . . . : Yes
+   IPv6 Address. . . . . . . . . . . : 2001:db8:1::25(Preferred)
+   Temporary IPv6 Address. . . . . . : 2001:db8:1::a1b2(Preferred)
+   Link-local IPv6 Address . . . . . : fe80::1c2d:3e4f:5a6b:7c8d%12(Preferred)
+   IPv4 Address. . . . . . . . . . . : 192.168.1.20(Preferred)
+   Subnet Mask . . . . . . . . . . . : 255.255.255.0
+   Default Gateway . . . . . . . . . : fe80::1%12
+                                       192.168.1.1
+   DNS Servers . . . . . . . . . . . : 192.168.1.1
+                                       8.8.8.8
+   NetBIOS over Tcpip. . . . . . . . : Enabled
+";
+
+    #[test]
+    fn test_parse_ipconfig_wifi() {
+        let status = parse_ipconfig(IPCONFIG_SAMPLE, None);
+
+        assert_eq!(status.ip_address.as_deref(), Some("192.168.1.20"));
+        assert_eq!(status.subnet_mask.as_deref(), Some("255.255.255.0"));
+        assert_eq!(status.gateway.as_deref(), Some("192.168.1.1"));
+        assert_eq!(status.mac_address.as_deref(), Some("A4:B1:C2:D3:E4:F5"));
+        assert_eq!(
+            status.dns_servers,
+            Some(vec!["192.168.1.1".to_string(), "8.8.8.8".to_string()])
+        );
+        assert_eq!(
+            status.ipv6_addresses,
+            Some(vec![
+                "2001:db8:1::25".to_string(),
+                "2001:db8:1::a1b2".to_string(),
+                "fe80::1c2d:3e4f:5a6b:7c8d".to_string(),
+            ])
+        );
+    }
+}
@@ -1,44 +1,380 @@
 //! Traceroute command implementation
 
+use crate::commands::ping;
 use crate::errors::{ExitCodes, NactlError};
+use crate::utils::asn::{self, AddressType};
 use crate::utils::output::{print_output, OutputFormat};
-use crate::utils::validation;
+use crate::utils::traceroute::{self, ProbeOutcome, ProbeReply, TraceProtocol, TracerouteOptions};
+use crate::utils::{resolve, stats, validation};
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::net::IpAddr;
 use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Serialize)]
+/// Outcome of one hop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HopStatus {
+    /// Every probe was answered
+    Ok,
+    /// Some probes timed out
+    Partial,
+    /// No probe was answered
+    Timeout,
+    HostUnreachable,
+    NetUnreachable,
+    /// The request could not be sent (e.g. no route)
+    TransmitError,
+    GeneralFailure,
+}
+
+impl HopStatus {
+    /// Errors end the trace: no later hop can answer
+    fn is_error(self) -> bool {
+        !matches!(
+            self,
+            HopStatus::Ok | HopStatus::Partial | HopStatus::Timeout
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 struct HopResult {
     hop: u32,
     ip: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     hostname: Option<String>,
+    /// Time the reverse lookup took, kept apart from `time_ms`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resolve_ms: Option<f64>,
+    /// The reverse lookup outlasted `--resolve-timeout`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    resolve_timed_out: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     time_ms: Option<Vec<f64>>,
+    status: HopStatus,
+    /// Probes on this hop that timed out
+    #[serde(default)]
+    lost: u32,
+    /// Repeated-probe statistics with `--rounds`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    statistics: Option<HopStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address_type: Option<AddressType>,
+    /// Owner from the `--asn-db` database
+    #[serde(skip_serializing_if = "Option::is_none")]
+    asn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    as_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HopStats {
+    sent: u32,
+    received: u32,
+    loss_percent: f64,
+    best_ms: Option<f64>,
+    avg_ms: Option<f64>,
+    worst_ms: Option<f64>,
+    stdev_ms: Option<f64>,
+    /// Loss here does not continue to later hops: the router deprioritizes
+    /// replies to probes aimed at it, but forwards traffic fine
+    #[serde(default)]
+    rate_limited: bool,
+}
+
+/// A run of consecutive hops in one autonomous system
+#[derive(Debug, Serialize, Deserialize)]
+struct AsPathEntry {
+    asn: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    as_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country: Option<String>,
+    first_hop: u32,
+    last_hop: u32,
+}
+
+/// How the trace was run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TraceEngine {
+    /// Parsed output of the system tracert command
+    #[default]
+    Tracert,
+    /// In-process probes (raw socket)
+    Icmp,
+    Udp,
+    Tcp,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 struct TraceData {
     host: String,
+    /// Traces saved before engines existed were all tracert
+    #[serde(default)]
+    engine: TraceEngine,
+    /// Destination port (TCP) or first port (UDP)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
     hops: Vec<HopResult>,
     destination_reached: bool,
     total_hops: u32,
+    /// Probe rounds sent to every hop with `--rounds`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rounds: Option<u32>,
+    /// First hop whose loss persists all the way to the last hop
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loss_origin_hop: Option<u32>,
+    /// Autonomous systems crossed, in path order, with `--asn-db`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    as_path: Option<Vec<AsPathEntry>>,
+    /// AS of the loss origin hop, when the database knows it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loss_origin_asn: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
 struct TraceResponse {
     success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
     data: TraceData,
 }
 
-pub fn execute(
-    host: &str,
-    max_hops: u32,
-    timeout: u32,
-    format: OutputFormat,
-) -> Result<u8, NactlError> {
+/// How a hop differs from the baseline trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum HopChangeKind {
+    Unchanged,
+    /// A different router answered at this point in the path
+    Changed,
+    /// Only in the current trace
+    Added,
+    /// Only in the baseline
+    Removed,
+    /// One side timed out, so it cannot be told
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+struct HopChange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_hop: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hop: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ip: Option<String>,
+    change: HopChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_avg_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    avg_ms: Option<f64>,
+    /// Current minus baseline average
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_delta_ms: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct TraceCompareData {
+    baseline: String,
+    /// Absent when the baseline is plain `nactl trace` output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_captured_at_ms: Option<u64>,
+    baseline_host: String,
+    host: String,
+    route_changed: bool,
+    added_count: usize,
+    removed_count: usize,
+    changed_count: usize,
+    baseline_destination_reached: bool,
+    destination_reached: bool,
+    /// Null unless both traces carry AS data (`--asn-db`)
+    as_path_changed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baseline_as_path: Option<Vec<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    as_path: Option<Vec<u32>>,
+    hops: Vec<HopChange>,
+    /// The current trace
+    trace: TraceData,
+}
+
+#[derive(Debug, Serialize)]
+struct TraceCompareResponse {
+    success: bool,
+    data: TraceCompareData,
+}
+
+/// Trace file format version
+const TRACE_FILE_VERSION: u32 = 1;
+
+/// Milliseconds the in-process engine waits for each probe's answer
+const PROBE_TIMEOUT_MS: u64 = 3000;
+
+/// Default time allowed for reverse lookups of all hops
+pub const DEFAULT_RESOLVE_TIMEOUT_MS: u32 = 2000;
+
+/// Default TCP probe port
+pub const DEFAULT_TCP_PORT: u16 = 80;
+
+/// Options for `nactl trace`
+#[derive(Debug, Clone)]
+pub struct TraceOptions {
+    pub max_hops: u32,
+    /// Overall timeout in milliseconds (0 for none)
+    pub timeout: u32,
+    /// Then ping every hop this many times
+    pub rounds: Option<u32>,
+    /// icmp, udp or tcp
+    pub protocol: String,
+    /// TCP destination port, or first UDP port
+    pub port: Option<u16>,
+    /// Offline IP-to-ASN database file
+    pub asn_db: Option<String>,
+    /// Save the trace to this file
+    pub save: Option<String>,
+    /// Compare the trace against one saved in this file
+    pub compare: Option<String>,
+    /// Skip reverse lookups of hop addresses
+    pub no_resolve: bool,
+    /// Time allowed for the reverse lookups, in milliseconds
+    pub resolve_timeout: u32,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            max_hops: 30,
+            timeout: 60000,
+            rounds: None,
+            protocol: "icmp".to_string(),
+            port: None,
+            asn_db: None,
+            save: None,
+            compare: None,
+            no_resolve: false,
+            resolve_timeout: DEFAULT_RESOLVE_TIMEOUT_MS,
+        }
+    }
+}
+
+pub fn execute(host: &str, options: &TraceOptions, format: OutputFormat) -> Result<u8, NactlError> {
     // Validate input to prevent command injection
     validation::validate_hostname(host)?;
+    if options.rounds == Some(0) {
+        return Err(NactlError::invalid_arguments("Rounds must be at least 1"));
+    }
+    if !(1..=255).contains(&options.max_hops) {
+        return Err(NactlError::invalid_arguments("Max hops must be 1-255"));
+    }
+
+    let protocol = match options.protocol.to_lowercase().as_str() {
+        "icmp" => TraceProtocol::Icmp,
+        "udp" => TraceProtocol::Udp,
+        "tcp" => TraceProtocol::Tcp,
+        _ => {
+            return Err(NactlError::invalid_arguments(
+                "Protocol must be 'icmp', 'udp' or 'tcp'",
+            ))
+        }
+    };
+    let port = match protocol {
+        TraceProtocol::Icmp if options.port.is_some() => {
+            return Err(NactlError::invalid_arguments(
+                "--port applies to UDP and TCP traces only",
+            ))
+        }
+        TraceProtocol::Icmp => None,
+        TraceProtocol::Udp => Some(options.port.unwrap_or(traceroute::DEFAULT_UDP_PORT)),
+        TraceProtocol::Tcp => Some(options.port.unwrap_or(DEFAULT_TCP_PORT)),
+    };
+    // Fail before tracing rather than after
+    if let Some(path) = &options.asn_db {
+        fs::metadata(path).map_err(|e| {
+            NactlError::invalid_input(format!("Failed to read ASN database '{}': {}", path, e))
+        })?;
+    }
+    let baseline = options.compare.as_deref().map(load_trace).transpose()?;
+
+    let target = ping::resolve_target(host, None)
+        .ok_or_else(|| NactlError::network_error(format!("Could not resolve host: {}", host)))?;
+    let probe_options = TracerouteOptions {
+        protocol,
+        port: port.unwrap_or(0),
+        max_hops: options.max_hops as u8,
+        probes_per_hop: 3,
+        probe_timeout: Duration::from_millis(PROBE_TIMEOUT_MS),
+        deadline: (options.timeout > 0).then(|| Duration::from_millis(options.timeout as u64)),
+    };
+
+    let mut data = match traceroute::trace(target, &probe_options) {
+        Ok(replies) => trace_from_replies(host, protocol, port, &replies),
+        // No raw socket without elevation; tracert covers ICMP
+        Err(_) if protocol == TraceProtocol::Icmp => run_tracert(host, options)?,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            return Err(NactlError::permission_denied(
+                "UDP and TCP traces require administrator privileges",
+            ))
+        }
+        Err(e) => {
+            return Err(NactlError::network_error(format!("Trace failed: {}", e)));
+        }
+    };
+
+    if let Some(rounds) = options.rounds {
+        let probe_timeout = if options.timeout == 0 {
+            PROBE_TIMEOUT_MS as u32
+        } else {
+            options.timeout.min(PROBE_TIMEOUT_MS as u32)
+        };
+        probe_hops(&mut data, rounds, probe_timeout);
+    }
+    enrich_hops(&mut data, options.asn_db.as_deref())?;
+    if !options.no_resolve {
+        resolve_hops(&mut data, options.resolve_timeout);
+    }
+
+    if let (Some(path), Some((captured_at_ms, baseline))) = (&options.compare, baseline) {
+        let response = TraceCompareResponse {
+            success: true,
+            data: compare_traces(path, captured_at_ms, baseline, data),
+        };
+        print_output(&response, format)?;
+        return Ok(ExitCodes::Success as u8);
+    }
+
+    let message = match &options.save {
+        Some(path) => {
+            save_trace(path, &data)?;
+            Some(format!("Trace saved to {}", path))
+        }
+        None => None,
+    };
+
+    let response = TraceResponse {
+        success: data.destination_reached,
+        message,
+        data,
+    };
+
+    print_output(&response, format)?;
+    Ok(ExitCodes::Success as u8)
+}
+
+/// Trace with the system tracert command
+fn run_tracert(host: &str, options: &TraceOptions) -> Result<TraceData, NactlError> {
+    let max_hops = options.max_hops;
+    let timeout = options.timeout;
 
     // Calculate per-hop timeout from overall timeout
     // tracert -w is timeout per probe in milliseconds
@@ -50,9 +386,12 @@ pub fn execute(
     };
 
     // Run tracert command
-    // Windows tracert: -h max_hops, -w timeout_per_probe
+    // Windows tracert: -d no name lookups (done in parallel afterwards, as
+    // tracert's sequential ones dominate the run time), -h max_hops,
+    // -w timeout_per_probe
     let output = Command::new("tracert")
         .args([
+            "-d",
             "-h",
             &max_hops.to_string(),
             "-w",
@@ -63,111 +402,958 @@ pub fn execute(
         .map_err(|e| NactlError::command_failed(format!("Failed to run tracert: {}", e)))?;
 
     let output_str = String::from_utf8_lossy(&output.stdout);
-    let data = parse_tracert_output(&output_str, host)?;
+    parse_tracert_output(&output_str, host)
+}
 
-    let response = TraceResponse {
-        success: data.destination_reached,
-        data,
+/// Group per-probe replies from the in-process engine into hops
+fn trace_from_replies(
+    host: &str,
+    protocol: TraceProtocol,
+    port: Option<u16>,
+    replies: &[ProbeReply],
+) -> TraceData {
+    let mut data = TraceData {
+        host: host.to_string(),
+        engine: match protocol {
+            TraceProtocol::Icmp => TraceEngine::Icmp,
+            TraceProtocol::Udp => TraceEngine::Udp,
+            TraceProtocol::Tcp => TraceEngine::Tcp,
+        },
+        port,
+        hops: Vec::new(),
+        destination_reached: replies.iter().any(|r| r.outcome == ProbeOutcome::Reached),
+        total_hops: 0,
+        rounds: None,
+        loss_origin_hop: None,
+        as_path: None,
+        loss_origin_asn: None,
     };
 
-    print_output(&response, format)?;
-    Ok(ExitCodes::Success as u8)
+    for ttl in replies.iter().map(|r| r.ttl).collect::<BTreeSet<_>>() {
+        let probes: Vec<&ProbeReply> = replies.iter().filter(|r| r.ttl == ttl).collect();
+        let times: Vec<f64> = probes
+            .iter()
+            .filter(|r| {
+                matches!(
+                    r.outcome,
+                    ProbeOutcome::TimeExceeded | ProbeOutcome::Reached
+                )
+            })
+            .filter_map(|r| r.rtt_ms)
+            .map(|t| stats::round_to(t, 3))
+            .collect();
+        let lost = probes
+            .iter()
+            .filter(|r| r.outcome == ProbeOutcome::Timeout)
+            .count() as u32;
+
+        let status = if probes
+            .iter()
+            .any(|r| r.outcome == ProbeOutcome::NetUnreachable)
+        {
+            HopStatus::NetUnreachable
+        } else if probes
+            .iter()
+            .any(|r| r.outcome == ProbeOutcome::HostUnreachable)
+        {
+            HopStatus::HostUnreachable
+        } else if times.is_empty() {
+            HopStatus::Timeout
+        } else if lost > 0 {
+            HopStatus::Partial
+        } else {
+            HopStatus::Ok
+        };
+
+        data.hops.push(HopResult {
+            hop: ttl as u32,
+            ip: probes
+                .iter()
+                .find_map(|r| r.from)
+                .map_or_else(|| "*".to_string(), |ip| ip.to_string()),
+            hostname: None,
+            resolve_ms: None,
+            resolve_timed_out: false,
+            time_ms: (!times.is_empty()).then_some(times),
+            status,
+            lost,
+            statistics: None,
+            address_type: None,
+            asn: None,
+            as_name: None,
+            country: None,
+        });
+        data.total_hops = ttl as u32;
+    }
+
+    data
 }
 
 fn parse_tracert_output(output: &str, host: &str) -> Result<TraceData, NactlError> {
     let mut data = TraceData {
         host: host.to_string(),
+        engine: TraceEngine::Tracert,
+        port: None,
         hops: Vec::new(),
         destination_reached: false,
         total_hops: 0,
+        rounds: None,
+        loss_origin_hop: None,
+        as_path: None,
+        loss_origin_asn: None,
     };
 
-    // Pattern for hop lines:
+    // "Tracing route to dns.google [8.8.8.8]"
+    // "Tracing route to 2606:4700:4700::1111 over a maximum of 30 hops"
+    let header_pattern = Regex::new(r"Tracing route to (\S+)(?:\s+\[([^\]\s]+)\])?").unwrap();
+    let destination_ip: Option<IpAddr> = header_pattern.captures(output).and_then(|caps| {
+        caps.get(2)
+            .or_else(|| caps.get(1))
+            .and_then(|m| m.as_str().parse().ok())
+    });
+
+    // Hop lines are a hop number, up to three probe results, then the
+    // responder or an error:
     // "  1    <1 ms    <1 ms    <1 ms  192.168.1.1"
-    // "  2     8 ms     9 ms     8 ms  10.0.0.1"
+    // "  2    12 ms     *       12 ms  hostname.example.com [2001:db8::1]"
     // "  3     *        *        *     Request timed out."
-    // "  2    12 ms    11 ms    12 ms  hostname.example.com [192.168.1.1]"
-    let hop_pattern = Regex::new(
-        r"^\s*(\d+)\s+(?:(<?\d+)\s*ms|(\*))\s+(?:(<?\d+)\s*ms|(\*))\s+(?:(<?\d+)\s*ms|(\*))\s+(.+)$"
-    ).unwrap();
-
-    // Pattern to extract IP and optional hostname
-    let ip_pattern = Regex::new(r"(\d+\.\d+\.\d+\.\d+)").unwrap();
-    let hostname_ip_pattern = Regex::new(r"(\S+)\s+\[(\d+\.\d+\.\d+\.\d+)\]").unwrap();
-
-    // Get destination IP from header
-    let dest_ip_pattern = Regex::new(r"Tracing route to.*\[?(\d+\.\d+\.\d+\.\d+)\]?").unwrap();
-    let mut destination_ip: Option<String> = None;
-
-    if let Some(caps) = dest_ip_pattern.captures(output) {
-        destination_ip = Some(caps[1].to_string());
-    }
+    // "  4  192.168.1.1  reports: Destination host unreachable."
+    // "  1  Transmit error: code 1231."
+    let hop_pattern = Regex::new(r"^\s*(\d+)\s+(.+)$").unwrap();
+    let probe_pattern = Regex::new(r"^(?:(<?\d+)\s*ms|\*)\s*").unwrap();
+    let named_pattern = Regex::new(r"^(\S+)\s+\[([^\]\s]+)\]").unwrap();
 
     for line in output.lines() {
-        if let Some(caps) = hop_pattern.captures(line) {
-            let hop_num: u32 = caps[1].parse().unwrap_or(0);
-
-            // Extract timing values
-            let mut times: Vec<f64> = Vec::new();
+        let Some(caps) = hop_pattern.captures(line) else {
+            continue;
+        };
+        let hop_num: u32 = caps[1].parse().unwrap_or(0);
+        let mut rest = caps[2].trim();
 
-            // Time 1
-            if let Some(time_match) = caps.get(2) {
-                let time_str = time_match.as_str().trim_start_matches('<');
-                if let Ok(t) = time_str.parse::<f64>() {
-                    times.push(t);
+        let mut times: Vec<f64> = Vec::new();
+        let mut lost = 0u32;
+        while let Some(probe) = probe_pattern.captures(rest) {
+            match probe.get(1) {
+                Some(time) => {
+                    if let Ok(t) = time.as_str().trim_start_matches('<').parse::<f64>() {
+                        times.push(t);
+                    }
                 }
+                None => lost += 1,
             }
+            rest = &rest[probe[0].len()..];
+        }
+
+        // Numbered lines without probes or a known error are not hops
+        let error = classify_error(rest);
+        if times.is_empty() && lost == 0 && error.is_none() {
+            continue;
+        }
 
-            // Time 2
-            if let Some(time_match) = caps.get(4) {
-                let time_str = time_match.as_str().trim_start_matches('<');
-                if let Ok(t) = time_str.parse::<f64>() {
-                    times.push(t);
+        let (ip, hostname) = if let Some(named) = named_pattern.captures(rest) {
+            (named[2].to_string(), Some(named[1].to_string()))
+        } else {
+            match rest.split_whitespace().next() {
+                Some(token) if token.trim_end_matches(':').parse::<IpAddr>().is_ok() => {
+                    (token.trim_end_matches(':').to_string(), None)
                 }
+                _ => ("*".to_string(), None),
             }
+        };
 
-            // Time 3
-            if let Some(time_match) = caps.get(6) {
-                let time_str = time_match.as_str().trim_start_matches('<');
-                if let Ok(t) = time_str.parse::<f64>() {
-                    times.push(t);
-                }
+        let status = match error {
+            Some(status) => status,
+            None if times.is_empty() => HopStatus::Timeout,
+            None if lost > 0 => HopStatus::Partial,
+            None => HopStatus::Ok,
+        };
+
+        if destination_ip.is_some_and(|dest| ip.parse() == Ok(dest)) && !status.is_error() {
+            data.destination_reached = true;
+        }
+
+        data.hops.push(HopResult {
+            hop: hop_num,
+            ip,
+            hostname,
+            resolve_ms: None,
+            resolve_timed_out: false,
+            time_ms: if times.is_empty() { None } else { Some(times) },
+            status,
+            lost,
+            statistics: None,
+            address_type: None,
+            asn: None,
+            as_name: None,
+            country: None,
+        });
+
+        data.total_hops = hop_num;
+    }
+
+    // Without a destination address, "Trace complete" after an answered
+    // final hop is the only evidence; tracert prints it after errors too
+    if destination_ip.is_none() && output.contains("Trace complete") {
+        data.destination_reached = data
+            .hops
+            .last()
+            .is_some_and(|hop| matches!(hop.status, HopStatus::Ok | HopStatus::Partial));
+    }
+
+    Ok(data)
+}
+
+/// Ping every responding hop `rounds` times, like pathping/MTR, so loss at
+/// a hop can be told apart from loss that carries on to the destination
+fn probe_hops(data: &mut TraceData, rounds: u32, timeout: u32) {
+    let targets: Vec<(usize, String)> = data
+        .hops
+        .iter()
+        .enumerate()
+        .filter(|(_, hop)| hop.ip.parse::<IpAddr>().is_ok())
+        .map(|(index, hop)| (index, hop.ip.clone()))
+        .collect();
+    let mut samples: Vec<Vec<Option<f64>>> = vec![Vec::new(); targets.len()];
+
+    let interval = Duration::from_millis(ping::DEFAULT_INTERVAL_MS);
+    for round in 0..rounds {
+        let started = Instant::now();
+
+        // All hops in a round are probed at once so each round takes one
+        // timeout at most, however long the path
+        let results: Vec<Option<f64>> = thread::scope(|scope| {
+            let handles: Vec<_> = targets
+                .iter()
+                .map(|(_, ip)| scope.spawn(move || ping::probe(ip, timeout)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().ok().flatten())
+                .collect()
+        });
+        for (hop_samples, result) in samples.iter_mut().zip(results) {
+            hop_samples.push(result);
+        }
+
+        if round + 1 < rounds {
+            thread::sleep(interval.saturating_sub(started.elapsed()));
+        }
+    }
+
+    for ((index, _), hop_samples) in targets.iter().zip(&samples) {
+        data.hops[*index].statistics = Some(hop_stats(hop_samples));
+    }
+
+    let losses: Vec<(u32, f64)> = targets
+        .iter()
+        .map(|(index, _)| {
+            let hop = &data.hops[*index];
+            (
+                hop.hop,
+                hop.statistics.as_ref().map_or(0.0, |s| s.loss_percent),
+            )
+        })
+        .collect();
+    let origin = loss_origin(&losses);
+
+    for (index, _) in &targets {
+        let hop = &mut data.hops[*index];
+        if let Some(stats) = hop.statistics.as_mut() {
+            stats.rate_limited =
+                stats.loss_percent > 0.0 && origin.is_none_or(|origin| hop.hop < origin);
+        }
+    }
+
+    data.rounds = Some(rounds);
+    data.loss_origin_hop = origin;
+}
+
+/// Write `data` to `path` for a later `--compare`
+fn save_trace(path: &str, data: &TraceData) -> Result<(), NactlError> {
+    let file = serde_json::json!({
+        "version": TRACE_FILE_VERSION,
+        "captured_at_ms": now_ms(),
+        "trace": data,
+    });
+    let json = serde_json::to_string_pretty(&file)
+        .map_err(|e| NactlError::general_error(format!("JSON serialization failed: {}", e)))?;
+
+    fs::write(path, json)
+        .map_err(|e| NactlError::general_error(format!("Failed to write trace '{}': {}", path, e)))
+}
+
+/// Read a trace written by `--save`, or the saved JSON output of
+/// `nactl trace`, with its capture time when known
+fn load_trace(path: &str) -> Result<(Option<u64>, TraceData), NactlError> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        NactlError::invalid_input(format!("Failed to read trace '{}': {}", path, e))
+    })?;
+    let invalid = |e: serde_json::Error| {
+        NactlError::invalid_input(format!("Invalid trace file '{}': {}", path, e))
+    };
+
+    let mut value: Value = serde_json::from_str(&contents).map_err(invalid)?;
+    let captured_at_ms = value.get("captured_at_ms").and_then(Value::as_u64);
+    // `--save` files nest it under `trace`, command output under `data`
+    let trace = match ["trace", "data"]
+        .into_iter()
+        .find(|key| value.get(key).is_some())
+    {
+        Some(key) => value[key].take(),
+        None => value,
+    };
+    let data = serde_json::from_value(trace).map_err(invalid)?;
+    Ok((captured_at_ms, data))
+}
+
+/// Align the current trace with the baseline and report what moved
+fn compare_traces(
+    path: &str,
+    captured_at_ms: Option<u64>,
+    baseline: TraceData,
+    current: TraceData,
+) -> TraceCompareData {
+    let hops: Vec<HopChange> = align_hops(&baseline.hops, &current.hops)
+        .into_iter()
+        .map(|(before, after)| {
+            let before = before.map(|i| &baseline.hops[i]);
+            let after = after.map(|i| &current.hops[i]);
+            let change = match (before, after) {
+                (Some(_), None) => HopChangeKind::Removed,
+                (None, _) => HopChangeKind::Added,
+                (Some(b), Some(a)) if b.ip == a.ip => HopChangeKind::Unchanged,
+                (Some(b), Some(a)) if b.ip == "*" || a.ip == "*" => HopChangeKind::Unknown,
+                _ => HopChangeKind::Changed,
+            };
+            let baseline_avg_ms = before.and_then(hop_avg_ms);
+            let avg_ms = after.and_then(hop_avg_ms);
+
+            HopChange {
+                baseline_hop: before.map(|h| h.hop),
+                hop: after.map(|h| h.hop),
+                baseline_ip: before.map(|h| h.ip.clone()),
+                ip: after.map(|h| h.ip.clone()),
+                change,
+                baseline_avg_ms,
+                avg_ms,
+                latency_delta_ms: baseline_avg_ms
+                    .zip(avg_ms)
+                    .map(|(b, a)| stats::round_to(a - b, 3)),
             }
+        })
+        .collect();
+
+    let count = |kind| hops.iter().filter(|h| h.change == kind).count();
+    let (added_count, removed_count, changed_count) = (
+        count(HopChangeKind::Added),
+        count(HopChangeKind::Removed),
+        count(HopChangeKind::Changed),
+    );
+
+    let asns = |data: &TraceData| {
+        data.as_path
+            .as_ref()
+            .map(|path| path.iter().map(|entry| entry.asn).collect::<Vec<u32>>())
+    };
+    let baseline_as_path = asns(&baseline);
+    let as_path = asns(&current);
 
-            // Extract hostname and IP
-            let rest = caps[8].trim();
-            let (ip, hostname) = if rest.contains("Request timed out") || rest == "*" {
-                ("*".to_string(), None)
-            } else if let Some(host_caps) = hostname_ip_pattern.captures(rest) {
-                (host_caps[2].to_string(), Some(host_caps[1].to_string()))
-            } else if let Some(ip_caps) = ip_pattern.captures(rest) {
-                (ip_caps[1].to_string(), None)
+    TraceCompareData {
+        baseline: path.to_string(),
+        baseline_captured_at_ms: captured_at_ms,
+        baseline_host: baseline.host,
+        host: current.host.clone(),
+        route_changed: added_count + removed_count + changed_count > 0,
+        added_count,
+        removed_count,
+        changed_count,
+        baseline_destination_reached: baseline.destination_reached,
+        destination_reached: current.destination_reached,
+        as_path_changed: baseline_as_path
+            .as_ref()
+            .zip(as_path.as_ref())
+            .map(|(b, a)| b != a),
+        baseline_as_path,
+        as_path,
+        hops,
+        trace: current,
+    }
+}
+
+/// Pair up hops of two traces: routers seen in both are matched in order
+/// (longest common subsequence), so one extra hop early in the path does
+/// not make every later hop look changed. Hops between matches are paired
+/// by position and the leftovers are added or removed.
+fn align_hops(before: &[HopResult], after: &[HopResult]) -> Vec<(Option<usize>, Option<usize>)> {
+    let same = |i: usize, j: usize| before[i].ip != "*" && before[i].ip == after[j].ip;
+
+    // lcs[i][j]: common routers in before[i..] and after[j..]
+    let mut lcs = vec![vec![0u32; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            lcs[i][j] = if same(i, j) {
+                lcs[i + 1][j + 1] + 1
             } else {
-                (rest.to_string(), None)
+                lcs[i + 1][j].max(lcs[i][j + 1])
             };
+        }
+    }
 
-            // Check if this is the destination
-            if let Some(ref dest) = destination_ip {
-                if &ip == dest {
-                    data.destination_reached = true;
-                }
+    let mut anchors = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() && j < after.len() {
+        if same(i, j) {
+            anchors.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    anchors.push((before.len(), after.len()));
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (anchor_i, anchor_j) in anchors {
+        while i < anchor_i || j < anchor_j {
+            pairs.push(((i < anchor_i).then_some(i), (j < anchor_j).then_some(j)));
+            i = (i + 1).min(anchor_i);
+            j = (j + 1).min(anchor_j);
+        }
+        if anchor_i < before.len() {
+            pairs.push((Some(anchor_i), Some(anchor_j)));
+            i = anchor_i + 1;
+            j = anchor_j + 1;
+        }
+    }
+    pairs
+}
+
+/// Average latency of a hop, preferring `--rounds` statistics
+fn hop_avg_ms(hop: &HopResult) -> Option<f64> {
+    if let Some(avg) = hop.statistics.as_ref().and_then(|s| s.avg_ms) {
+        return Some(avg);
+    }
+    let times = hop.time_ms.as_ref().filter(|t| !t.is_empty())?;
+    Some(stats::round_to(
+        times.iter().sum::<f64>() / times.len() as f64,
+        3,
+    ))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Reverse-resolve every hop at once, within `timeout_ms` overall
+fn resolve_hops(data: &mut TraceData, timeout_ms: u32) {
+    let addresses: Vec<(usize, IpAddr)> = data
+        .hops
+        .iter()
+        .enumerate()
+        .filter(|(_, hop)| hop.hostname.is_none())
+        .filter_map(|(index, hop)| hop.ip.parse().ok().map(|ip| (index, ip)))
+        .collect();
+    let unique: Vec<IpAddr> = addresses
+        .iter()
+        .map(|(_, ip)| *ip)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let lookups = resolve::reverse_lookup_all(&unique, Duration::from_millis(timeout_ms as u64));
+    for (index, ip) in addresses {
+        let hop = &mut data.hops[index];
+        match lookups.get(&ip) {
+            Some(lookup) => {
+                hop.hostname = lookup.hostname.clone();
+                hop.resolve_ms = Some(stats::round_to(lookup.elapsed_ms, 3));
             }
+            None => hop.resolve_timed_out = true,
+        }
+    }
+}
 
-            data.hops.push(HopResult {
-                hop: hop_num,
-                ip,
-                hostname,
-                time_ms: if times.is_empty() { None } else { Some(times) },
-            });
+/// Classify every hop's address and, given a database, add its AS and the
+/// AS path
+fn enrich_hops(data: &mut TraceData, asn_db: Option<&str>) -> Result<(), NactlError> {
+    let addresses: Vec<(usize, IpAddr)> = data
+        .hops
+        .iter()
+        .enumerate()
+        .filter_map(|(index, hop)| hop.ip.parse().ok().map(|ip| (index, ip)))
+        .collect();
+    for (index, ip) in &addresses {
+        data.hops[*index].address_type = Some(asn::classify(*ip));
+    }
 
-            data.total_hops = hop_num;
+    let Some(path) = asn_db else {
+        return Ok(());
+    };
+    let ips: Vec<IpAddr> = addresses.iter().map(|(_, ip)| *ip).collect();
+    let owners = asn::lookup(path, &ips)?;
+    for (index, ip) in &addresses {
+        if let Some(owner) = owners.get(ip) {
+            let hop = &mut data.hops[*index];
+            hop.asn = Some(owner.asn);
+            hop.as_name = owner.as_name.clone();
+            hop.country = owner.country.clone();
         }
     }
 
-    // Check for "Trace complete" message
-    if output.contains("Trace complete") {
-        data.destination_reached = true;
+    data.as_path = Some(as_path(&data.hops));
+    data.loss_origin_asn = data
+        .loss_origin_hop
+        .and_then(|origin| data.hops.iter().find(|hop| hop.hop == origin))
+        .and_then(|hop| hop.asn);
+    Ok(())
+}
+
+/// Collapse consecutive hops in the same AS; hops without one (private
+/// addresses, timeouts) do not break a run
+fn as_path(hops: &[HopResult]) -> Vec<AsPathEntry> {
+    let mut path: Vec<AsPathEntry> = Vec::new();
+    for hop in hops {
+        let Some(asn) = hop.asn else {
+            continue;
+        };
+        match path.last_mut() {
+            Some(last) if last.asn == asn => last.last_hop = hop.hop,
+            _ => path.push(AsPathEntry {
+                asn,
+                as_name: hop.as_name.clone(),
+                country: hop.country.clone(),
+                first_hop: hop.hop,
+                last_hop: hop.hop,
+            }),
+        }
     }
+    path
+}
 
-    Ok(data)
+fn hop_stats(samples: &[Option<f64>]) -> HopStats {
+    let times: Vec<f64> = samples.iter().flatten().copied().collect();
+    let sent = samples.len() as u32;
+    let received = times.len() as u32;
+
+    HopStats {
+        sent,
+        received,
+        loss_percent: if sent == 0 {
+            0.0
+        } else {
+            stats::round_to((sent - received) as f64 / sent as f64 * 100.0, 1)
+        },
+        best_ms: times.iter().copied().reduce(f64::min),
+        avg_ms: (!times.is_empty())
+            .then(|| stats::round_to(times.iter().sum::<f64>() / times.len() as f64, 3)),
+        worst_ms: times.iter().copied().reduce(f64::max),
+        stdev_ms: stats::stddev(&times).map(|v| stats::round_to(v, 3)),
+        rate_limited: false,
+    }
+}
+
+/// First hop from which every later hop also loses probes, given
+/// `(hop, loss_percent)` in path order
+fn loss_origin(losses: &[(u32, f64)]) -> Option<u32> {
+    let persisting = losses
+        .iter()
+        .rev()
+        .take_while(|(_, loss)| *loss > 0.0)
+        .count();
+    (persisting > 0).then(|| losses[losses.len() - persisting].0)
+}
+
+/// Status for tracert's error text, if the line carries one
+fn classify_error(text: &str) -> Option<HopStatus> {
+    let lower = text.to_lowercase();
+    if lower.contains("request timed out") {
+        Some(HopStatus::Timeout)
+    } else if lower.contains("destination host unreachable") {
+        Some(HopStatus::HostUnreachable)
+    } else if lower.contains("destination net unreachable") {
+        Some(HopStatus::NetUnreachable)
+    } else if lower.contains("transmit error") {
+        Some(HopStatus::TransmitError)
+    } else if lower.contains("general failure") {
+        Some(HopStatus::GeneralFailure)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loss_origin() {
+        // Hop 2 rate-limits ICMP but forwards everything
+        assert_eq!(
+            loss_origin(&[(1, 0.0), (2, 40.0), (3, 0.0), (4, 0.0)]),
+            None
+        );
+        // Real loss starting at hop 4, with hop 2 rate-limiting as well
+        assert_eq!(
+            loss_origin(&[(1, 0.0), (2, 40.0), (3, 0.0), (4, 10.0), (5, 12.0)]),
+            Some(4)
+        );
+        assert_eq!(loss_origin(&[]), None);
+    }
+
+    #[test]
+    fn test_hop_stats() {
+        let stats = hop_stats(&[Some(10.0), None, Some(20.0), Some(30.0)]);
+        assert_eq!(stats.sent, 4);
+        assert_eq!(stats.received, 3);
+        assert_eq!(stats.loss_percent, 25.0);
+        assert_eq!(stats.best_ms, Some(10.0));
+        assert_eq!(stats.avg_ms, Some(20.0));
+        assert_eq!(stats.worst_ms, Some(30.0));
+        assert_eq!(stats.stdev_ms, Some(8.165));
+    }
+
+    #[test]
+    fn test_probe_hops_loopback() {
+        let mut data = parse_tracert_output(
+            "Tracing route to 127.0.0.1 over a maximum of 30 hops\n\
+             \x20 1    <1 ms    <1 ms    <1 ms  127.0.0.1\n\
+             \x20 2     *        *        *     Request timed out.\n",
+            "127.0.0.1",
+        )
+        .unwrap();
+        probe_hops(&mut data, 2, 500);
+
+        assert_eq!(data.rounds, Some(2));
+        assert_eq!(data.hops[0].statistics.as_ref().unwrap().sent, 2);
+        // Silent hops are not probed
+        assert!(data.hops[1].statistics.is_none());
+    }
+
+    #[test]
+    fn test_parse_ipv4_trace() {
+        let output = "
+Tracing route to one.one.one.one [1.1.1.1]
+over a maximum of 30 hops:
+
+  1    <1 ms    <1 ms    <1 ms  192.168.1.1
+  2     8 ms     *        9 ms  10.0.0.1
+  3     *        *        *     Request timed out.
+  4    12 ms    11 ms    12 ms  one.one.one.one [1.1.1.1]
+
+Trace complete.
+";
+        let data = parse_tracert_output(output, "1.1.1.1").unwrap();
+
+        assert_eq!(data.hops.len(), 4);
+        assert_eq!(data.hops[0].status, HopStatus::Ok);
+        assert_eq!(data.hops[0].time_ms, Some(vec![1.0, 1.0, 1.0]));
+        assert_eq!(data.hops[1].status, HopStatus::Partial);
+        assert_eq!(data.hops[1].lost, 1);
+        assert_eq!(data.hops[1].time_ms, Some(vec![8.0, 9.0]));
+        assert_eq!(data.hops[2].status, HopStatus::Timeout);
+        assert_eq!(data.hops[2].ip, "*");
+        assert_eq!(data.hops[2].lost, 3);
+        assert_eq!(data.hops[3].ip, "1.1.1.1");
+        assert_eq!(data.hops[3].hostname.as_deref(), Some("one.one.one.one"));
+        assert!(data.destination_reached);
+        assert_eq!(data.total_hops, 4);
+    }
+
+    #[test]
+    fn test_parse_ipv6_trace() {
+        let output = "
+Tracing route to google.com [2607:f8b0:4004:c07::66]
+over a maximum of 30 hops:
+
+  1     1 ms    <1 ms    <1 ms  2001:db8:1::1
+  2    10 ms     9 ms    11 ms  lga25s62-in-x0e.1e100.net [2607:f8b0:4004:c07::66]
+
+Trace complete.
+";
+        let data = parse_tracert_output(output, "google.com").unwrap();
+
+        assert_eq!(data.hops[0].ip, "2001:db8:1::1");
+        assert_eq!(data.hops[0].hostname, None);
+        assert_eq!(data.hops[1].ip, "2607:f8b0:4004:c07::66");
+        assert_eq!(
+            data.hops[1].hostname.as_deref(),
+            Some("lga25s62-in-x0e.1e100.net")
+        );
+        assert!(data.destination_reached);
+    }
+
+    #[test]
+    fn test_parse_error_lines() {
+        let output = "
+Tracing route to 10.9.9.9 over a maximum of 30 hops
+
+  1    <1 ms    <1 ms    <1 ms  192.168.1.1
+  2  192.168.1.1  reports: Destination net unreachable.
+
+Trace complete.
+";
+        let data = parse_tracert_output(output, "10.9.9.9").unwrap();
+
+        assert_eq!(data.hops[1].status, HopStatus::NetUnreachable);
+        assert_eq!(data.hops[1].ip, "192.168.1.1");
+        assert_eq!(data.hops[1].time_ms, None);
+        assert!(!data.destination_reached);
+
+        let host_unreachable = parse_tracert_output(
+            "  3  10.0.0.1  reports: Destination host unreachable.\n",
+            "example.com",
+        )
+        .unwrap();
+        assert_eq!(host_unreachable.hops[0].status, HopStatus::HostUnreachable);
+
+        let transmit =
+            parse_tracert_output("  1  Transmit error: code 1231.\n", "example.com").unwrap();
+        assert_eq!(transmit.hops[0].status, HopStatus::TransmitError);
+        assert_eq!(transmit.hops[0].ip, "*");
+
+        let failure = parse_tracert_output(
+            "  1     *        *        *     General failure.\n",
+            "example.com",
+        )
+        .unwrap();
+        assert_eq!(failure.hops[0].status, HopStatus::GeneralFailure);
+        assert_eq!(failure.hops[0].lost, 3);
+    }
+
+    #[test]
+    fn test_trace_from_replies() {
+        let reply = |ttl, outcome, from: Option<&str>, rtt_ms| ProbeReply {
+            ttl,
+            outcome,
+            from: from.map(|ip| ip.parse().unwrap()),
+            rtt_ms,
+        };
+        let replies = vec![
+            reply(
+                1,
+                ProbeOutcome::TimeExceeded,
+                Some("192.168.1.1"),
+                Some(1.2345),
+            ),
+            reply(
+                1,
+                ProbeOutcome::TimeExceeded,
+                Some("192.168.1.1"),
+                Some(1.5),
+            ),
+            reply(1, ProbeOutcome::Timeout, None, None),
+            reply(2, ProbeOutcome::Timeout, None, None),
+            reply(2, ProbeOutcome::Timeout, None, None),
+            reply(3, ProbeOutcome::Reached, Some("93.184.216.34"), Some(20.0)),
+        ];
+        let data = trace_from_replies("example.com", TraceProtocol::Tcp, Some(443), &replies);
+
+        assert_eq!(data.engine, TraceEngine::Tcp);
+        assert_eq!(data.port, Some(443));
+        assert!(data.destination_reached);
+        assert_eq!(data.total_hops, 3);
+
+        assert_eq!(data.hops[0].status, HopStatus::Partial);
+        assert_eq!(data.hops[0].ip, "192.168.1.1");
+        assert_eq!(data.hops[0].time_ms, Some(vec![1.235, 1.5]));
+        assert_eq!(data.hops[0].lost, 1);
+
+        assert_eq!(data.hops[1].status, HopStatus::Timeout);
+        assert_eq!(data.hops[1].ip, "*");
+        assert_eq!(data.hops[1].time_ms, None);
+
+        assert_eq!(data.hops[2].status, HopStatus::Ok);
+
+        let unreachable = trace_from_replies(
+            "10.9.9.9",
+            TraceProtocol::Udp,
+            Some(33434),
+            &[reply(
+                2,
+                ProbeOutcome::NetUnreachable,
+                Some("10.0.0.1"),
+                Some(3.0),
+            )],
+        );
+        assert_eq!(unreachable.hops[0].status, HopStatus::NetUnreachable);
+        assert_eq!(unreachable.hops[0].time_ms, None);
+        assert!(!unreachable.destination_reached);
+    }
+
+    #[test]
+    fn test_enrich_hops() {
+        let output = "\
+  1     1 ms     1 ms     1 ms  192.168.1.1
+  2     8 ms     9 ms     8 ms  100.64.0.1
+  3    10 ms    10 ms    11 ms  24.1.0.1
+  4     *        *        *     Request timed out.
+  5    12 ms    12 ms    12 ms  24.2.0.1
+  6    20 ms    21 ms    20 ms  8.8.8.8
+";
+        let mut data = parse_tracert_output(output, "8.8.8.8").unwrap();
+        let path = std::env::temp_dir().join(format!("nactl-trace-asn-{}.tsv", std::process::id()));
+        std::fs::write(
+            &path,
+            "24.0.0.0/8\t7922\tUS\tCOMCAST-7922\n8.8.8.0/24\t15169\tUS\tGOOGLE\n",
+        )
+        .unwrap();
+        data.loss_origin_hop = Some(3);
+        let result = enrich_hops(&mut data, path.to_str());
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        assert_eq!(data.hops[0].address_type, Some(AddressType::Private));
+        assert_eq!(data.hops[1].address_type, Some(AddressType::Cgnat));
+        assert_eq!(data.hops[2].address_type, Some(AddressType::Public));
+        assert_eq!(data.hops[3].address_type, None);
+        assert_eq!(data.hops[2].asn, Some(7922));
+        assert_eq!(data.hops[2].as_name.as_deref(), Some("COMCAST-7922"));
+        assert_eq!(data.hops[0].asn, None);
+
+        let as_path = data.as_path.unwrap();
+        assert_eq!(as_path.len(), 2);
+        assert_eq!(
+            (as_path[0].asn, as_path[0].first_hop, as_path[0].last_hop),
+            (7922, 3, 5)
+        );
+        assert_eq!(as_path[1].asn, 15169);
+        assert_eq!(data.loss_origin_asn, Some(7922));
+    }
+
+    #[test]
+    fn test_align_hops() {
+        let yesterday = parse_tracert_output(
+            "\
+  1     1 ms     1 ms     1 ms  192.168.1.1
+  2     8 ms     8 ms     8 ms  10.0.0.1
+  3    10 ms    10 ms    10 ms  24.1.0.1
+  4    20 ms    20 ms    20 ms  8.8.8.8
+",
+            "8.8.8.8",
+        )
+        .unwrap();
+        let today = parse_tracert_output(
+            "\
+  1     1 ms     1 ms     1 ms  192.168.1.1
+  2     9 ms     9 ms     9 ms  10.0.0.1
+  3    11 ms    11 ms    11 ms  10.0.0.9
+  4     *        *        *     Request timed out.
+  5    40 ms    40 ms    40 ms  8.8.8.8
+",
+            "8.8.8.8",
+        )
+        .unwrap();
+
+        assert_eq!(
+            align_hops(&yesterday.hops, &today.hops),
+            vec![
+                (Some(0), Some(0)),
+                (Some(1), Some(1)),
+                (Some(2), Some(2)),
+                (None, Some(3)),
+                (Some(3), Some(4)),
+            ]
+        );
+
+        let data = compare_traces("yesterday.json", Some(1), yesterday, today);
+        let kinds: Vec<HopChangeKind> = data.hops.iter().map(|h| h.change).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HopChangeKind::Unchanged,
+                HopChangeKind::Unchanged,
+                HopChangeKind::Changed,
+                HopChangeKind::Added,
+                HopChangeKind::Unchanged,
+            ]
+        );
+        assert!(data.route_changed);
+        assert_eq!((data.added_count, data.changed_count), (1, 1));
+        assert_eq!(data.hops[1].latency_delta_ms, Some(1.0));
+        assert_eq!(data.hops[4].latency_delta_ms, Some(20.0));
+        assert_eq!(data.as_path_changed, None);
+    }
+
+    #[test]
+    fn test_align_removed_and_timeouts() {
+        let before = parse_tracert_output(
+            "  1  1 ms  1 ms  1 ms  192.168.1.1\n  2  *  *  *  Request timed out.\n  3  5 ms  5 ms  5 ms  10.0.0.3\n",
+            "10.0.0.3",
+        )
+        .unwrap();
+        let after = parse_tracert_output(
+            "  1  1 ms  1 ms  1 ms  192.168.1.1\n  2  4 ms  4 ms  4 ms  10.0.0.3\n",
+            "10.0.0.3",
+        )
+        .unwrap();
+
+        let data = compare_traces("before.json", None, before, after);
+        let kinds: Vec<HopChangeKind> = data.hops.iter().map(|h| h.change).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HopChangeKind::Unchanged,
+                HopChangeKind::Removed,
+                HopChangeKind::Unchanged,
+            ]
+        );
+        assert_eq!(data.hops[2].baseline_hop, Some(3));
+        assert_eq!(data.hops[2].hop, Some(2));
+    }
+
+    #[test]
+    fn test_save_and_load_trace() {
+        let data =
+            parse_tracert_output("  1  1 ms  1 ms  1 ms  192.168.1.1\n", "192.168.1.1").unwrap();
+        let dir = std::env::temp_dir();
+        let saved = dir.join(format!("nactl-trace-save-{}.json", std::process::id()));
+        let output = dir.join(format!("nactl-trace-output-{}.json", std::process::id()));
+
+        save_trace(saved.to_str().unwrap(), &data).unwrap();
+        let response = TraceResponse {
+            success: true,
+            message: None,
+            data,
+        };
+        std::fs::write(&output, serde_json::to_string(&response).unwrap()).unwrap();
+
+        let from_save = load_trace(saved.to_str().unwrap());
+        let from_output = load_trace(output.to_str().unwrap());
+        std::fs::remove_file(&saved).unwrap();
+        std::fs::remove_file(&output).unwrap();
+
+        let (captured_at_ms, loaded) = from_save.unwrap();
+        assert!(captured_at_ms.is_some());
+        assert_eq!(loaded.hops[0].ip, "192.168.1.1");
+        assert_eq!(loaded.engine, TraceEngine::Tracert);
+
+        let (captured_at_ms, loaded) = from_output.unwrap();
+        assert_eq!(captured_at_ms, None);
+        assert_eq!(loaded.hops[0].time_ms, Some(vec![1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn test_resolve_hops() {
+        let output = "\
+  1    <1 ms    <1 ms    <1 ms  127.0.0.1
+  2     *        *        *     Request timed out.
+  3     1 ms     1 ms     1 ms  one.one.one.one [1.1.1.1]
+";
+        let mut data = parse_tracert_output(output, "1.1.1.1").unwrap();
+        resolve_hops(&mut data, 5000);
+
+        // Looked up, whether or not the sandbox resolver knows a name
+        assert!(data.hops[0].resolve_ms.is_some() || data.hops[0].resolve_timed_out);
+        assert_eq!(data.hops[1].resolve_ms, None);
+        assert!(!data.hops[1].resolve_timed_out);
+        // Already named by tracert
+        assert_eq!(data.hops[2].resolve_ms, None);
+        assert_eq!(data.hops[2].hostname.as_deref(), Some("one.one.one.one"));
+    }
 }
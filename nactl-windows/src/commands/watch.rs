@@ -0,0 +1,386 @@
+//! Network change watch command implementation
+//!
+//! Polls status data at an interval and writes one JSON object per line
+//! (NDJSON) for every meaningful change, so callers can react to network
+//! changes without diffing `nactl status` output themselves.
+
+use crate::commands::status::{self, NetworkStatus};
+use crate::errors::{ExitCodes, NactlError};
+use crate::utils::registry;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Default signal thresholds (percent) that emit an event when crossed
+pub const DEFAULT_SIGNAL_THRESHOLDS: &str = "25,50,75";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Initial state when watching starts
+    Snapshot,
+    Connected,
+    Disconnected,
+    SsidChanged,
+    /// BSSID changed while the SSID stayed the same
+    Roamed,
+    BssidChanged,
+    InterfaceChanged,
+    IpChanged,
+    GatewayChanged,
+    DnsChanged,
+    SignalThreshold,
+    ProxyChanged,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WatchEvent {
+    pub event: EventKind,
+    pub timestamp_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<String>,
+    pub before: Value,
+    pub after: Value,
+}
+
+impl WatchEvent {
+    fn change(event: EventKind, field: &str, before: Value, after: Value) -> Self {
+        Self {
+            event,
+            timestamp_ms: now_ms(),
+            field: Some(field.to_string()),
+            threshold: None,
+            direction: None,
+            before,
+            after,
+        }
+    }
+}
+
+/// Proxy values as stored in the registry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProxyValues {
+    pub proxy_enable: bool,
+    pub proxy_server: Option<String>,
+    pub proxy_override: Option<String>,
+    pub auto_config_url: Option<String>,
+}
+
+/// Everything compared between polls
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub status: NetworkStatus,
+    pub proxy: ProxyValues,
+}
+
+/// Poll status and emit change events until the duration elapses
+pub fn execute(
+    interval: u64,
+    duration: Option<Duration>,
+    thresholds: &str,
+    interface: Option<&str>,
+) -> Result<u8, NactlError> {
+    let thresholds = parse_thresholds(thresholds)?;
+    let interval = Duration::from_millis(interval.max(100));
+    let deadline = duration.map(|duration| Instant::now() + duration);
+
+    let mut previous = take_snapshot(interface);
+    emit(&WatchEvent {
+        event: EventKind::Snapshot,
+        timestamp_ms: now_ms(),
+        field: None,
+        threshold: None,
+        direction: None,
+        before: Value::Null,
+        after: json!({ "status": previous.status, "proxy": previous.proxy }),
+    })?;
+
+    loop {
+        if let Some(deadline) = deadline {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep(interval.min(deadline - now));
+        } else {
+            thread::sleep(interval);
+        }
+
+        let current = take_snapshot(interface);
+        for event in diff(&previous, &current, &thresholds) {
+            emit(&event)?;
+        }
+        previous = current;
+    }
+
+    Ok(ExitCodes::Success as u8)
+}
+
+/// Events describing how `after` differs from `before`
+pub fn diff(before: &Snapshot, after: &Snapshot, thresholds: &[i32]) -> Vec<WatchEvent> {
+    let mut events = Vec::new();
+    let (b, a) = (&before.status, &after.status);
+
+    if b.connected != a.connected {
+        let kind = if a.connected {
+            EventKind::Connected
+        } else {
+            EventKind::Disconnected
+        };
+        events.push(WatchEvent::change(
+            kind,
+            "connected",
+            json!(b.connected),
+            json!(a.connected),
+        ));
+    }
+
+    if b.interface != a.interface {
+        events.push(WatchEvent::change(
+            EventKind::InterfaceChanged,
+            "interface",
+            json!(b.interface),
+            json!(a.interface),
+        ));
+    }
+
+    if b.ssid != a.ssid {
+        events.push(WatchEvent::change(
+            EventKind::SsidChanged,
+            "ssid",
+            json!(b.ssid),
+            json!(a.ssid),
+        ));
+    } else if b.bssid != a.bssid {
+        // Same network, different access point
+        let kind = if b.bssid.is_some() && a.bssid.is_some() {
+            EventKind::Roamed
+        } else {
+            EventKind::BssidChanged
+        };
+        events.push(WatchEvent::change(
+            kind,
+            "bssid",
+            json!(b.bssid),
+            json!(a.bssid),
+        ));
+    }
+
+    if b.ip_address != a.ip_address || b.subnet_mask != a.subnet_mask {
+        events.push(WatchEvent::change(
+            EventKind::IpChanged,
+            "ip_address",
+            json!({ "ip_address": b.ip_address, "subnet_mask": b.subnet_mask }),
+            json!({ "ip_address": a.ip_address, "subnet_mask": a.subnet_mask }),
+        ));
+    }
+
+    if b.gateway != a.gateway {
+        events.push(WatchEvent::change(
+            EventKind::GatewayChanged,
+            "gateway",
+            json!(b.gateway),
+            json!(a.gateway),
+        ));
+    }
+
+    if b.dns_servers != a.dns_servers {
+        events.push(WatchEvent::change(
+            EventKind::DnsChanged,
+            "dns_servers",
+            json!(b.dns_servers),
+            json!(a.dns_servers),
+        ));
+    }
+
+    if let (Some(old), Some(new)) = (b.signal_strength, a.signal_strength) {
+        for &threshold in thresholds {
+            let direction = if old < threshold && new >= threshold {
+                "up"
+            } else if old >= threshold && new < threshold {
+                "down"
+            } else {
+                continue;
+            };
+
+            events.push(WatchEvent {
+                event: EventKind::SignalThreshold,
+                timestamp_ms: now_ms(),
+                field: Some("signal_strength".to_string()),
+                threshold: Some(threshold),
+                direction: Some(direction.to_string()),
+                before: json!(old),
+                after: json!(new),
+            });
+        }
+    }
+
+    let (bp, ap) = (&before.proxy, &after.proxy);
+    let proxy_fields = [
+        (
+            "ProxyEnable",
+            json!(bp.proxy_enable),
+            json!(ap.proxy_enable),
+        ),
+        (
+            "ProxyServer",
+            json!(bp.proxy_server),
+            json!(ap.proxy_server),
+        ),
+        (
+            "ProxyOverride",
+            json!(bp.proxy_override),
+            json!(ap.proxy_override),
+        ),
+        (
+            "AutoConfigURL",
+            json!(bp.auto_config_url),
+            json!(ap.auto_config_url),
+        ),
+    ];
+    for (name, old, new) in proxy_fields {
+        if old != new {
+            events.push(WatchEvent::change(EventKind::ProxyChanged, name, old, new));
+        }
+    }
+
+    events
+}
+
+/// Parse a comma-separated list of signal percentages
+pub fn parse_thresholds(list: &str) -> Result<Vec<i32>, NactlError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.parse::<i32>() {
+            Ok(v) if (0..=100).contains(&v) => Ok(v),
+            _ => Err(NactlError::invalid_arguments(format!(
+                "Invalid signal threshold '{}' (expected 0-100)",
+                s
+            ))),
+        })
+        .collect()
+}
+
+fn take_snapshot(interface: Option<&str>) -> Snapshot {
+    Snapshot {
+        status: status::collect(interface),
+        proxy: ProxyValues {
+            proxy_enable: registry::get_proxy_enabled().unwrap_or(false),
+            proxy_server: registry::get_proxy_server().unwrap_or(None),
+            proxy_override: registry::get_proxy_override().unwrap_or(None),
+            auto_config_url: registry::get_auto_config_url().unwrap_or(None),
+        },
+    }
+}
+
+/// Write one event as a single line, regardless of --pretty
+fn emit(event: &WatchEvent) -> Result<(), NactlError> {
+    let line = serde_json::to_string(event)
+        .map_err(|e| NactlError::general_error(format!("JSON serialization failed: {}", e)))?;
+
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", line)
+        .and_then(|_| stdout.flush())
+        .map_err(|e| NactlError::general_error(format!("Failed to write event: {}", e)))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wifi(ssid: &str, bssid: &str, signal: i32) -> Snapshot {
+        Snapshot {
+            status: NetworkStatus {
+                connected: true,
+                interface: Some("Wi-Fi".to_string()),
+                ssid: Some(ssid.to_string()),
+                bssid: Some(bssid.to_string()),
+                signal_strength: Some(signal),
+                ip_address: Some("192.168.1.20".to_string()),
+                gateway: Some("192.168.1.1".to_string()),
+                dns_servers: Some(vec!["192.168.1.1".to_string()]),
+                ..NetworkStatus::default()
+            },
+            proxy: ProxyValues::default(),
+        }
+    }
+
+    fn kinds(events: &[WatchEvent]) -> Vec<EventKind> {
+        events.iter().map(|e| e.event).collect()
+    }
+
+    #[test]
+    fn test_diff_no_change() {
+        let a = wifi("Office", "aa:bb:cc:00:00:01", 80);
+        assert!(diff(&a, &a.clone(), &[25, 50, 75]).is_empty());
+    }
+
+    #[test]
+    fn test_diff_roaming_and_signal() {
+        let before = wifi("Office", "aa:bb:cc:00:00:01", 80);
+        let after = wifi("Office", "aa:bb:cc:00:00:02", 40);
+        let events = diff(&before, &after, &[25, 50, 75]);
+
+        assert_eq!(
+            kinds(&events),
+            vec![
+                EventKind::Roamed,
+                EventKind::SignalThreshold,
+                EventKind::SignalThreshold
+            ]
+        );
+        assert_eq!(events[1].threshold, Some(50));
+        assert_eq!(events[1].direction.as_deref(), Some("down"));
+        assert_eq!(events[2].threshold, Some(75));
+    }
+
+    #[test]
+    fn test_diff_disconnect() {
+        let before = wifi("Office", "aa:bb:cc:00:00:01", 80);
+        let after = Snapshot::default();
+        let events = diff(&before, &after, &[]);
+
+        assert_eq!(events[0].event, EventKind::Disconnected);
+        assert!(kinds(&events).contains(&EventKind::IpChanged));
+        assert!(kinds(&events).contains(&EventKind::DnsChanged));
+        assert_eq!(events[0].before, json!(true));
+        assert_eq!(events[0].after, json!(false));
+    }
+
+    #[test]
+    fn test_diff_proxy_registry() {
+        let before = wifi("Office", "aa:bb:cc:00:00:01", 80);
+        let mut after = before.clone();
+        after.proxy.proxy_enable = true;
+        after.proxy.proxy_server = Some("10.0.0.9:3128".to_string());
+
+        let events = diff(&before, &after, &[]);
+        assert_eq!(
+            kinds(&events),
+            vec![EventKind::ProxyChanged, EventKind::ProxyChanged]
+        );
+        assert_eq!(events[1].field.as_deref(), Some("ProxyServer"));
+        assert_eq!(events[1].after, json!("10.0.0.9:3128"));
+    }
+
+    #[test]
+    fn test_parse_thresholds() {
+        assert_eq!(parse_thresholds("25, 50,75").unwrap(), vec![25, 50, 75]);
+        assert!(parse_thresholds("150").is_err());
+        assert!(parse_thresholds("abc").is_err());
+    }
+}
@@ -142,6 +142,10 @@ impl NactlError {
         Self::new(ExitCodes::GeneralError, ErrorCode::ParseError, message)
     }
 
+    pub fn network_error(message: impl Into<String>) -> Self {
+        Self::new(ExitCodes::GeneralError, ErrorCode::NetworkError, message)
+    }
+
     pub fn invalid_input(message: impl Into<String>) -> Self {
         Self::new(
             ExitCodes::InvalidArguments,
@@ -12,7 +12,9 @@ mod commands;
 mod errors;
 mod utils;
 
-use commands::{dns, ping, proxy, stack, status, trace, wifi};
+use commands::{
+    connectivity, diagnose, dns, mtu, ping, portal, proxy, scan, stack, status, trace, watch, wifi,
+};
 use errors::ExitCodes;
 use utils::output::OutputFormat;
 
@@ -73,12 +75,41 @@ enum Commands {
     Version,
 
     /// Get comprehensive network connection status
-    Status,
+    Status {
+        /// URL fetched to verify internet access
+        #[arg(long = "probe-url", default_value = connectivity::DEFAULT_PROBE_URL)]
+        probe_url: String,
+
+        /// Body the probe URL must return
+        #[arg(long = "probe-body", default_value = connectivity::DEFAULT_PROBE_BODY)]
+        probe_body: String,
+
+        /// Timeout for each connectivity stage in milliseconds
+        #[arg(long = "probe-timeout", default_value = "3000")]
+        probe_timeout: u32,
+
+        /// Skip the internet connectivity assessment
+        #[arg(long = "no-probe")]
+        no_probe: bool,
+
+        /// Save the current state as a baseline snapshot file
+        #[arg(long, value_name = "FILE", conflicts_with = "compare")]
+        save: Option<String>,
+
+        /// Compare the current state against a saved snapshot file
+        #[arg(long, value_name = "FILE")]
+        compare: Option<String>,
+    },
 
     /// Test connectivity to a host
     Ping {
-        /// Target host to ping
-        host: String,
+        /// Target hosts to ping (several are pinged concurrently)
+        #[arg(required_unless_present = "targets")]
+        hosts: Vec<String>,
+
+        /// Read additional targets from a file, one per line
+        #[arg(long, value_name = "FILE")]
+        targets: Option<String>,
 
         /// Number of packets to send
         #[arg(short = 'c', long, default_value = "4")]
@@ -87,6 +118,54 @@ enum Commands {
         /// Timeout in milliseconds
         #[arg(short = 't', long, default_value = "1000")]
         timeout: u32,
+
+        /// Force IPv4
+        #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6")]
+        ipv4: bool,
+
+        /// Force IPv6
+        #[arg(short = '6', long = "ipv6")]
+        ipv6: bool,
+
+        /// Time TCP handshakes to this port instead of sending ICMP
+        #[arg(long, value_name = "PORT")]
+        tcp: Option<u16>,
+
+        /// Send from this local address or interface (defaults to --interface)
+        #[arg(short = 'S', long, value_name = "ADDR|INTERFACE")]
+        source: Option<String>,
+
+        /// ICMP payload size in bytes
+        #[arg(short = 'l', long, default_value_t = ping::DEFAULT_PING_SIZE, conflicts_with = "tcp")]
+        size: u32,
+
+        /// Time to live (hop limit for IPv6)
+        #[arg(long)]
+        ttl: Option<u32>,
+
+        /// Set the Don't Fragment bit
+        #[arg(short = 'f', long = "dont-fragment", conflicts_with = "tcp")]
+        dont_fragment: bool,
+
+        /// Milliseconds between requests
+        #[arg(long, default_value_t = ping::DEFAULT_INTERVAL_MS)]
+        interval: u64,
+
+        /// Ping until interrupted with Ctrl+C
+        #[arg(long, conflicts_with_all = ["count", "duration"])]
+        continuous: bool,
+
+        /// Ping for a fixed time (e.g. 90, 30s, 5m, 2h)
+        #[arg(long, conflicts_with = "count")]
+        duration: Option<String>,
+
+        /// Seconds between rolling summaries in continuous or timed runs
+        #[arg(long = "summary-interval", default_value_t = ping::DEFAULT_SUMMARY_INTERVAL_SECS)]
+        summary_interval: u64,
+
+        /// Write every sample (timestamp, seq, rtt, status) to a CSV file
+        #[arg(long, value_name = "FILE")]
+        csv: Option<String>,
     },
 
     /// Trace route to destination
@@ -101,6 +180,56 @@ enum Commands {
         /// Timeout in milliseconds (0 for no timeout)
         #[arg(short = 't', long, default_value = "60000")]
         timeout: u32,
+
+        /// Then ping every hop this many times for per-hop loss and latency
+        #[arg(short = 'r', long)]
+        rounds: Option<u32>,
+
+        /// Probe protocol: icmp, udp or tcp (udp and tcp require elevation)
+        #[arg(short = 'P', long, default_value = "icmp")]
+        protocol: String,
+
+        /// TCP destination port (default 80) or first UDP port (default 33434)
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Offline IP-to-ASN database (iptoasn.com TSV or prefix lines)
+        #[arg(long, value_name = "FILE")]
+        asn_db: Option<String>,
+
+        /// Save the trace to a file for a later --compare
+        #[arg(long, value_name = "FILE", conflicts_with = "compare")]
+        save: Option<String>,
+
+        /// Compare the trace against one saved earlier
+        #[arg(long, value_name = "FILE")]
+        compare: Option<String>,
+
+        /// Do not resolve hop addresses to names
+        #[arg(short = 'd', long = "no-resolve")]
+        no_resolve: bool,
+
+        /// Time allowed for resolving hop names, in milliseconds
+        #[arg(long = "resolve-timeout", default_value = "2000")]
+        resolve_timeout: u32,
+    },
+
+    /// Discover the path MTU to a host
+    Mtu {
+        /// Target host (hostname or IP address)
+        host: String,
+
+        /// Force IPv4
+        #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6")]
+        ipv4: bool,
+
+        /// Force IPv6
+        #[arg(short = '6', long = "ipv6")]
+        ipv6: bool,
+
+        /// Timeout per probe in milliseconds
+        #[arg(short = 't', long, default_value = "1000")]
+        timeout: u32,
     },
 
     /// DNS management commands
@@ -126,6 +255,48 @@ enum Commands {
         #[command(subcommand)]
         action: ProxyCommands,
     },
+
+    /// Run a layered diagnosis and recommend a fix
+    Diagnose {
+        /// URL fetched to verify internet access
+        #[arg(long = "probe-url", default_value = connectivity::DEFAULT_PROBE_URL)]
+        probe_url: String,
+
+        /// Body the probe URL must return
+        #[arg(long = "probe-body", default_value = connectivity::DEFAULT_PROBE_BODY)]
+        probe_body: String,
+
+        /// Timeout for each check in milliseconds
+        #[arg(short = 't', long, default_value = "3000")]
+        timeout: u32,
+    },
+
+    /// Watch for network changes and emit NDJSON events
+    Watch {
+        /// Polling interval in milliseconds
+        #[arg(long, default_value = "2000")]
+        interval: u64,
+
+        /// Stop after this long, e.g. 90, 30s, 5m, 2h (runs until interrupted if omitted)
+        #[arg(short = 'd', long)]
+        duration: Option<String>,
+
+        /// Signal strength percentages that emit an event when crossed
+        #[arg(long = "signal-thresholds", default_value = watch::DEFAULT_SIGNAL_THRESHOLDS)]
+        signal_thresholds: String,
+    },
+
+    /// Captive portal detection commands
+    Portal {
+        #[command(subcommand)]
+        action: PortalCommands,
+    },
+
+    /// Network discovery commands
+    Scan {
+        #[command(subcommand)]
+        action: ScanCommands,
+    },
 }
 
 #[derive(Subcommand)]
@@ -133,17 +304,59 @@ enum DnsCommands {
     /// Flush DNS resolver cache
     Flush,
 
-    /// Set custom DNS servers
+    /// Set custom DNS servers (IPv4 and/or IPv6), verified and rolled back on failure
     Set {
-        /// Primary DNS server
-        primary: String,
-
-        /// Secondary DNS server (optional)
-        secondary: Option<String>,
+        /// DNS servers in order of preference
+        #[arg(required = true)]
+        servers: Vec<String>,
     },
 
     /// Reset DNS to automatic (DHCP)
     Reset,
+
+    /// Show DNS client configuration per interface
+    Get {
+        /// Include interfaces without DNS servers
+        #[arg(short = 'a', long)]
+        all: bool,
+    },
+
+    /// Query a DNS server directly
+    Lookup {
+        /// Name to look up (an IP address for PTR)
+        name: String,
+
+        /// Record type: A, AAAA, CNAME, MX, TXT, NS, SOA, SRV or PTR
+        #[arg(short = 'T', long = "type", default_value = "A")]
+        record_type: String,
+
+        /// Server to ask, as IP or IP:port (default: the first configured server)
+        #[arg(short = 's', long)]
+        server: Option<String>,
+
+        /// Query over TCP instead of UDP
+        #[arg(long)]
+        tcp: bool,
+
+        /// Timeout in milliseconds
+        #[arg(short = 't', long, default_value_t = dns::DEFAULT_LOOKUP_TIMEOUT_MS)]
+        timeout: u32,
+    },
+
+    /// Benchmark the configured and candidate DNS servers
+    Benchmark {
+        /// Comma-separated candidate servers tried alongside the configured ones
+        #[arg(short = 's', long, default_value = dns::DEFAULT_BENCHMARK_SERVERS)]
+        servers: String,
+
+        /// Times each name is queried per server
+        #[arg(short = 'r', long, default_value = "5")]
+        rounds: u32,
+
+        /// Timeout per query in milliseconds
+        #[arg(short = 't', long, default_value = "2000")]
+        timeout: u32,
+    },
 }
 
 #[derive(Subcommand)]
@@ -177,6 +390,53 @@ enum ProxyCommands {
     Clear,
 }
 
+#[derive(Subcommand)]
+enum PortalCommands {
+    /// Probe well-known endpoints for a captive portal
+    Check {
+        /// Additional probe URL (http:// only)
+        #[arg(short = 'u', long)]
+        url: Option<String>,
+
+        /// HTTP status the custom URL must return
+        #[arg(long = "expect-status", default_value = "200")]
+        expect_status: u16,
+
+        /// Body the custom URL must return
+        #[arg(long = "expect-body")]
+        expect_body: Option<String>,
+
+        /// Timeout per probe in milliseconds
+        #[arg(short = 't', long, default_value = "5000")]
+        timeout: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScanCommands {
+    /// Find live hosts in an IPv4 subnet
+    Hosts {
+        /// Subnet in CIDR notation (e.g. 192.168.1.0/24, at most /20)
+        cidr: String,
+
+        /// Probe method: icmp, tcp or both
+        #[arg(short = 'm', long, default_value = "both")]
+        method: String,
+
+        /// Comma-separated TCP ports tried on each address
+        #[arg(long, default_value = scan::DEFAULT_SCAN_PORTS)]
+        ports: String,
+
+        /// Timeout per probe in milliseconds
+        #[arg(short = 't', long, default_value = "500")]
+        timeout: u32,
+
+        /// Number of addresses probed at once
+        #[arg(long, default_value = "128")]
+        concurrency: usize,
+    },
+}
+
 fn main() -> ExitCode {
     // Set up panic handler first thing
     setup_panic_handler();
@@ -240,26 +500,151 @@ fn main() -> ExitCode {
             Ok(ExitCodes::Success as u8)
         }
 
-        Some(Commands::Status) => status::execute(format, interface),
+        Some(Commands::Status {
+            probe_url,
+            probe_body,
+            probe_timeout,
+            no_probe,
+            save,
+            compare,
+        }) => {
+            // Connectivity is a live measurement, not part of a snapshot diff
+            if no_probe || compare.is_some() {
+                status::execute(format, interface, None, save.as_deref(), compare.as_deref())
+            } else {
+                connectivity::ProbeConfig::new(&probe_url, &probe_body, probe_timeout).and_then(
+                    |probe| {
+                        status::execute(
+                            format,
+                            interface,
+                            Some(&probe),
+                            save.as_deref(),
+                            compare.as_deref(),
+                        )
+                    },
+                )
+            }
+        }
 
         Some(Commands::Ping {
-            host,
+            mut hosts,
+            targets,
             count,
             timeout,
-        }) => ping::execute(&host, count, timeout, format),
+            ipv4,
+            ipv6,
+            tcp,
+            source,
+            size,
+            ttl,
+            dont_fragment,
+            interval,
+            continuous,
+            duration,
+            summary_interval,
+            csv,
+        }) => {
+            let family = ping::IpFamily::from_flags(ipv4, ipv6);
+            let extra = targets.as_deref().map(ping::read_targets).transpose();
+            extra
+                .and_then(|extra| {
+                    hosts.extend(extra.unwrap_or_default());
+                    duration
+                        .as_deref()
+                        .map(utils::validation::parse_duration)
+                        .transpose()
+                })
+                .and_then(|duration| {
+                    let source = source
+                        .as_deref()
+                        .or(interface)
+                        .map(|s| ping::resolve_source(s, family))
+                        .transpose()?;
+                    let options = ping::PingOptions {
+                        count,
+                        timeout,
+                        family,
+                        tcp_port: tcp,
+                        interval,
+                        continuous,
+                        duration,
+                        summary_interval,
+                        csv,
+                        source,
+                        size,
+                        ttl,
+                        dont_fragment,
+                    };
+                    ping::execute(&hosts, &options, format)
+                })
+        }
 
         Some(Commands::Trace {
             host,
             max_hops,
             timeout,
-        }) => trace::execute(&host, max_hops, timeout, format),
+            rounds,
+            protocol,
+            port,
+            asn_db,
+            save,
+            compare,
+            no_resolve,
+            resolve_timeout,
+        }) => {
+            let options = trace::TraceOptions {
+                max_hops,
+                timeout,
+                rounds,
+                protocol,
+                port,
+                asn_db,
+                save,
+                compare,
+                no_resolve,
+                resolve_timeout,
+            };
+            trace::execute(&host, &options, format)
+        }
+
+        Some(Commands::Mtu {
+            host,
+            ipv4,
+            ipv6,
+            timeout,
+        }) => mtu::execute(
+            &host,
+            ping::IpFamily::from_flags(ipv4, ipv6),
+            timeout,
+            format,
+            interface,
+        ),
 
         Some(Commands::Dns { action }) => match action {
             DnsCommands::Flush => dns::flush(format),
-            DnsCommands::Set { primary, secondary } => {
-                dns::set(&primary, secondary.as_deref(), format, interface)
-            }
+            DnsCommands::Set { servers } => dns::set(&servers, format, interface),
             DnsCommands::Reset => dns::reset(format, interface),
+            DnsCommands::Get { all } => dns::get(all, format, interface),
+            DnsCommands::Lookup {
+                name,
+                record_type,
+                server,
+                tcp,
+                timeout,
+            } => dns::lookup(
+                &name,
+                &record_type,
+                server.as_deref(),
+                tcp,
+                timeout,
+                format,
+                interface,
+            ),
+            DnsCommands::Benchmark {
+                servers,
+                rounds,
+                timeout,
+            } => dns::benchmark(&servers, rounds, timeout, format, interface),
         },
 
         Some(Commands::Stack { action }) => match action {
@@ -275,6 +660,48 @@ fn main() -> ExitCode {
             ProxyCommands::Get => proxy::get(format),
             ProxyCommands::Clear => proxy::clear(format),
         },
+
+        Some(Commands::Diagnose {
+            probe_url,
+            probe_body,
+            timeout,
+        }) => connectivity::ProbeConfig::new(&probe_url, &probe_body, timeout)
+            .and_then(|probe| diagnose::execute(&probe, format, interface)),
+
+        Some(Commands::Watch {
+            interval,
+            duration,
+            signal_thresholds,
+        }) => duration
+            .as_deref()
+            .map(utils::validation::parse_duration)
+            .transpose()
+            .and_then(|duration| watch::execute(interval, duration, &signal_thresholds, interface)),
+
+        Some(Commands::Portal { action }) => match action {
+            PortalCommands::Check {
+                url,
+                expect_status,
+                expect_body,
+                timeout,
+            } => portal::check(
+                url.as_deref(),
+                expect_status,
+                expect_body.as_deref(),
+                timeout,
+                format,
+            ),
+        },
+
+        Some(Commands::Scan { action }) => match action {
+            ScanCommands::Hosts {
+                cidr,
+                method,
+                ports,
+                timeout,
+                concurrency,
+            } => scan::hosts(&cidr, &method, &ports, timeout, concurrency, format),
+        },
     };
 
     match result {
@@ -0,0 +1,290 @@
+//! Address classification and offline IP-to-ASN lookup
+//!
+//! The database is a tab-separated file the user supplies, one range per
+//! line, in either form:
+//!
+//! ```text
+//! 1.0.0.0    1.0.0.255    13335    US    CLOUDFLARENET
+//! 1.0.0.0/24    13335    US    CLOUDFLARENET
+//! ```
+//!
+//! The first is the iptoasn.com `ip2asn-v4.tsv` / `ip2asn-v6.tsv` layout.
+//! Lines starting with `#` and ranges with AS 0 (not routed) are ignored.
+
+use crate::errors::NactlError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+
+/// Where an address sits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressType {
+    /// RFC 1918, or IPv6 unique local (fc00::/7)
+    Private,
+    /// Carrier-grade NAT shared space (100.64.0.0/10)
+    Cgnat,
+    Loopback,
+    /// 169.254.0.0/16 or fe80::/10
+    LinkLocal,
+    /// Unspecified, multicast, broadcast or reserved ranges
+    Reserved,
+    Public,
+}
+
+/// Classify an address without any lookup
+pub fn classify(ip: IpAddr) -> AddressType {
+    match ip {
+        IpAddr::V4(v4) => {
+            let octets = v4.octets();
+            if v4.is_loopback() {
+                AddressType::Loopback
+            } else if v4.is_private() {
+                AddressType::Private
+            } else if octets[0] == 100 && octets[1] & 0xc0 == 64 {
+                AddressType::Cgnat
+            } else if v4.is_link_local() {
+                AddressType::LinkLocal
+            } else if v4.is_unspecified()
+                || v4.is_multicast()
+                || v4.is_broadcast()
+                || octets[0] == 0
+                || octets[0] >= 240
+            {
+                AddressType::Reserved
+            } else {
+                AddressType::Public
+            }
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                classify(IpAddr::V4(v4))
+            } else if v6.is_loopback() {
+                AddressType::Loopback
+            } else if first & 0xfe00 == 0xfc00 {
+                AddressType::Private
+            } else if first & 0xffc0 == 0xfe80 {
+                AddressType::LinkLocal
+            } else if v6.is_unspecified() || v6.is_multicast() {
+                AddressType::Reserved
+            } else {
+                AddressType::Public
+            }
+        }
+    }
+}
+
+/// Autonomous system that announces an address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsnInfo {
+    pub asn: u32,
+    pub as_name: Option<String>,
+    pub country: Option<String>,
+}
+
+/// One database line: an inclusive address range and its owner
+#[derive(Debug, PartialEq, Eq)]
+struct AsnRange {
+    start: IpAddr,
+    end: IpAddr,
+    info: AsnInfo,
+}
+
+impl AsnRange {
+    fn contains(&self, ip: IpAddr) -> bool {
+        ip.is_ipv4() == self.start.is_ipv4() && self.start <= ip && ip <= self.end
+    }
+
+    /// Number of addresses covered, for picking the most specific range
+    fn size(&self) -> u128 {
+        to_u128(self.end) - to_u128(self.start)
+    }
+}
+
+/// Look `ips` up in the database at `path`. The file is streamed rather
+/// than loaded, since full tables run to hundreds of thousands of lines.
+pub fn lookup(path: &str, ips: &[IpAddr]) -> Result<HashMap<IpAddr, AsnInfo>, NactlError> {
+    let file = File::open(path).map_err(|e| {
+        NactlError::invalid_input(format!("Failed to read ASN database '{}': {}", path, e))
+    })?;
+
+    // Most specific range seen so far for each address, with its size
+    let mut best: HashMap<IpAddr, (u128, AsnInfo)> = HashMap::new();
+    let mut ranges = 0;
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| {
+            NactlError::invalid_input(format!("Failed to read ASN database '{}': {}", path, e))
+        })?;
+        let Some(range) = parse_line(&line) else {
+            continue;
+        };
+        ranges += 1;
+
+        for &ip in ips.iter().filter(|&&ip| range.contains(ip)) {
+            if best.get(&ip).is_none_or(|(size, _)| range.size() < *size) {
+                best.insert(ip, (range.size(), range.info.clone()));
+            }
+        }
+    }
+
+    if ranges == 0 {
+        return Err(NactlError::invalid_input(format!(
+            "No address ranges found in ASN database '{}'",
+            path
+        )));
+    }
+
+    Ok(best.into_iter().map(|(ip, (_, info))| (ip, info)).collect())
+}
+
+/// Parse a range line (`start end asn [country] [name]`) or a prefix line
+/// (`prefix asn [country] [name]`)
+fn parse_line(line: &str) -> Option<AsnRange> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.trim().is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+    let (start, end, rest) = if let Some((addr, len)) = fields[0].split_once('/') {
+        let (start, end) = prefix_range(addr.parse().ok()?, len.parse().ok()?)?;
+        (start, end, &fields[1..])
+    } else {
+        let start: IpAddr = fields[0].parse().ok()?;
+        let end: IpAddr = fields.get(1)?.parse().ok()?;
+        if start.is_ipv4() != end.is_ipv4() || start > end {
+            return None;
+        }
+        (start, end, &fields[2..])
+    };
+
+    let asn_field = rest.first()?;
+    let asn: u32 = asn_field
+        .strip_prefix("AS")
+        .unwrap_or(asn_field)
+        .parse()
+        .ok()?;
+    if asn == 0 {
+        return None;
+    }
+
+    let text = |i: usize| {
+        rest.get(i)
+            .filter(|s| !s.is_empty() && **s != "None" && **s != "Not routed")
+            .map(|s| s.to_string())
+    };
+
+    Some(AsnRange {
+        start,
+        end,
+        info: AsnInfo {
+            asn,
+            country: text(1),
+            as_name: text(2),
+        },
+    })
+}
+
+/// First and last address of `addr/len`
+fn prefix_range(addr: IpAddr, len: u32) -> Option<(IpAddr, IpAddr)> {
+    let bits = if addr.is_ipv4() { 32 } else { 128 };
+    if len > bits {
+        return None;
+    }
+    let host_bits = bits - len;
+    let mask = if host_bits == 128 {
+        u128::MAX
+    } else {
+        (1u128 << host_bits) - 1
+    };
+    let start = to_u128(addr) & !mask;
+    Some((from_u128(start, addr), from_u128(start | mask, addr)))
+}
+
+fn to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u32::from(v4) as u128,
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+/// Address of the same family as `like`
+fn from_u128(value: u128, like: IpAddr) -> IpAddr {
+    match like {
+        IpAddr::V4(_) => IpAddr::V4((value as u32).into()),
+        IpAddr::V6(_) => IpAddr::V6(value.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(ip("192.168.1.1")), AddressType::Private);
+        assert_eq!(classify(ip("172.20.0.1")), AddressType::Private);
+        assert_eq!(classify(ip("100.64.0.1")), AddressType::Cgnat);
+        assert_eq!(classify(ip("100.127.255.254")), AddressType::Cgnat);
+        assert_eq!(classify(ip("100.128.0.1")), AddressType::Public);
+        assert_eq!(classify(ip("127.0.0.1")), AddressType::Loopback);
+        assert_eq!(classify(ip("169.254.10.1")), AddressType::LinkLocal);
+        assert_eq!(classify(ip("224.0.0.1")), AddressType::Reserved);
+        assert_eq!(classify(ip("8.8.8.8")), AddressType::Public);
+        assert_eq!(classify(ip("fd00::1")), AddressType::Private);
+        assert_eq!(classify(ip("fe80::1")), AddressType::LinkLocal);
+        assert_eq!(classify(ip("::1")), AddressType::Loopback);
+        assert_eq!(classify(ip("::ffff:10.0.0.1")), AddressType::Private);
+        assert_eq!(classify(ip("2606:4700::1111")), AddressType::Public);
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let range = parse_line("1.0.0.0\t1.0.0.255\t13335\tUS\tCLOUDFLARENET").unwrap();
+        assert_eq!(range.start, ip("1.0.0.0"));
+        assert_eq!(range.end, ip("1.0.0.255"));
+        assert_eq!(range.info.asn, 13335);
+        assert_eq!(range.info.country.as_deref(), Some("US"));
+        assert_eq!(range.info.as_name.as_deref(), Some("CLOUDFLARENET"));
+
+        let prefix = parse_line("2001:db8::/32\tAS64500\t\tExample Net").unwrap();
+        assert_eq!(prefix.end, ip("2001:db8:ffff:ffff:ffff:ffff:ffff:ffff"));
+        assert_eq!(prefix.info.asn, 64500);
+        assert_eq!(prefix.info.country, None);
+
+        assert_eq!(parse_line("1.0.4.0\t1.0.7.255\t0\tNone\tNot routed"), None);
+        assert_eq!(parse_line("# comment"), None);
+        assert_eq!(parse_line("garbage"), None);
+    }
+
+    #[test]
+    fn test_lookup_most_specific() {
+        let path = std::env::temp_dir().join(format!("nactl-asn-{}.tsv", std::process::id()));
+        std::fs::write(
+            &path,
+            "# test table\n\
+             24.0.0.0/8\t7922\tUS\tCOMCAST-7922\n\
+             24.10.0.0\t24.10.255.255\t64501\tUS\tCUSTOMER\n",
+        )
+        .unwrap();
+
+        let result = lookup(
+            path.to_str().unwrap(),
+            &[ip("24.1.2.3"), ip("24.10.1.1"), ip("8.8.8.8")],
+        );
+        std::fs::remove_file(&path).unwrap();
+        let found = result.unwrap();
+
+        assert_eq!(found[&ip("24.1.2.3")].asn, 7922);
+        assert_eq!(found[&ip("24.10.1.1")].asn, 64501);
+        assert!(!found.contains_key(&ip("8.8.8.8")));
+    }
+}
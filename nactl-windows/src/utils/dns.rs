@@ -0,0 +1,795 @@
+//! Minimal DNS client (RFC 1035) for resolver checks
+//!
+//! Queries go straight to the chosen server over UDP or TCP, bypassing the
+//! system resolver and its cache, so results show what that server answers.
+
+use crate::errors::NactlError;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+pub const DNS_PORT: u16 = 53;
+
+/// UDP payload size advertised with EDNS(0), per DNS Flag Day 2020
+const EDNS_UDP_SIZE: u16 = 1232;
+
+const FLAG_QR: u16 = 0x8000;
+const FLAG_AA: u16 = 0x0400;
+const FLAG_TC: u16 = 0x0200;
+const FLAG_RD: u16 = 0x0100;
+const FLAG_RA: u16 = 0x0080;
+const FLAG_AD: u16 = 0x0020;
+
+const CLASS_IN: u16 = 1;
+const TYPE_OPT: u16 = 41;
+
+/// Query types `dns lookup` supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Txt,
+    Ns,
+    Soa,
+    Srv,
+    Ptr,
+}
+
+impl RecordType {
+    /// Parse a type name such as `AAAA` (case-insensitive)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_uppercase().as_str() {
+            "A" => Some(Self::A),
+            "AAAA" => Some(Self::Aaaa),
+            "CNAME" => Some(Self::Cname),
+            "MX" => Some(Self::Mx),
+            "TXT" => Some(Self::Txt),
+            "NS" => Some(Self::Ns),
+            "SOA" => Some(Self::Soa),
+            "SRV" => Some(Self::Srv),
+            "PTR" => Some(Self::Ptr),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> u16 {
+        match self {
+            Self::A => 1,
+            Self::Ns => 2,
+            Self::Cname => 5,
+            Self::Soa => 6,
+            Self::Ptr => 12,
+            Self::Mx => 15,
+            Self::Txt => 16,
+            Self::Aaaa => 28,
+            Self::Srv => 33,
+        }
+    }
+
+    pub fn name(self) -> String {
+        type_name(self.code())
+    }
+}
+
+/// Header flags of a response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Flags {
+    /// Authoritative answer
+    pub aa: bool,
+    /// Truncated
+    pub tc: bool,
+    /// Recursion desired
+    pub rd: bool,
+    /// Recursion available
+    pub ra: bool,
+    /// Authenticated data (DNSSEC validated by the resolver)
+    pub ad: bool,
+}
+
+/// A resource record in presentation form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Record {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// A decoded response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub id: u16,
+    pub rcode: u8,
+    pub flags: Flags,
+    pub answers: Vec<Record>,
+    pub authority: Vec<Record>,
+}
+
+impl Message {
+    /// Response code name, e.g. `NOERROR` or `NXDOMAIN`
+    pub fn response_code(&self) -> String {
+        match self.rcode {
+            0 => "NOERROR".to_string(),
+            1 => "FORMERR".to_string(),
+            2 => "SERVFAIL".to_string(),
+            3 => "NXDOMAIN".to_string(),
+            4 => "NOTIMP".to_string(),
+            5 => "REFUSED".to_string(),
+            other => format!("RCODE{}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// A completed query
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    pub message: Message,
+    /// From sending the query to decoding the answer
+    pub latency_ms: f64,
+    pub transport: Transport,
+}
+
+/// Ask `server` for `name`. UDP answers with the truncation flag set are
+/// retried over TCP, within the same `timeout`.
+pub fn query(
+    server: SocketAddr,
+    name: &str,
+    record_type: RecordType,
+    tcp: bool,
+    timeout: Duration,
+) -> Result<Exchange, NactlError> {
+    let id = random_id();
+    let request = encode_query(id, name, record_type)?;
+    let started = Instant::now();
+
+    if !tcp {
+        let message = query_udp(server, &request, id, timeout)?;
+        if !message.flags.tc {
+            return Ok(Exchange {
+                message,
+                latency_ms: elapsed_ms(started),
+                transport: Transport::Udp,
+            });
+        }
+    }
+
+    let remaining = timeout.saturating_sub(started.elapsed());
+    if remaining.is_zero() {
+        return Err(no_response(server, timeout));
+    }
+    let message = query_tcp(server, &request, id, remaining)?;
+    Ok(Exchange {
+        message,
+        latency_ms: elapsed_ms(started),
+        transport: Transport::Tcp,
+    })
+}
+
+/// `in-addr.arpa` / `ip6.arpa` name for a PTR query
+pub fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, c, d] = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+        }
+        IpAddr::V6(v6) => {
+            let mut labels: Vec<String> = Vec::with_capacity(34);
+            for byte in v6.octets().iter().rev() {
+                labels.push(format!("{:x}", byte & 0x0f));
+                labels.push(format!("{:x}", byte >> 4));
+            }
+            labels.push("ip6.arpa".to_string());
+            labels.join(".")
+        }
+    }
+}
+
+fn query_udp(
+    server: SocketAddr,
+    request: &[u8],
+    id: u16,
+    timeout: Duration,
+) -> Result<Message, NactlError> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).map_err(|e| io_error(e, server, timeout))?;
+    // Connecting filters out datagrams from anyone but the server
+    socket
+        .connect(server)
+        .map_err(|e| io_error(e, server, timeout))?;
+    socket
+        .send(request)
+        .map_err(|e| io_error(e, server, timeout))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(no_response(server, timeout));
+        }
+        socket
+            .set_read_timeout(Some(remaining))
+            .map_err(|e| io_error(e, server, timeout))?;
+        let len = socket
+            .recv(&mut buf)
+            .map_err(|e| io_error(e, server, timeout))?;
+
+        // Stray or spoofed answers to other queries are skipped
+        if len >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+            return decode(&buf[..len]);
+        }
+    }
+}
+
+fn query_tcp(
+    server: SocketAddr,
+    request: &[u8],
+    id: u16,
+    timeout: Duration,
+) -> Result<Message, NactlError> {
+    let deadline = Instant::now() + timeout;
+    let mut stream =
+        TcpStream::connect_timeout(&server, timeout).map_err(|e| io_error(e, server, timeout))?;
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(no_response(server, timeout));
+    }
+    stream
+        .set_read_timeout(Some(remaining))
+        .and_then(|_| stream.set_write_timeout(Some(remaining)))
+        .map_err(|e| io_error(e, server, timeout))?;
+
+    // Messages over TCP carry a two-byte length prefix
+    let mut framed = (request.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(request);
+    stream
+        .write_all(&framed)
+        .map_err(|e| io_error(e, server, timeout))?;
+
+    let mut len = [0u8; 2];
+    stream
+        .read_exact(&mut len)
+        .map_err(|e| io_error(e, server, timeout))?;
+    let len = u16::from_be_bytes(len) as usize;
+    let mut buf = vec![0u8; len];
+    stream
+        .read_exact(&mut buf)
+        .map_err(|e| io_error(e, server, timeout))?;
+
+    let message = decode(&buf)?;
+    if message.id != id {
+        return Err(NactlError::parse_error(format!(
+            "{} answered with a mismatched query ID",
+            server
+        )));
+    }
+    Ok(message)
+}
+
+/// Build a recursive query with an EDNS(0) record, asking for the AD flag
+pub fn encode_query(id: u16, name: &str, record_type: RecordType) -> Result<Vec<u8>, NactlError> {
+    let mut packet = Vec::with_capacity(64);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&(FLAG_RD | FLAG_AD).to_be_bytes());
+    // One question, one additional (OPT) record
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 1]);
+
+    encode_name(name, &mut packet)?;
+    packet.extend_from_slice(&record_type.code().to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    // OPT: root name, advertised UDP size in the class field, no options
+    packet.push(0);
+    packet.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    packet.extend_from_slice(&EDNS_UDP_SIZE.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    Ok(packet)
+}
+
+fn encode_name(name: &str, packet: &mut Vec<u8>) -> Result<(), NactlError> {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    let start = packet.len();
+
+    if !name.is_empty() {
+        for label in name.split('.') {
+            if label.is_empty() || label.len() > 63 {
+                return Err(NactlError::invalid_input(format!(
+                    "Invalid DNS name '{}': labels must be 1-63 characters",
+                    name
+                )));
+            }
+            if label.bytes().any(|b| !b.is_ascii_graphic()) {
+                return Err(NactlError::invalid_input(format!(
+                    "Invalid DNS name '{}'",
+                    name
+                )));
+            }
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+    }
+    packet.push(0);
+
+    if packet.len() - start > 255 {
+        return Err(NactlError::invalid_input(format!(
+            "Invalid DNS name '{}': longer than 255 bytes",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Decode a response
+pub fn decode(buf: &[u8]) -> Result<Message, NactlError> {
+    let id = read_u16(buf, 0)?;
+    let flags = read_u16(buf, 2)?;
+    let questions = read_u16(buf, 4)?;
+    let answers = read_u16(buf, 6)?;
+    let authority = read_u16(buf, 8)?;
+
+    if flags & FLAG_QR == 0 {
+        return Err(NactlError::parse_error("DNS message is not a response"));
+    }
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(buf, offset)?.1 + 4;
+    }
+
+    let mut records = |count: u16| -> Result<Vec<Record>, NactlError> {
+        let mut records = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (record, next) = read_record(buf, offset)?;
+            offset = next;
+            records.extend(record);
+        }
+        Ok(records)
+    };
+    let answers = records(answers)?;
+    let authority = records(authority)?;
+
+    Ok(Message {
+        id,
+        rcode: (flags & 0x000f) as u8,
+        flags: Flags {
+            aa: flags & FLAG_AA != 0,
+            tc: flags & FLAG_TC != 0,
+            rd: flags & FLAG_RD != 0,
+            ra: flags & FLAG_RA != 0,
+            ad: flags & FLAG_AD != 0,
+        },
+        answers,
+        authority,
+    })
+}
+
+/// One resource record at `offset` and the offset after it. OPT
+/// pseudo-records yield no record.
+fn read_record(buf: &[u8], offset: usize) -> Result<(Option<Record>, usize), NactlError> {
+    let (name, offset) = read_name(buf, offset)?;
+    let record_type = read_u16(buf, offset)?;
+    let ttl = read_u32(buf, offset + 4)?;
+    let len = read_u16(buf, offset + 8)? as usize;
+    let start = offset + 10;
+    let end = start + len;
+    if end > buf.len() {
+        return Err(truncated());
+    }
+    if record_type == TYPE_OPT {
+        return Ok((None, end));
+    }
+
+    let record = Record {
+        name,
+        record_type: type_name(record_type),
+        ttl,
+        data: format_rdata(buf, start, end, record_type)?,
+    };
+    Ok((Some(record), end))
+}
+
+/// Record data in presentation form
+fn format_rdata(
+    buf: &[u8],
+    start: usize,
+    end: usize,
+    record_type: u16,
+) -> Result<String, NactlError> {
+    let rdata = &buf[start..end];
+    let data = match record_type {
+        1 if rdata.len() == 4 => Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).to_string(),
+        28 if rdata.len() == 16 => {
+            let octets: [u8; 16] = rdata.try_into().map_err(|_| truncated())?;
+            Ipv6Addr::from(octets).to_string()
+        }
+        // NS, CNAME, PTR
+        2 | 5 | 12 => read_name(buf, start)?.0,
+        // MX: preference, exchange
+        15 => format!("{} {}", read_u16(buf, start)?, read_name(buf, start + 2)?.0),
+        // TXT: one or more length-prefixed strings
+        16 => {
+            let mut text = String::new();
+            let mut pos = 0;
+            while pos < rdata.len() {
+                let len = rdata[pos] as usize;
+                let chunk = rdata.get(pos + 1..pos + 1 + len).ok_or_else(truncated)?;
+                text.push_str(&String::from_utf8_lossy(chunk));
+                pos += 1 + len;
+            }
+            text
+        }
+        // SOA: mname rname serial refresh retry expire minimum
+        6 => {
+            let (mname, next) = read_name(buf, start)?;
+            let (rname, next) = read_name(buf, next)?;
+            let numbers: Vec<String> = (0..5)
+                .map(|i| read_u32(buf, next + i * 4).map(|n| n.to_string()))
+                .collect::<Result<_, _>>()?;
+            format!("{} {} {}", mname, rname, numbers.join(" "))
+        }
+        // SRV: priority weight port target
+        33 => format!(
+            "{} {} {} {}",
+            read_u16(buf, start)?,
+            read_u16(buf, start + 2)?,
+            read_u16(buf, start + 4)?,
+            read_name(buf, start + 6)?.0
+        ),
+        // Unknown types in RFC 3597 generic form
+        _ => {
+            let hex: String = rdata.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("\\# {} {}", rdata.len(), hex)
+                .trim_end()
+                .to_string()
+        }
+    };
+    Ok(data)
+}
+
+/// A possibly compressed name at `offset`, and the offset after it
+fn read_name(buf: &[u8], offset: usize) -> Result<(String, usize), NactlError> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = offset;
+    let mut next = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *buf.get(pos).ok_or_else(truncated)? as usize;
+        if len & 0xc0 == 0xc0 {
+            // Compression pointer; a bounded number of jumps stops loops
+            jumps += 1;
+            if jumps > 64 {
+                return Err(NactlError::parse_error("DNS name compression loop"));
+            }
+            next.get_or_insert(pos + 2);
+            pos = (read_u16(buf, pos)? & 0x3fff) as usize;
+        } else if len == 0 {
+            let name = if labels.is_empty() {
+                ".".to_string()
+            } else {
+                labels.join(".")
+            };
+            return Ok((name, next.unwrap_or(pos + 1)));
+        } else {
+            let label = buf.get(pos + 1..pos + 1 + len).ok_or_else(truncated)?;
+            labels.push(String::from_utf8_lossy(label).to_string());
+            pos += 1 + len;
+        }
+    }
+}
+
+fn type_name(code: u16) -> String {
+    match code {
+        1 => "A".to_string(),
+        2 => "NS".to_string(),
+        5 => "CNAME".to_string(),
+        6 => "SOA".to_string(),
+        12 => "PTR".to_string(),
+        15 => "MX".to_string(),
+        16 => "TXT".to_string(),
+        28 => "AAAA".to_string(),
+        33 => "SRV".to_string(),
+        46 => "RRSIG".to_string(),
+        other => format!("TYPE{}", other),
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize) -> Result<u16, NactlError> {
+    buf.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(truncated)
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Result<u32, NactlError> {
+    buf.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(truncated)
+}
+
+fn truncated() -> NactlError {
+    NactlError::parse_error("Truncated DNS message")
+}
+
+fn no_response(server: SocketAddr, timeout: Duration) -> NactlError {
+    NactlError::timeout(format!(
+        "No response from {} within {} ms",
+        server,
+        timeout.as_millis()
+    ))
+}
+
+fn io_error(e: io::Error, server: SocketAddr, timeout: Duration) -> NactlError {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => no_response(server, timeout),
+        // Windows reports ICMP port unreachable on a UDP socket as a reset
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset => {
+            NactlError::network_error(format!("{} is not accepting DNS queries", server))
+        }
+        _ => NactlError::network_error(format!("DNS query to {} failed: {}", server, e)),
+    }
+}
+
+/// Unpredictable query ID, so off-path spoofed answers are unlikely to match
+fn random_id() -> u16 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0),
+    );
+    hasher.finish() as u16
+}
+
+fn elapsed_ms(started: Instant) -> f64 {
+    started.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answer `query` as a stand-in server would: one A record unless
+    /// `truncate` is set, in which case the answer is cut and TC set
+    fn answer(query: &[u8], truncate: bool, rcode: u8) -> Vec<u8> {
+        let question_end = read_name(query, 12).unwrap().1 + 4;
+        let answers = u16::from(!truncate && rcode == 0);
+        let mut flags = FLAG_QR | FLAG_RD | FLAG_RA | rcode as u16;
+        if truncate {
+            flags |= FLAG_TC;
+        }
+
+        let mut packet = query[..2].to_vec();
+        packet.extend_from_slice(&flags.to_be_bytes());
+        packet.extend_from_slice(&[0, 1]);
+        packet.extend_from_slice(&answers.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0]);
+        packet.extend_from_slice(&query[12..question_end]);
+        if answers == 1 {
+            // Name as a pointer to the question, A, IN, TTL 300, 192.0.2.1
+            packet.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 1, 44, 0, 4, 192, 0, 2, 1]);
+        }
+        packet
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let packet = encode_query(0x1234, "example.com.", RecordType::Aaaa).unwrap();
+
+        assert_eq!(&packet[..4], &[0x12, 0x34, 0x01, 0x20]);
+        assert_eq!(&packet[4..12], &[0, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(&packet[12..25], b"\x07example\x03com\x00");
+        assert_eq!(&packet[25..29], &[0, 28, 0, 1]);
+        // OPT advertising 1232 bytes
+        assert_eq!(&packet[29..], &[0, 0, 41, 0x04, 0xd0, 0, 0, 0, 0, 0, 0]);
+
+        assert!(encode_query(1, "bad..name", RecordType::A).is_err());
+        assert!(encode_query(1, &"a".repeat(64), RecordType::A).is_err());
+        assert!(encode_query(1, "has space.com", RecordType::A).is_err());
+        assert!(encode_query(1, "_sip._tcp.example.com", RecordType::Srv).is_ok());
+    }
+
+    #[test]
+    fn test_decode_records() {
+        let query = encode_query(7, "example.com", RecordType::Mx).unwrap();
+        let question_end = read_name(&query, 12).unwrap().1 + 4;
+
+        let mut packet = vec![0, 7, 0x85, 0xa0, 0, 1, 0, 4, 0, 0, 0, 0];
+        packet.extend_from_slice(&query[12..question_end]);
+        // MX 10 mail.example.com (suffix compressed)
+        packet.extend_from_slice(&[0xc0, 12, 0, 15, 0, 1, 0, 0, 0x0e, 0x10, 0, 9, 0, 10]);
+        packet.extend_from_slice(b"\x04mail\xc0\x0c");
+        // TXT split over two strings
+        packet.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60, 0, 8]);
+        packet.extend_from_slice(b"\x03v=s\x03pf1");
+        // AAAA
+        packet.extend_from_slice(&[0xc0, 12, 0, 28, 0, 1, 0, 0, 0, 60, 0, 16]);
+        packet.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        // SRV 0 5 5060 sip.example.com
+        packet.extend_from_slice(&[0xc0, 12, 0, 33, 0, 1, 0, 0, 0, 60, 0, 12]);
+        packet.extend_from_slice(&[0, 0, 0, 5, 0x13, 0xc4]);
+        packet.extend_from_slice(b"\x03sip\xc0\x0c");
+
+        let message = decode(&packet).unwrap();
+        assert_eq!(message.id, 7);
+        assert_eq!(message.response_code(), "NOERROR");
+        assert_eq!(
+            message.flags,
+            Flags {
+                aa: true,
+                tc: false,
+                rd: true,
+                ra: true,
+                ad: true
+            }
+        );
+
+        let data: Vec<(&str, &str, u32)> = message
+            .answers
+            .iter()
+            .map(|r| (r.record_type.as_str(), r.data.as_str(), r.ttl))
+            .collect();
+        assert_eq!(
+            data,
+            vec![
+                ("MX", "10 mail.example.com", 3600),
+                ("TXT", "v=spf1", 60),
+                ("AAAA", "2001:db8::1", 60),
+                ("SRV", "0 5 5060 sip.example.com", 60),
+            ]
+        );
+        assert_eq!(message.answers[0].name, "example.com");
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_messages() {
+        assert!(decode(&[0, 1, 0x81]).is_err());
+
+        // A pointer to itself
+        let looped = [
+            0, 1, 0x81, 0x80, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 1, 0, 1,
+        ];
+        assert!(decode(&looped).is_err());
+
+        // Answer claims more data than the packet holds
+        let query = encode_query(1, "a.example", RecordType::A).unwrap();
+        let mut short = answer(&query, false, 0);
+        short.truncate(short.len() - 2);
+        assert!(decode(&short).is_err());
+    }
+
+    #[test]
+    fn test_reverse_name() {
+        assert_eq!(
+            reverse_name("192.0.2.10".parse().unwrap()),
+            "10.2.0.192.in-addr.arpa"
+        );
+        let v6 = reverse_name("2001:db8::567:89ab".parse().unwrap());
+        assert!(v6.starts_with("b.a.9.8.7.6.5.0.0.0.0.0"));
+        assert!(v6.ends_with("8.b.d.0.1.0.0.2.ip6.arpa"));
+        assert_eq!(v6.split('.').count(), 34);
+    }
+
+    #[test]
+    fn test_query_stand_in_server() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            // A stray answer with the wrong ID first, then the real one
+            let (len, from) = server.recv_from(&mut buf).unwrap();
+            let mut stray = answer(&buf[..len], false, 0);
+            stray[0] ^= 0xff;
+            server.send_to(&stray, from).unwrap();
+            server
+                .send_to(&answer(&buf[..len], false, 0), from)
+                .unwrap();
+
+            let (len, from) = server.recv_from(&mut buf).unwrap();
+            server
+                .send_to(&answer(&buf[..len], false, 3), from)
+                .unwrap();
+        });
+
+        let exchange = query(
+            addr,
+            "host.test",
+            RecordType::A,
+            false,
+            Duration::from_secs(2),
+        )
+        .unwrap();
+        assert_eq!(exchange.transport, Transport::Udp);
+        assert_eq!(exchange.message.answers[0].data, "192.0.2.1");
+        assert_eq!(exchange.message.answers[0].ttl, 300);
+        assert!(exchange.message.flags.ra);
+
+        let missing = query(
+            addr,
+            "missing.test",
+            RecordType::A,
+            false,
+            Duration::from_secs(2),
+        )
+        .unwrap();
+        assert_eq!(missing.message.response_code(), "NXDOMAIN");
+        assert!(missing.message.answers.is_empty());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_query_truncated_retries_over_tcp() {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = udp.local_addr().unwrap();
+        let Ok(tcp) = TcpListener::bind(addr) else {
+            return;
+        };
+
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = udp.recv_from(&mut buf).unwrap();
+            udp.send_to(&answer(&buf[..len], true, 0), from).unwrap();
+
+            let (mut stream, _) = tcp.accept().unwrap();
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut request = vec![0u8; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut request).unwrap();
+            let reply = answer(&request, false, 0);
+            stream
+                .write_all(&(reply.len() as u16).to_be_bytes())
+                .unwrap();
+            stream.write_all(&reply).unwrap();
+        });
+
+        let exchange = query(
+            addr,
+            "big.test",
+            RecordType::A,
+            false,
+            Duration::from_secs(2),
+        )
+        .unwrap();
+        assert_eq!(exchange.transport, Transport::Tcp);
+        assert_eq!(exchange.message.answers.len(), 1);
+        assert!(!exchange.message.flags.tc);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_query_timeout() {
+        // Bound but never answers
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = silent.local_addr().unwrap();
+
+        let started = Instant::now();
+        let result = query(
+            addr,
+            "slow.test",
+            RecordType::A,
+            false,
+            Duration::from_millis(200),
+        );
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
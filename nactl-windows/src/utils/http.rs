@@ -0,0 +1,281 @@
+//! Minimal HTTP/1.1 client for connectivity probes
+//!
+//! Probes must see exactly what the network returns, so redirects are never
+//! followed and only plain `http://` URLs are supported.
+
+use crate::errors::NactlError;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+/// Largest response body read from a probe endpoint
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// User agent sent with probes (matches the Windows NCSI client)
+const USER_AGENT: &str = "Microsoft NCSI";
+
+/// A parsed `http://` URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl Url {
+    /// Parse an `http://host[:port]/path` URL
+    pub fn parse(url: &str) -> Result<Self, NactlError> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            NactlError::invalid_input(format!("Only http:// URLs are supported: {}", url))
+        })?;
+
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, "/"),
+        };
+
+        let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            // IPv6 literal: [::1]:8080
+            let (host, after) = bracketed
+                .split_once(']')
+                .ok_or_else(|| NactlError::invalid_input(format!("Invalid URL: {}", url)))?;
+            let port = match after.strip_prefix(':') {
+                Some(p) => parse_port(p, url)?,
+                None => 80,
+            };
+            (host.to_string(), port)
+        } else if let Some((host, port)) = authority.rsplit_once(':') {
+            (host.to_string(), parse_port(port, url)?)
+        } else {
+            (authority.to_string(), 80)
+        };
+
+        if host.is_empty() {
+            return Err(NactlError::invalid_input(format!("Invalid URL: {}", url)));
+        }
+
+        Ok(Self {
+            host,
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Host header value for this URL
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+
+        if self.port == 80 {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+}
+
+fn parse_port(port: &str, url: &str) -> Result<u16, NactlError> {
+    port.parse::<u16>()
+        .map_err(|_| NactlError::invalid_input(format!("Invalid port in URL: {}", url)))
+}
+
+/// A received HTTP response
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub elapsed_ms: f64,
+}
+
+impl HttpResponse {
+    /// Look up a header value (case-insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether the response is a redirect with a Location header
+    pub fn redirect_location(&self) -> Option<&str> {
+        if (300..400).contains(&self.status) {
+            self.header("Location")
+        } else {
+            None
+        }
+    }
+}
+
+/// Issue a GET request for `url` to an already resolved address
+pub fn get_from(
+    addr: SocketAddr,
+    url: &Url,
+    timeout: Duration,
+) -> Result<HttpResponse, NactlError> {
+    let start = Instant::now();
+
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)
+        .map_err(|e| NactlError::network_error(format!("Failed to connect to {}: {}", addr, e)))?;
+    stream.set_read_timeout(Some(timeout)).ok();
+    stream.set_write_timeout(Some(timeout)).ok();
+
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: */*\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        url.path,
+        url.host_header(),
+        USER_AGENT
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| NactlError::network_error(format!("Failed to send request: {}", e)))?;
+
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                raw.extend_from_slice(&buf[..n]);
+                if raw.len() >= MAX_RESPONSE_BYTES {
+                    break;
+                }
+            }
+            // Keep whatever arrived before the server stalled
+            Err(_) if !raw.is_empty() => break,
+            Err(e) => {
+                return Err(NactlError::network_error(format!(
+                    "Failed to read response: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    let mut response = parse_response(&raw)?;
+    response.elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+    Ok(response)
+}
+
+fn parse_response(raw: &[u8]) -> Result<HttpResponse, NactlError> {
+    let header_end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| NactlError::parse_error("Incomplete HTTP response"))?;
+
+    let head = String::from_utf8_lossy(&raw[..header_end]);
+    let mut lines = head.split("\r\n");
+
+    // "HTTP/1.1 200 OK"
+    let status = lines
+        .next()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| NactlError::parse_error("Invalid HTTP status line"))?;
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let body_bytes = &raw[header_end + 4..];
+    let chunked = headers.iter().any(|(k, v)| {
+        k.eq_ignore_ascii_case("Transfer-Encoding") && v.to_lowercase().contains("chunked")
+    });
+
+    let body = if chunked {
+        decode_chunked(body_bytes)
+    } else {
+        body_bytes.to_vec()
+    };
+
+    Ok(HttpResponse {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+        elapsed_ms: 0.0,
+    })
+}
+
+fn decode_chunked(mut data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    while let Some(line_end) = data.windows(2).position(|w| w == b"\r\n") {
+        let size_line = String::from_utf8_lossy(&data[..line_end]);
+        let size_str = size_line.split(';').next().unwrap_or("").trim();
+        let size = match usize::from_str_radix(size_str, 16) {
+            Ok(0) | Err(_) => break,
+            Ok(size) => size,
+        };
+
+        data = &data[line_end + 2..];
+        let take = size.min(data.len());
+        body.extend_from_slice(&data[..take]);
+        data = &data[take..];
+        data = data.strip_prefix(b"\r\n").unwrap_or(data);
+    }
+
+    body
+}
+
+/// Serve a single canned HTTP response on a loopback port
+#[cfg(test)]
+pub(crate) fn stand_in_server(response: &'static str) -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    port
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let url = Url::parse("http://www.msftconnecttest.com/connecttest.txt").unwrap();
+        assert_eq!(url.host, "www.msftconnecttest.com");
+        assert_eq!(url.port, 80);
+        assert_eq!(url.path, "/connecttest.txt");
+
+        let url = Url::parse("http://127.0.0.1:8080").unwrap();
+        assert_eq!(url.port, 8080);
+        assert_eq!(url.path, "/");
+
+        let url = Url::parse("http://[::1]:81/x").unwrap();
+        assert_eq!(url.host, "::1");
+        assert_eq!(url.host_header(), "[::1]:81");
+
+        assert!(Url::parse("https://example.com/").is_err());
+        assert!(Url::parse("http://:80/").is_err());
+    }
+
+    #[test]
+    fn test_parse_chunked_response() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nHello\r\n6\r\n World\r\n0\r\n\r\n";
+        let response = parse_response(raw).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "Hello World");
+    }
+
+    #[test]
+    fn test_redirect_location() {
+        let raw = b"HTTP/1.1 302 Found\r\nlocation: http://portal.example/login\r\n\r\n";
+        let response = parse_response(raw).unwrap();
+        assert_eq!(
+            response.redirect_location(),
+            Some("http://portal.example/login")
+        );
+    }
+}
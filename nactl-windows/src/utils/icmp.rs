@@ -0,0 +1,486 @@
+//! In-process ICMP echo
+//!
+//! Sends echo requests over an ICMP datagram socket where the OS allows it
+//! unprivileged, or a raw socket when running elevated. Callers fall back to
+//! `ping.exe` when neither can be opened (Windows has no unprivileged ICMP
+//! sockets, so that is the non-elevated case there).
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+const ICMPV4_ECHO_REPLY: u8 = 0;
+const ICMPV4_UNREACHABLE: u8 = 3;
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV4_TIME_EXCEEDED: u8 = 11;
+
+const ICMPV6_UNREACHABLE: u8 = 1;
+const ICMPV6_PACKET_TOO_BIG: u8 = 2;
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// Distinguishes pingers in one process; raw sockets see every ICMP packet
+static NEXT_IDENT: AtomicU16 = AtomicU16::new(0);
+
+/// Largest packet read back, enough for any reply to a 64KB request
+const RECV_BUFFER_BYTES: usize = 65536 + 128;
+
+/// How the echo socket was opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketMode {
+    /// Unprivileged ICMP datagram socket
+    Datagram,
+    /// Raw socket (requires elevation)
+    Raw,
+}
+
+/// Per-request options
+#[derive(Debug, Clone)]
+pub struct EchoOptions {
+    /// Payload size in bytes
+    pub size: usize,
+    pub ttl: Option<u32>,
+    pub dont_fragment: bool,
+    /// Local address to send from
+    pub source: Option<IpAddr>,
+}
+
+impl Default for EchoOptions {
+    fn default() -> Self {
+        // Same payload size as ping.exe
+        Self {
+            size: 32,
+            ttl: None,
+            dont_fragment: false,
+            source: None,
+        }
+    }
+}
+
+/// What came back for one echo request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoOutcome {
+    EchoReply,
+    TimeExceeded,
+    NetUnreachable,
+    HostUnreachable,
+    /// Packet was larger than the path MTU with DF set (`mtu` when reported)
+    FragmentationNeeded {
+        mtu: Option<u32>,
+    },
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct EchoResponse {
+    pub outcome: EchoOutcome,
+    /// Address that answered (the target, or a router for errors)
+    pub from: Option<IpAddr>,
+    /// TTL of the reply, when the IP header is visible
+    pub ttl: Option<u32>,
+    pub rtt_ms: f64,
+}
+
+/// An open ICMP echo socket bound to one target
+pub struct Pinger {
+    socket: Socket,
+    target: IpAddr,
+    mode: SocketMode,
+    ident: u16,
+    payload: Vec<u8>,
+}
+
+impl Pinger {
+    /// Open a socket for `target`, preferring a datagram socket over raw
+    pub fn new(target: IpAddr, options: &EchoOptions) -> io::Result<Self> {
+        let (domain, protocol) = match target {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+        };
+
+        let (socket, mode) = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+            Ok(socket) => (socket, SocketMode::Datagram),
+            Err(_) => (
+                Socket::new(domain, Type::RAW, Some(protocol))?,
+                SocketMode::Raw,
+            ),
+        };
+
+        if let Some(source) = options.source {
+            socket.bind(&SocketAddr::new(source, 0).into())?;
+        }
+        if let Some(ttl) = options.ttl {
+            match target {
+                IpAddr::V4(_) => socket.set_ttl(ttl)?,
+                IpAddr::V6(_) => socket.set_unicast_hops_v6(ttl)?,
+            }
+        }
+        if options.dont_fragment {
+            set_dont_fragment(&socket, target.is_ipv6())?;
+        }
+
+        Ok(Self {
+            socket,
+            target,
+            mode,
+            ident: next_ident(),
+            payload: payload(options.size),
+        })
+    }
+
+    pub fn mode(&self) -> SocketMode {
+        self.mode
+    }
+
+    /// Change the payload size of subsequent requests
+    pub fn set_size(&mut self, size: usize) {
+        self.payload = payload(size);
+    }
+
+    /// Send one echo request and wait up to `timeout` for its answer
+    pub fn echo(&self, seq: u16, timeout: Duration) -> io::Result<EchoResponse> {
+        let request = self.build_request(seq);
+        let target = SockAddr::from(SocketAddr::new(self.target, 0));
+
+        let start = Instant::now();
+        if let Err(e) = self.socket.send_to(&request, &target) {
+            if e.raw_os_error() == Some(EMSGSIZE) {
+                return Ok(response(
+                    EchoOutcome::FragmentationNeeded { mtu: None },
+                    None,
+                    None,
+                    start,
+                ));
+            }
+            return Err(e);
+        }
+
+        let mut buf = vec![MaybeUninit::<u8>::uninit(); RECV_BUFFER_BYTES];
+        loop {
+            let remaining = match timeout.checked_sub(start.elapsed()) {
+                Some(d) if !d.is_zero() => d,
+                _ => return Ok(response(EchoOutcome::Timeout, None, None, start)),
+            };
+            self.socket.set_read_timeout(Some(remaining))?;
+
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // SAFETY: recv_from initialized the first `len` bytes
+            let packet: Vec<u8> = buf[..len]
+                .iter()
+                .map(|b| unsafe { b.assume_init() })
+                .collect();
+            let from = from.as_socket().map(|a| a.ip());
+
+            if let Some((outcome, ttl)) = self.match_packet(&packet, seq) {
+                return Ok(response(outcome, from, ttl, start));
+            }
+        }
+    }
+
+    fn build_request(&self, seq: u16) -> Vec<u8> {
+        echo_request(self.target.is_ipv6(), self.ident, seq, &self.payload)
+    }
+
+    /// Outcome and reply TTL if `packet` answers request `seq`
+    fn match_packet(&self, packet: &[u8], seq: u16) -> Option<(EchoOutcome, Option<u32>)> {
+        let (message, ttl) = parse(packet, self.target.is_ipv6())?;
+        match message {
+            IcmpMessage::EchoReply {
+                ident,
+                seq: reply_seq,
+            } => self
+                .is_ours(ident, reply_seq, seq)
+                .then_some((EchoOutcome::EchoReply, ttl)),
+            IcmpMessage::Error {
+                outcome, quoted, ..
+            } => {
+                // The quoted packet is our echo request
+                if quoted.len() < 8 {
+                    return None;
+                }
+                let ident = u16::from_be_bytes([quoted[4], quoted[5]]);
+                let quoted_seq = u16::from_be_bytes([quoted[6], quoted[7]]);
+                self.is_ours(ident, quoted_seq, seq)
+                    .then_some((outcome, ttl))
+            }
+        }
+    }
+
+    /// Whether an identifier/sequence pair belongs to this request. Datagram
+    /// sockets have the identifier rewritten by the kernel, so only the
+    /// sequence number is meaningful there.
+    fn is_ours(&self, ident: u16, reply_seq: u16, seq: u16) -> bool {
+        reply_seq == seq && (self.mode == SocketMode::Datagram || ident == self.ident)
+    }
+}
+
+/// A received ICMP message, decoded far enough to match it to a probe
+#[derive(Debug, PartialEq, Eq)]
+pub enum IcmpMessage<'a> {
+    EchoReply {
+        ident: u16,
+        seq: u16,
+    },
+    /// Time exceeded, unreachable or packet too big
+    Error {
+        outcome: EchoOutcome,
+        /// Destination port unreachable: a UDP probe reached the host
+        port_unreachable: bool,
+        /// Transport header of the offending packet (ICMP, UDP or TCP),
+        /// after its quoted IP header
+        quoted: &'a [u8],
+    },
+}
+
+/// Decode a packet read from an ICMP socket, with the reply TTL when the
+/// IP header is visible. Anything other than echo replies and errors is
+/// `None`.
+pub fn parse(packet: &[u8], v6: bool) -> Option<(IcmpMessage<'_>, Option<u32>)> {
+    let (icmp, ttl) = match v6 {
+        // IPv4 raw sockets include the IP header; ICMP types never start
+        // with a 4 in the high nibble, so its presence is unambiguous
+        false if packet.first().map(|b| b >> 4) == Some(4) => {
+            let header_len = ((packet[0] & 0x0f) as usize) * 4;
+            (packet.get(header_len..)?, packet.get(8).map(|&t| t as u32))
+        }
+        _ => (packet, None),
+    };
+    if icmp.len() < 8 {
+        return None;
+    }
+
+    let (kind, code) = (icmp[0], icmp[1]);
+    let v4 = !v6;
+
+    let echo_reply = if v4 {
+        ICMPV4_ECHO_REPLY
+    } else {
+        ICMPV6_ECHO_REPLY
+    };
+    if kind == echo_reply {
+        let message = IcmpMessage::EchoReply {
+            ident: u16::from_be_bytes([icmp[4], icmp[5]]),
+            seq: u16::from_be_bytes([icmp[6], icmp[7]]),
+        };
+        return Some((message, ttl));
+    }
+
+    let outcome = match (v4, kind, code) {
+        (true, ICMPV4_TIME_EXCEEDED, _) | (false, ICMPV6_TIME_EXCEEDED, _) => {
+            EchoOutcome::TimeExceeded
+        }
+        (true, ICMPV4_UNREACHABLE, 0) | (false, ICMPV6_UNREACHABLE, 0) => {
+            EchoOutcome::NetUnreachable
+        }
+        (true, ICMPV4_UNREACHABLE, 4) => {
+            let mtu = u16::from_be_bytes([icmp[6], icmp[7]]) as u32;
+            EchoOutcome::FragmentationNeeded {
+                mtu: (mtu > 0).then_some(mtu),
+            }
+        }
+        (false, ICMPV6_PACKET_TOO_BIG, _) => EchoOutcome::FragmentationNeeded {
+            mtu: Some(u32::from_be_bytes([icmp[4], icmp[5], icmp[6], icmp[7]])),
+        },
+        (true, ICMPV4_UNREACHABLE, _) | (false, ICMPV6_UNREACHABLE, _) => {
+            EchoOutcome::HostUnreachable
+        }
+        _ => return None,
+    };
+    let port_unreachable = matches!(
+        (v4, kind, code),
+        (true, ICMPV4_UNREACHABLE, 3) | (false, ICMPV6_UNREACHABLE, 4)
+    );
+
+    // Errors quote the offending packet after the 8-byte ICMP header
+    let quoted = &icmp[8..];
+    let quoted = if v4 {
+        let header_len = ((*quoted.first()? & 0x0f) as usize) * 4;
+        quoted.get(header_len..)?
+    } else {
+        // Fixed 40-byte IPv6 header
+        quoted.get(40..)?
+    };
+
+    let message = IcmpMessage::Error {
+        outcome,
+        port_unreachable,
+        quoted,
+    };
+    Some((message, ttl))
+}
+
+/// Echo request packet; the IPv4 checksum is filled in, the kernel
+/// computes the ICMPv6 one (it covers a pseudo-header)
+pub fn echo_request(v6: bool, ident: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let kind = if v6 {
+        ICMPV6_ECHO_REQUEST
+    } else {
+        ICMPV4_ECHO_REQUEST
+    };
+
+    let mut packet = vec![kind, 0, 0, 0];
+    packet.extend_from_slice(&ident.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(payload);
+
+    if !v6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// Identifier for a new echo socket, unique within the process
+pub fn next_ident() -> u16 {
+    (std::process::id() as u16).wrapping_add(NEXT_IDENT.fetch_add(1, Ordering::Relaxed))
+}
+
+/// ping.exe's payload pattern
+fn payload(size: usize) -> Vec<u8> {
+    (0..size).map(|i| b'a' + (i % 23) as u8).collect()
+}
+
+fn response(
+    outcome: EchoOutcome,
+    from: Option<IpAddr>,
+    ttl: Option<u32>,
+    start: Instant,
+) -> EchoResponse {
+    EchoResponse {
+        outcome,
+        from,
+        ttl,
+        rtt_ms: start.elapsed().as_secs_f64() * 1000.0,
+    }
+}
+
+/// RFC 1071 internet checksum
+pub fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => u16::from_be_bytes([*hi, *lo]) as u32,
+            [hi] => (*hi as u32) << 8,
+            _ => 0,
+        })
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(windows)]
+const EMSGSIZE: i32 = 10040; // WSAEMSGSIZE
+
+#[cfg(not(windows))]
+const EMSGSIZE: i32 = libc::EMSGSIZE;
+
+#[cfg(windows)]
+fn set_dont_fragment(socket: &Socket, v6: bool) -> io::Result<()> {
+    use std::os::windows::io::AsRawSocket;
+    use windows::Win32::Networking::WinSock::{
+        setsockopt, IPPROTO_IP, IPPROTO_IPV6, IPV6_DONTFRAG, IP_DONTFRAGMENT, SOCKET,
+    };
+
+    let (level, name) = if v6 {
+        (IPPROTO_IPV6.0, IPV6_DONTFRAG)
+    } else {
+        (IPPROTO_IP.0, IP_DONTFRAGMENT)
+    };
+    let value = 1i32.to_ne_bytes();
+
+    // SAFETY: the socket handle is valid for the lifetime of `socket`
+    let result = unsafe {
+        setsockopt(
+            SOCKET(socket.as_raw_socket() as usize),
+            level,
+            name,
+            Some(&value),
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(windows))]
+fn set_dont_fragment(socket: &Socket, v6: bool) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name, value) = if v6 {
+        (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_DO,
+        )
+    } else {
+        (
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_DO,
+        )
+    };
+
+    // SAFETY: the descriptor is valid and `value` outlives the call
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const i32 as *const libc::c_void,
+            std::mem::size_of::<i32>() as libc::socklen_t,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        // Echo request, id 1, seq 1, no payload
+        let packet = [8, 0, 0, 0, 0, 1, 0, 1];
+        assert_eq!(checksum(&packet), 0xf7fd);
+        assert_eq!(checksum(&[0xff]), 0x00ff);
+    }
+
+    #[test]
+    #[ignore = "needs unprivileged ICMP sockets (ping_group_range), root or CAP_NET_RAW; run with --ignored"]
+    fn test_echo_loopback() {
+        let options = EchoOptions {
+            size: 64,
+            ttl: Some(32),
+            dont_fragment: true,
+            source: Some("127.0.0.1".parse().unwrap()),
+        };
+        let pinger = Pinger::new("127.0.0.1".parse().unwrap(), &options).unwrap();
+
+        let reply = pinger.echo(1, Duration::from_secs(2)).unwrap();
+        assert_eq!(reply.outcome, EchoOutcome::EchoReply);
+        assert_eq!(reply.from, Some("127.0.0.1".parse().unwrap()));
+        assert!(reply.rtt_ms < 1000.0);
+    }
+}
@@ -0,0 +1,50 @@
+//! Ctrl+C handling for long-running commands
+//!
+//! Commands that run until interrupted install the handler and poll
+//! `requested()` so they can stop cleanly and still print their final output.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl+C has been pressed since `install()`
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Catch Ctrl+C instead of letting it terminate the process
+#[cfg(windows)]
+pub fn install() {
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::System::Console::{SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT};
+
+    unsafe extern "system" fn handler(ctrl_type: u32) -> BOOL {
+        if ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            BOOL(1)
+        } else {
+            BOOL(0)
+        }
+    }
+
+    // SAFETY: the handler only touches an atomic
+    unsafe {
+        let _ = SetConsoleCtrlHandler(Some(handler), true);
+    }
+}
+
+/// Catch Ctrl+C instead of letting it terminate the process
+#[cfg(not(windows))]
+pub fn install() {
+    extern "C" fn handler(_signal: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handler only touches an atomic, which is signal-safe
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            handler as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
@@ -1,8 +1,16 @@
 //! Utility modules for nactl
 
 pub mod admin;
+pub mod asn;
+pub mod dns;
+pub mod http;
+pub mod icmp;
+pub mod interrupt;
 pub mod netsh;
 pub mod output;
 pub mod powershell;
 pub mod registry;
+pub mod resolve;
+pub mod stats;
+pub mod traceroute;
 pub mod validation;
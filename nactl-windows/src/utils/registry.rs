@@ -7,6 +7,7 @@ use winreg::enums::*;
 #[cfg(windows)]
 use winreg::RegKey;
 
+#[cfg(windows)]
 const INTERNET_SETTINGS_PATH: &str = r"Software\Microsoft\Windows\CurrentVersion\Internet Settings";
 
 /// Get proxy enabled status from registry
@@ -188,6 +189,3 @@ fn notify_internet_settings_change() {
         );
     }
 }
-
-#[cfg(not(windows))]
-fn notify_internet_settings_change() {}
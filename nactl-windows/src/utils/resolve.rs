@@ -0,0 +1,193 @@
+//! Reverse DNS lookups
+
+use socket2::SockAddr;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Result of one reverse lookup
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReverseLookup {
+    pub hostname: Option<String>,
+    /// How long the resolver took
+    pub elapsed_ms: f64,
+}
+
+/// Look every address up at once. Addresses whose lookup did not finish
+/// within `timeout` are missing from the result; their threads are left to
+/// finish in the background since the system resolver cannot be cancelled.
+pub fn reverse_lookup_all(ips: &[IpAddr], timeout: Duration) -> HashMap<IpAddr, ReverseLookup> {
+    lookup_all_with(ips, timeout, reverse_lookup)
+}
+
+/// `reverse_lookup_all` with the per-address lookup supplied
+fn lookup_all_with<F>(
+    ips: &[IpAddr],
+    timeout: Duration,
+    lookup: F,
+) -> HashMap<IpAddr, ReverseLookup>
+where
+    F: Fn(IpAddr) -> Option<String> + Copy + Send + 'static,
+{
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = mpsc::channel();
+
+    let mut pending = 0;
+    for &ip in ips {
+        let sender = sender.clone();
+        let spawned = thread::Builder::new().spawn(move || {
+            let started = Instant::now();
+            let hostname = lookup(ip);
+            let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+            let _ = sender.send((
+                ip,
+                ReverseLookup {
+                    hostname,
+                    elapsed_ms,
+                },
+            ));
+        });
+        if spawned.is_ok() {
+            pending += 1;
+        }
+    }
+
+    let mut results = HashMap::new();
+    while pending > 0 {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok((ip, lookup)) => {
+                results.insert(ip, lookup);
+                pending -= 1;
+            }
+            Err(_) => break,
+        }
+    }
+    results
+}
+
+/// Hostname for an address via the system resolver (PTR, NetBIOS, hosts
+/// file), or `None` when it has no name
+#[cfg(windows)]
+pub fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    use std::sync::Once;
+    use windows::Win32::Networking::WinSock::{
+        getnameinfo, WSAStartup, NI_MAXHOST, NI_NAMEREQD, SOCKADDR, WSADATA,
+    };
+
+    // Winsock must be initialized before resolver calls; std only does so
+    // lazily when it first opens a socket
+    static WINSOCK: Once = Once::new();
+    WINSOCK.call_once(|| {
+        let mut data = WSADATA::default();
+        // SAFETY: `data` is a valid out pointer
+        unsafe {
+            WSAStartup(0x0202, &mut data);
+        }
+    });
+
+    let addr = SockAddr::from(SocketAddr::new(ip, 0));
+    let mut host = vec![0u8; NI_MAXHOST as usize];
+
+    // SAFETY: `addr` points to a valid sockaddr of the given length
+    let result = unsafe {
+        getnameinfo(
+            addr.as_ptr() as *const SOCKADDR,
+            windows::Win32::Networking::WinSock::socklen_t(addr.len()),
+            Some(&mut host),
+            None,
+            NI_NAMEREQD as i32,
+        )
+    };
+
+    if result != 0 {
+        return None;
+    }
+    name_from_buffer(&host, ip)
+}
+
+/// Hostname for an address via the system resolver, or `None` when it has
+/// no name
+#[cfg(not(windows))]
+pub fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    let addr = SockAddr::from(SocketAddr::new(ip, 0));
+    let mut host = vec![0 as libc::c_char; libc::NI_MAXHOST as usize];
+
+    // SAFETY: `addr` points to a valid sockaddr of the given length and
+    // `host` is writable for its full length
+    let result = unsafe {
+        libc::getnameinfo(
+            addr.as_ptr(),
+            addr.len(),
+            host.as_mut_ptr(),
+            host.len() as libc::socklen_t,
+            std::ptr::null_mut(),
+            0,
+            libc::NI_NAMEREQD,
+        )
+    };
+
+    if result != 0 {
+        return None;
+    }
+    let bytes: Vec<u8> = host.iter().map(|&c| c as u8).collect();
+    name_from_buffer(&bytes, ip)
+}
+
+/// NUL-terminated name, ignoring resolvers that echo the address back
+fn name_from_buffer(buffer: &[u8], ip: IpAddr) -> Option<String> {
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    let name = String::from_utf8_lossy(&buffer[..end]).to_string();
+
+    if name.is_empty() || name.parse::<IpAddr>() == Ok(ip) {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_from_buffer() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        assert_eq!(
+            name_from_buffer(b"router.lan\0\0", ip),
+            Some("router.lan".to_string())
+        );
+        assert_eq!(name_from_buffer(b"10.0.0.1\0", ip), None);
+        assert_eq!(name_from_buffer(b"\0", ip), None);
+    }
+
+    #[test]
+    fn test_lookup_all_deadline() {
+        let fast: IpAddr = "10.0.0.1".parse().unwrap();
+        let unnamed: IpAddr = "10.0.0.2".parse().unwrap();
+        let slow: IpAddr = "10.0.0.3".parse().unwrap();
+
+        let started = Instant::now();
+        let results =
+            lookup_all_with(
+                &[fast, unnamed, slow],
+                Duration::from_millis(300),
+                |ip| match ip.to_string().as_str() {
+                    "10.0.0.1" => Some("router.lan".to_string()),
+                    "10.0.0.3" => {
+                        thread::sleep(Duration::from_secs(3));
+                        Some("slow.lan".to_string())
+                    }
+                    _ => None,
+                },
+            );
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(results[&fast].hostname.as_deref(), Some("router.lan"));
+        assert_eq!(results[&unnamed].hostname, None);
+        assert!(!results.contains_key(&slow));
+        assert!(reverse_lookup_all(&[], Duration::from_millis(10)).is_empty());
+    }
+}
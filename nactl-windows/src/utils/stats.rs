@@ -0,0 +1,125 @@
+//! Latency statistics and call quality estimates
+
+/// Population standard deviation
+pub fn stddev(samples: &[f64]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+    Some(variance.sqrt())
+}
+
+/// RFC 3550 interarrival jitter over consecutive samples in send order:
+/// a running average of RTT differences with a 1/16 gain
+pub fn jitter(samples: &[f64]) -> Option<f64> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let jitter = samples
+        .windows(2)
+        .fold(0.0, |j, pair| j + ((pair[1] - pair[0]).abs() - j) / 16.0);
+    Some(jitter)
+}
+
+/// Nearest-rank percentile (`pct` in 0-100)
+pub fn percentile(samples: &[f64], pct: f64) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// Length of the longest run of consecutive losses
+pub fn longest_loss_burst(received: &[bool]) -> u32 {
+    received
+        .iter()
+        .fold((0u32, 0u32), |(longest, run), &ok| {
+            let run = if ok { 0 } else { run + 1 };
+            (longest.max(run), run)
+        })
+        .0
+}
+
+/// Estimated E-model R-factor (ITU-T G.107, simplified for VoIP monitoring)
+pub fn r_factor(latency_ms: f64, jitter_ms: f64, loss_percent: f64) -> f64 {
+    // Jitter buffers add roughly twice the jitter, plus codec delay
+    let effective_latency = latency_ms + 2.0 * jitter_ms + 10.0;
+
+    let delay_impairment = if effective_latency < 160.0 {
+        effective_latency / 40.0
+    } else {
+        (effective_latency - 120.0) / 10.0
+    };
+
+    (93.2 - delay_impairment - 2.5 * loss_percent).clamp(0.0, 100.0)
+}
+
+/// Mean Opinion Score (1.0 - 4.5) for an R-factor
+pub fn mos(r: f64) -> f64 {
+    if r <= 0.0 {
+        1.0
+    } else if r >= 100.0 {
+        4.5
+    } else {
+        1.0 + 0.035 * r + 0.000007 * r * (r - 60.0) * (100.0 - r)
+    }
+}
+
+/// Round to a fixed number of decimal places for output
+pub fn round_to(value: f64, places: i32) -> f64 {
+    let factor = 10f64.powi(places);
+    (value * factor).round() / factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples = [5.0, 1.0, 4.0, 2.0, 3.0, 10.0, 6.0, 8.0, 7.0, 9.0];
+        assert_eq!(percentile(&samples, 50.0), Some(5.0));
+        assert_eq!(percentile(&samples, 90.0), Some(9.0));
+        assert_eq!(percentile(&samples, 99.0), Some(10.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_stddev_and_jitter() {
+        assert_eq!(stddev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]), Some(2.0));
+        assert_eq!(jitter(&[10.0, 10.0, 10.0]), Some(0.0));
+        // One 16ms swing contributes 1/16 of it
+        assert_eq!(jitter(&[10.0, 26.0]), Some(1.0));
+        assert_eq!(jitter(&[10.0]), None);
+    }
+
+    #[test]
+    fn test_longest_loss_burst() {
+        assert_eq!(
+            longest_loss_burst(&[true, false, false, true, false, false, false]),
+            3
+        );
+        assert_eq!(longest_loss_burst(&[true, true]), 0);
+    }
+
+    #[test]
+    fn test_quality_score() {
+        // Clean wired path: toll quality
+        let good = r_factor(20.0, 1.0, 0.0);
+        assert!(good > 90.0);
+        assert!(mos(good) > 4.3);
+
+        // Lossy, jittery path: users notice
+        let bad = r_factor(180.0, 40.0, 10.0);
+        assert!(bad < 60.0);
+        assert!(mos(bad) < 3.1);
+
+        assert_eq!(mos(0.0), 1.0);
+        assert_eq!(r_factor(2000.0, 0.0, 100.0), 0.0);
+    }
+}
@@ -0,0 +1,518 @@
+//! In-process traceroute
+//!
+//! Sends every probe up front, each with its own TTL, and collects the ICMP
+//! answers on one raw socket until every hop has answered or timed out, or
+//! the overall deadline passes. Probes are ICMP echo requests, UDP datagrams
+//! to high ports, or TCP SYNs from a non-blocking connect. Reading the ICMP
+//! errors needs a raw socket, so this requires elevation (root on Linux).
+
+use crate::utils::icmp::{self, EchoOutcome, IcmpMessage};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// First destination port of UDP probes, as in classic traceroute
+pub const DEFAULT_UDP_PORT: u16 = 33434;
+
+/// Wait between sends so routers' ICMP rate limits are not tripped
+const SEND_SPACING: Duration = Duration::from_millis(2);
+
+/// How long one receive waits before send-side state is checked again
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceProtocol {
+    Icmp,
+    Udp,
+    Tcp,
+}
+
+#[derive(Debug, Clone)]
+pub struct TracerouteOptions {
+    pub protocol: TraceProtocol,
+    /// TCP destination port, or the first UDP port (one per probe)
+    pub port: u16,
+    pub max_hops: u8,
+    pub probes_per_hop: u8,
+    /// How long to wait for each probe's answer
+    pub probe_timeout: Duration,
+    /// Hard limit for the whole trace
+    pub deadline: Option<Duration>,
+}
+
+/// What came back for one probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    /// A router on the way answered
+    TimeExceeded,
+    /// The destination answered (echo reply, port unreachable, SYN-ACK/RST)
+    Reached,
+    NetUnreachable,
+    HostUnreachable,
+    Timeout,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeReply {
+    pub ttl: u8,
+    pub outcome: ProbeOutcome,
+    pub from: Option<IpAddr>,
+    pub rtt_ms: Option<f64>,
+}
+
+/// A probe in flight
+struct Probe {
+    ttl: u8,
+    /// Echo sequence, UDP destination port, or TCP source port
+    key: u16,
+    sent: Instant,
+    /// TCP probes keep their connecting socket
+    socket: Option<Socket>,
+    reply: Option<ProbeReply>,
+}
+
+impl Probe {
+    fn answer(&mut self, outcome: ProbeOutcome, from: Option<IpAddr>) {
+        if self.reply.is_none() {
+            self.reply = Some(ProbeReply {
+                ttl: self.ttl,
+                outcome,
+                from,
+                rtt_ms: Some(self.sent.elapsed().as_secs_f64() * 1000.0),
+            });
+        }
+    }
+}
+
+/// Trace the path to `target`, returning one reply per probe ordered by TTL,
+/// up to the hop where the destination answered
+pub fn trace(target: IpAddr, options: &TracerouteOptions) -> io::Result<Vec<ProbeReply>> {
+    let started = Instant::now();
+    let v6 = target.is_ipv6();
+    let (domain, icmp_protocol) = if v6 {
+        (Domain::IPV6, Protocol::ICMPV6)
+    } else {
+        (Domain::IPV4, Protocol::ICMPV4)
+    };
+
+    // Raw sockets on Windows must be bound before they can receive
+    let local = local_address(target)?;
+    let socket = Socket::new(domain, Type::RAW, Some(icmp_protocol))?;
+    socket.bind(&SocketAddr::new(local, 0).into())?;
+    let udp = match options.protocol {
+        TraceProtocol::Udp => {
+            let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+            socket.bind(&SocketAddr::new(local, 0).into())?;
+            Some(socket)
+        }
+        _ => None,
+    };
+    let mut listener = Listener {
+        udp_port: match &udp {
+            Some(socket) => local_port(socket)?,
+            None => 0,
+        },
+        socket,
+        ident: icmp::next_ident(),
+        v6,
+        buf: vec![MaybeUninit::<u8>::uninit(); 65536],
+    };
+    let ident = listener.ident;
+    let deadline = options.deadline.map(|d| started + d);
+
+    let mut probes: Vec<Probe> = Vec::new();
+    for ttl in 1..=options.max_hops {
+        // Nothing past the destination needs probing
+        if reached_ttl(&probes).is_some() || deadline.is_some_and(|d| Instant::now() >= d) {
+            break;
+        }
+        for _ in 0..options.probes_per_hop {
+            let index = probes.len() as u16;
+            let probe = match options.protocol {
+                TraceProtocol::Icmp => {
+                    set_ttl(&listener.socket, v6, ttl)?;
+                    let packet = icmp::echo_request(v6, ident, index, &[0u8; 32]);
+                    let sent = Instant::now();
+                    listener
+                        .socket
+                        .send_to(&packet, &SocketAddr::new(target, 0).into())?;
+                    new_probe(ttl, index, sent, None)
+                }
+                TraceProtocol::Udp => {
+                    let socket = udp.as_ref().expect("UDP socket opened above");
+                    let port = options.port.wrapping_add(index);
+                    set_ttl(socket, v6, ttl)?;
+                    let sent = Instant::now();
+                    socket.send_to(&[0u8; 32], &SocketAddr::new(target, port).into())?;
+                    new_probe(ttl, port, sent, None)
+                }
+                TraceProtocol::Tcp => {
+                    let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+                    socket.set_nonblocking(true)?;
+                    socket.bind(&SocketAddr::new(local, 0).into())?;
+                    set_ttl(&socket, v6, ttl)?;
+                    let port = local_port(&socket)?;
+                    let sent = Instant::now();
+                    // In progress is the expected result of a non-blocking connect
+                    let _ = socket.connect(&SocketAddr::new(target, options.port).into());
+                    new_probe(ttl, port, sent, Some(socket))
+                }
+            };
+            probes.push(probe);
+            // Collect replies while spacing sends so their times stay accurate
+            listener.drain(&mut probes, options, target, SEND_SPACING)?;
+        }
+    }
+
+    let last_sent = Instant::now();
+
+    loop {
+        let now = Instant::now();
+        let reached_ttl = reached_ttl(&probes);
+        let settled = probes
+            .iter()
+            .filter(|p| reached_ttl.is_none_or(|ttl| p.ttl <= ttl))
+            .all(|p| p.reply.is_some());
+
+        if settled || now >= last_sent + options.probe_timeout || deadline.is_some_and(|d| now >= d)
+        {
+            break;
+        }
+
+        listener.drain(&mut probes, options, target, POLL_INTERVAL)?;
+    }
+
+    let reached_ttl = reached_ttl(&probes);
+
+    Ok(probes
+        .into_iter()
+        .filter(|p| reached_ttl.is_none_or(|ttl| p.ttl <= ttl))
+        .map(|p| {
+            p.reply.unwrap_or(ProbeReply {
+                ttl: p.ttl,
+                outcome: ProbeOutcome::Timeout,
+                from: None,
+                rtt_ms: None,
+            })
+        })
+        .collect())
+}
+
+/// Raw ICMP socket that hears time-exceeded and unreachable errors
+struct Listener {
+    socket: Socket,
+    ident: u16,
+    /// Source port of the UDP probe socket
+    udp_port: u16,
+    v6: bool,
+    buf: Vec<MaybeUninit<u8>>,
+}
+
+impl Listener {
+    /// Match every reply that arrives within `wait` to its probe
+    fn drain(
+        &mut self,
+        probes: &mut [Probe],
+        options: &TracerouteOptions,
+        target: IpAddr,
+        wait: Duration,
+    ) -> io::Result<()> {
+        let until = Instant::now() + wait;
+        loop {
+            if options.protocol == TraceProtocol::Tcp {
+                poll_connects(probes, target);
+            }
+
+            let remaining = until.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(());
+            }
+            self.socket.set_read_timeout(Some(remaining))?;
+            let (len, from) = match self.socket.recv_from(&mut self.buf) {
+                Ok(received) => received,
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // SAFETY: recv_from initialized the first `len` bytes
+            let packet: Vec<u8> = self.buf[..len]
+                .iter()
+                .map(|b| unsafe { b.assume_init() })
+                .collect();
+            let from = from.as_socket().map(|a| a.ip());
+
+            let Some((message, _)) = icmp::parse(&packet, self.v6) else {
+                continue;
+            };
+            if let Some((key, outcome)) = match_reply(&message, options, self.ident, self.udp_port)
+            {
+                if let Some(probe) = probes.iter_mut().find(|p| p.key == key) {
+                    probe.answer(outcome, from);
+                }
+            }
+        }
+    }
+}
+
+/// Lowest TTL at which the destination answered
+fn reached_ttl(probes: &[Probe]) -> Option<u8> {
+    probes
+        .iter()
+        .filter(|p| matches!(&p.reply, Some(r) if r.outcome == ProbeOutcome::Reached))
+        .map(|p| p.ttl)
+        .min()
+}
+
+fn new_probe(ttl: u8, key: u16, sent: Instant, socket: Option<Socket>) -> Probe {
+    Probe {
+        ttl,
+        key,
+        sent,
+        socket,
+        reply: None,
+    }
+}
+
+/// Probe key and outcome for an ICMP message answering one of our probes
+fn match_reply(
+    message: &IcmpMessage,
+    options: &TracerouteOptions,
+    ident: u16,
+    udp_port: u16,
+) -> Option<(u16, ProbeOutcome)> {
+    match (message, options.protocol) {
+        (IcmpMessage::EchoReply { ident: id, seq }, TraceProtocol::Icmp) if *id == ident => {
+            Some((*seq, ProbeOutcome::Reached))
+        }
+        (
+            IcmpMessage::Error {
+                outcome,
+                port_unreachable,
+                quoted,
+            },
+            protocol,
+        ) => {
+            if quoted.len() < 8 {
+                return None;
+            }
+            let first = u16::from_be_bytes([quoted[0], quoted[1]]);
+            let second = u16::from_be_bytes([quoted[2], quoted[3]]);
+
+            let key = match protocol {
+                // Quoted echo request: identifier, then sequence
+                TraceProtocol::Icmp => {
+                    let quoted_ident = u16::from_be_bytes([quoted[4], quoted[5]]);
+                    let quoted_seq = u16::from_be_bytes([quoted[6], quoted[7]]);
+                    (quoted_ident == ident).then_some(quoted_seq)?
+                }
+                // Quoted UDP/TCP header: source port, then destination port
+                TraceProtocol::Udp => (first == udp_port).then_some(second)?,
+                TraceProtocol::Tcp => (second == options.port).then_some(first)?,
+            };
+
+            let outcome = match outcome {
+                EchoOutcome::TimeExceeded => ProbeOutcome::TimeExceeded,
+                _ if *port_unreachable && protocol == TraceProtocol::Udp => ProbeOutcome::Reached,
+                EchoOutcome::NetUnreachable => ProbeOutcome::NetUnreachable,
+                _ => ProbeOutcome::HostUnreachable,
+            };
+            Some((key, outcome))
+        }
+        _ => None,
+    }
+}
+
+/// Record TCP probes whose handshake completed or was refused: either way
+/// the destination answered
+fn poll_connects(probes: &mut [Probe], target: IpAddr) {
+    for probe in probes.iter_mut().filter(|p| p.reply.is_none()) {
+        let Some(socket) = probe.socket.as_ref() else {
+            continue;
+        };
+
+        let connected = socket.peer_addr().is_ok();
+        let refused = matches!(
+            socket.take_error(),
+            Ok(Some(e)) if e.kind() == io::ErrorKind::ConnectionRefused
+        );
+        if connected || refused {
+            probe.answer(ProbeOutcome::Reached, Some(target));
+            // Close now; only the handshake matters
+            probe.socket = None;
+        }
+    }
+}
+
+fn set_ttl(socket: &Socket, v6: bool, ttl: u8) -> io::Result<()> {
+    if v6 {
+        socket.set_unicast_hops_v6(ttl as u32)
+    } else {
+        socket.set_ttl(ttl as u32)
+    }
+}
+
+fn local_port(socket: &Socket) -> io::Result<u16> {
+    Ok(socket.local_addr()?.as_socket().map_or(0, |a| a.port()))
+}
+
+/// Local address the OS would use to reach `target`
+fn local_address(target: IpAddr) -> io::Result<IpAddr> {
+    let unspecified: IpAddr = if target.is_ipv6() {
+        "::".parse().expect("valid address")
+    } else {
+        "0.0.0.0".parse().expect("valid address")
+    };
+    // Connecting a UDP socket only selects a route; nothing is sent
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+    socket.connect(SocketAddr::new(target, 9))?;
+    Ok(socket.local_addr()?.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(protocol: TraceProtocol, port: u16) -> TracerouteOptions {
+        TracerouteOptions {
+            protocol,
+            port,
+            max_hops: 5,
+            probes_per_hop: 2,
+            probe_timeout: Duration::from_secs(2),
+            deadline: Some(Duration::from_secs(5)),
+        }
+    }
+
+    #[test]
+    fn test_match_quoted_udp() {
+        let quoted = [0x9c, 0x40, 0x82, 0x9a, 0, 40, 0, 0];
+        let message = IcmpMessage::Error {
+            outcome: EchoOutcome::TimeExceeded,
+            port_unreachable: false,
+            quoted: &quoted,
+        };
+        let udp = options(TraceProtocol::Udp, DEFAULT_UDP_PORT);
+
+        // Source port 40000, destination port 33434
+        assert_eq!(
+            match_reply(&message, &udp, 1, 40000),
+            Some((33434, ProbeOutcome::TimeExceeded))
+        );
+        assert_eq!(match_reply(&message, &udp, 1, 40001), None);
+    }
+
+    #[test]
+    fn test_match_icmp_echo_reply() {
+        let icmp = options(TraceProtocol::Icmp, 0);
+        let reply = IcmpMessage::EchoReply { ident: 7, seq: 3 };
+
+        assert_eq!(
+            match_reply(&reply, &icmp, 7, 0),
+            Some((3, ProbeOutcome::Reached))
+        );
+        // Another process's echo reply
+        assert_eq!(match_reply(&reply, &icmp, 8, 0), None);
+        // Echo replies do not answer UDP or TCP probes
+        assert_eq!(
+            match_reply(&reply, &options(TraceProtocol::Udp, DEFAULT_UDP_PORT), 7, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_match_quoted_icmp() {
+        // Quoted echo request: type 8, code 0, checksum, ident 7, seq 3
+        let quoted = [8, 0, 0xab, 0xcd, 0, 7, 0, 3];
+        let icmp = options(TraceProtocol::Icmp, 0);
+
+        let expired = IcmpMessage::Error {
+            outcome: EchoOutcome::TimeExceeded,
+            port_unreachable: false,
+            quoted: &quoted,
+        };
+        assert_eq!(
+            match_reply(&expired, &icmp, 7, 0),
+            Some((3, ProbeOutcome::TimeExceeded))
+        );
+        assert_eq!(match_reply(&expired, &icmp, 8, 0), None);
+
+        let unreachable = IcmpMessage::Error {
+            outcome: EchoOutcome::NetUnreachable,
+            port_unreachable: false,
+            quoted: &quoted,
+        };
+        assert_eq!(
+            match_reply(&unreachable, &icmp, 7, 0),
+            Some((3, ProbeOutcome::NetUnreachable))
+        );
+
+        // Too short to hold the quoted ident and seq
+        let truncated = IcmpMessage::Error {
+            outcome: EchoOutcome::TimeExceeded,
+            port_unreachable: false,
+            quoted: &quoted[..6],
+        };
+        assert_eq!(match_reply(&truncated, &icmp, 7, 0), None);
+    }
+
+    #[test]
+    fn test_match_quoted_tcp() {
+        // Source port 50000 (the probe key), destination port 443
+        let quoted = [0xc3, 0x50, 0x01, 0xbb, 0, 0, 0, 1];
+        let tcp = options(TraceProtocol::Tcp, 443);
+
+        let expired = IcmpMessage::Error {
+            outcome: EchoOutcome::TimeExceeded,
+            port_unreachable: false,
+            quoted: &quoted,
+        };
+        assert_eq!(
+            match_reply(&expired, &tcp, 1, 0),
+            Some((50000, ProbeOutcome::TimeExceeded))
+        );
+        assert_eq!(
+            match_reply(&expired, &options(TraceProtocol::Tcp, 80), 1, 0),
+            None
+        );
+
+        // Port unreachable only means "reached" for UDP probes
+        let port_unreachable = IcmpMessage::Error {
+            outcome: EchoOutcome::HostUnreachable,
+            port_unreachable: true,
+            quoted: &quoted,
+        };
+        assert_eq!(
+            match_reply(&port_unreachable, &tcp, 1, 0),
+            Some((50000, ProbeOutcome::HostUnreachable))
+        );
+    }
+
+    #[test]
+    #[ignore = "needs root or CAP_NET_RAW for raw sockets; run with --ignored"]
+    fn test_trace_loopback() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let target: IpAddr = "127.0.0.1".parse().unwrap();
+
+        for protocol in [TraceProtocol::Icmp, TraceProtocol::Udp, TraceProtocol::Tcp] {
+            let replies = trace(target, &options(protocol, port))
+                .unwrap_or_else(|e| panic!("{:?}: {}", protocol, e));
+
+            // Loopback is one hop away
+            assert!(replies.iter().all(|r| r.ttl == 1), "{:?}", protocol);
+            assert!(
+                replies.iter().any(|r| r.outcome == ProbeOutcome::Reached),
+                "{:?}: {:?}",
+                protocol,
+                replies
+            );
+        }
+    }
+}
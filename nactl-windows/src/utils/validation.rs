@@ -2,6 +2,7 @@
 
 use crate::errors::NactlError;
 use regex::Regex;
+use std::time::Duration;
 
 /// Maximum SSID length per Wi-Fi specification
 const MAX_SSID_LENGTH: usize = 32;
@@ -9,6 +10,18 @@ const MAX_SSID_LENGTH: usize = 32;
 /// Maximum hostname length per DNS specification
 const MAX_HOSTNAME_LENGTH: usize = 253;
 
+/// Maximum interface alias length Windows accepts
+const MAX_INTERFACE_NAME_LENGTH: usize = 256;
+
+/// Largest echo payload ping.exe accepts
+pub const MAX_PING_SIZE: u32 = 65500;
+
+/// Shortest gap between ping requests, to avoid flooding
+pub const MIN_PING_INTERVAL_MS: u64 = 100;
+
+/// Longest run accepted by `--duration` (30 days)
+pub const MAX_DURATION_SECS: u64 = 30 * 24 * 3600;
+
 /// Validate an SSID to prevent command injection
 pub fn validate_ssid(ssid: &str) -> Result<(), NactlError> {
     if ssid.is_empty() {
@@ -112,12 +125,98 @@ pub fn validate_ip_address(ip: &str) -> Result<(), NactlError> {
     Ok(())
 }
 
+/// Validate a network interface name (e.g. "Wi-Fi", "Ethernet 2")
+pub fn validate_interface_name(name: &str) -> Result<(), NactlError> {
+    if name.trim().is_empty() {
+        return Err(NactlError::invalid_input("Interface name cannot be empty"));
+    }
+
+    if name.len() > MAX_INTERFACE_NAME_LENGTH {
+        return Err(NactlError::invalid_input(format!(
+            "Interface name too long (max {} characters)",
+            MAX_INTERFACE_NAME_LENGTH
+        )));
+    }
+
+    let dangerous_chars = [
+        '\"', '\'', '\\', '\n', '\r', '\0', '`', '$', '|', ';', '&', '<', '>',
+    ];
+    if let Some(c) = name.chars().find(|c| dangerous_chars.contains(c)) {
+        return Err(NactlError::invalid_input(format!(
+            "Interface name contains invalid character: {:?}",
+            c
+        )));
+    }
+
+    Ok(())
+}
+
+/// Validate a ping payload size in bytes
+pub fn validate_ping_size(size: u32) -> Result<(), NactlError> {
+    if size > MAX_PING_SIZE {
+        return Err(NactlError::invalid_arguments(format!(
+            "Packet size must be 0-{} bytes",
+            MAX_PING_SIZE
+        )));
+    }
+    Ok(())
+}
+
+/// Validate an IP time-to-live / hop limit
+pub fn validate_ttl(ttl: u32) -> Result<(), NactlError> {
+    if !(1..=255).contains(&ttl) {
+        return Err(NactlError::invalid_arguments("TTL must be 1-255"));
+    }
+    Ok(())
+}
+
+/// Validate the gap between ping requests in milliseconds
+pub fn validate_ping_interval(interval_ms: u64) -> Result<(), NactlError> {
+    if interval_ms < MIN_PING_INTERVAL_MS {
+        return Err(NactlError::invalid_arguments(format!(
+            "Interval must be at least {} ms",
+            MIN_PING_INTERVAL_MS
+        )));
+    }
+    Ok(())
+}
+
 /// Sanitize a string for use in command arguments (escaping special characters)
 pub fn sanitize_for_command(input: &str) -> String {
     // For Windows, we escape double quotes by doubling them
     input.replace('"', "\"\"")
 }
 
+/// Parse a duration such as `90` (seconds), `30s`, `5m` or `2h`
+pub fn parse_duration(value: &str) -> Result<Duration, NactlError> {
+    let value = value.trim();
+    let (number, unit_secs) = match value.char_indices().last() {
+        Some((idx, 's')) => (&value[..idx], 1),
+        Some((idx, 'm')) => (&value[..idx], 60),
+        Some((idx, 'h')) => (&value[..idx], 3600),
+        _ => (value, 1),
+    };
+
+    let secs = match number.parse::<u64>() {
+        Ok(n) if n > 0 => n.checked_mul(unit_secs),
+        _ => {
+            return Err(NactlError::invalid_arguments(format!(
+                "Invalid duration '{}' (expected e.g. 30s, 5m, 2h)",
+                value
+            )))
+        }
+    };
+
+    match secs {
+        Some(secs) if secs <= MAX_DURATION_SECS => Ok(Duration::from_secs(secs)),
+        _ => Err(NactlError::invalid_arguments(format!(
+            "Duration '{}' is longer than the maximum of {}h",
+            value,
+            MAX_DURATION_SECS / 3600
+        ))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,4 +263,41 @@ mod tests {
         assert!(validate_ip_address("256.1.1.1").is_err());
         assert!(validate_ip_address("not.an.ip").is_err());
     }
+
+    #[test]
+    fn test_validate_interface_name() {
+        assert!(validate_interface_name("Wi-Fi").is_ok());
+        assert!(validate_interface_name("Ethernet 2").is_ok());
+        assert!(validate_interface_name("").is_err());
+        assert!(validate_interface_name("Wi-Fi\" & calc").is_err());
+    }
+
+    #[test]
+    fn test_validate_ping_options() {
+        assert!(validate_ping_size(0).is_ok());
+        assert!(validate_ping_size(1472).is_ok());
+        assert!(validate_ping_size(65501).is_err());
+        assert!(validate_ttl(1).is_ok());
+        assert!(validate_ttl(0).is_err());
+        assert!(validate_ttl(256).is_err());
+        assert!(validate_ping_interval(200).is_ok());
+        assert!(validate_ping_interval(10).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("m").is_err());
+        assert_eq!(
+            parse_duration("720h").unwrap(),
+            Duration::from_secs(MAX_DURATION_SECS)
+        );
+        assert!(parse_duration("721h").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 2)).is_err());
+    }
 }